@@ -0,0 +1,459 @@
+//! Local HTTP/JSON API for ordering and monitoring
+//!
+//! Lets a tablet on the exhibition LAN order drinks and watch the machine.
+//! Nothing here drives hardware itself: orders join the same `OrderQueue`
+//! as the console, the emergency stop counts as a press of the button, and
+//! a reset is carried out by the service loop through the `SafetyMonitor`,
+//! so the API is gated exactly like the physical controls.
+//!
+//! | Method   | Path              | Does                                     |
+//! |----------|-------------------|------------------------------------------|
+//! | `GET`    | `/recipes`        | Recipes that can be ordered              |
+//! | `GET`    | `/orders`         | Order being served and those waiting     |
+//! | `POST`   | `/orders`         | Place an order                           |
+//! | `DELETE` | `/orders/{id}`    | Cancel a waiting order                   |
+//! | `POST`   | `/confirm`        | Answer a recipe's confirmation step      |
+//! | `GET`    | `/status`         | Safety state, temperature, pumps, queue  |
+//! | `POST`   | `/emergency-stop` | Stop, as if the button was pressed       |
+//! | `POST`   | `/reset`          | Reset an emergency stop (bearer token)   |
+//! | `GET`    | `/`               | Live telemetry page                      |
+//! | `GET`    | `/telemetry`      | Telemetry stream (WebSocket)             |
+//!
+//! Anyone on the network can stop the machine; only holders of
+//! `http.reset_token` can start it again.
+
+pub mod telemetry;
+
+use crate::config::Ingredient;
+use crate::control::orders::{Order, OrderId, OrderQueue, OrderRequest};
+use crate::control::{DispenseController, PumpStats};
+use crate::error::HotChocolaBotError;
+use crate::safety::SafetyStates;
+use anyhow::Result;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use telemetry::Telemetry;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// What every request handler can reach
+#[derive(Clone)]
+pub struct ApiState {
+    controller: Arc<DispenseController>,
+    orders: OrderQueue,
+    safety: watch::Receiver<SafetyStates>,
+    reset_token: Option<String>,
+    telemetry: Option<Telemetry>,
+}
+
+impl ApiState {
+    pub fn new(
+        controller: Arc<DispenseController>,
+        orders: OrderQueue,
+        safety: watch::Receiver<SafetyStates>,
+        reset_token: Option<String>,
+    ) -> Self {
+        Self { controller, orders, safety, reset_token, telemetry: None }
+    }
+
+    /// Stream `telemetry` to WebSocket clients
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+}
+
+/// The API's routes over `state`
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/recipes", get(list_recipes))
+        .route("/orders", get(list_orders).post(place_order))
+        .route("/orders/{id}", delete(cancel_order))
+        .route("/confirm", post(confirm))
+        .route("/status", get(status))
+        .route("/emergency-stop", post(emergency_stop))
+        .route("/reset", post(reset))
+        .route("/", get(telemetry::page))
+        .route("/telemetry", get(stream_telemetry))
+        .with_state(state)
+}
+
+/// Serve the API on `bind` in the background
+pub async fn spawn(bind: &str, state: ApiState) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(bind).await
+        .map_err(|e| HotChocolaBotError::Config(format!("Cannot listen on {}: {}", bind, e)))?;
+    info!("HTTP API listening on {}", listener.local_addr()?);
+
+    let app = router(state);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("HTTP API stopped: {:#}", e);
+        }
+    }))
+}
+
+/// A failure as a JSON response carrying its fault code
+pub struct ApiError {
+    status: StatusCode,
+    error: anyhow::Error,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, error: impl Into<anyhow::Error>) -> Self {
+        Self { status, error: error.into() }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        use HotChocolaBotError::*;
+
+        let status = match HotChocolaBotError::find(&error) {
+            Some(UnknownRecipe { .. }) => StatusCode::NOT_FOUND,
+            Some(QueueClosed) => StatusCode::SERVICE_UNAVAILABLE,
+            // Refused by the safety system: try again once it is resolved
+            Some(fault) if fault.code() / 100 == 2 => StatusCode::CONFLICT,
+            Some(fault) if fault.code() / 100 == 4 => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self { status, error }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    code: u16,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: format!("{:#}", self.error),
+            code: HotChocolaBotError::code_of(&self.error),
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Serialize)]
+struct RecipeView {
+    name: String,
+    display_name: String,
+    description: Option<String>,
+    allergens: Vec<String>,
+    target_temp: f32,
+}
+
+async fn list_recipes(State(state): State<ApiState>) -> Json<Vec<RecipeView>> {
+    let recipes = state.controller.recipes().iter()
+        .map(|(name, recipe)| RecipeView {
+            name: name.to_string(),
+            display_name: recipe.display_name.clone().unwrap_or_else(|| name.to_string()),
+            description: recipe.description.clone(),
+            allergens: recipe.allergens.clone(),
+            target_temp: recipe.target_temp,
+        })
+        .collect();
+    Json(recipes)
+}
+
+#[derive(Serialize)]
+struct OrderView {
+    id: OrderId,
+    recipe: String,
+    strength: f32,
+    sweetness: f32,
+    temp: Option<f32>,
+}
+
+impl From<Order> for OrderView {
+    fn from(order: Order) -> Self {
+        let modifiers = order.request.modifiers;
+        Self {
+            id: order.id,
+            recipe: order.request.recipe,
+            strength: modifiers.strength,
+            sweetness: modifiers.sweetness,
+            temp: modifiers.target_temp,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct QueueView {
+    serving: Option<OrderId>,
+    waiting: Vec<OrderView>,
+}
+
+async fn list_orders(State(state): State<ApiState>) -> Json<QueueView> {
+    Json(QueueView {
+        serving: state.controller.current_order_id(),
+        waiting: state.orders.pending().into_iter().map(OrderView::from).collect(),
+    })
+}
+
+/// Body of `POST /orders`; modifiers left out keep the recipe as written
+#[derive(Deserialize)]
+struct PlaceOrder {
+    recipe: String,
+    strength: Option<f32>,
+    sweetness: Option<f32>,
+    temp: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct Placed {
+    id: OrderId,
+    position: Option<usize>,
+}
+
+async fn place_order(
+    State(state): State<ApiState>,
+    Json(body): Json<PlaceOrder>,
+) -> ApiResult<(StatusCode, Json<Placed>)> {
+    let mut request = OrderRequest::new(body.recipe);
+    if let Some(strength) = body.strength {
+        request.modifiers.strength = strength;
+    }
+    if let Some(sweetness) = body.sweetness {
+        request.modifiers.sweetness = sweetness;
+    }
+    request.modifiers.target_temp = body.temp;
+
+    // Refuse what could never be served now rather than when its turn comes
+    request.modifiers.apply(state.controller.recipe(&request.recipe)?)?;
+
+    let id = state.orders.submit(request)?;
+    let position = state.orders.position(id);
+    Ok((StatusCode::CREATED, Json(Placed { id, position })))
+}
+
+async fn cancel_order(State(state): State<ApiState>, Path(id): Path<OrderId>) -> ApiResult<StatusCode> {
+    state.orders.cancel(id).map_err(|e| ApiError::new(StatusCode::CONFLICT, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    confirmed: bool,
+}
+
+async fn confirm(State(state): State<ApiState>, Json(answer): Json<Answer>) -> ApiResult<StatusCode> {
+    state.orders.answer(answer.confirmed).map_err(|e| ApiError::new(StatusCode::CONFLICT, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+struct PumpView {
+    ingredient: Ingredient,
+    running: bool,
+}
+
+#[derive(Serialize)]
+struct StatusView {
+    safety: String,
+    /// `None` if the sensor could not be read
+    temperature: Option<f32>,
+    heater_power: f32,
+    pumps: Vec<PumpView>,
+    pump_stats: PumpStats,
+    /// `None` if no button is fitted
+    emergency_stop_pressed: Option<bool>,
+    serving: Option<OrderId>,
+    waiting: usize,
+}
+
+async fn status(State(state): State<ApiState>) -> Json<StatusView> {
+    let controller = &state.controller;
+    let safety = format!("{:?}", *state.safety.borrow());
+
+    Json(StatusView {
+        safety,
+        temperature: controller.read_temperature().await.ok(),
+        heater_power: controller.heater_power().await,
+        pumps: Ingredient::ALL.into_iter()
+            .map(|ingredient| PumpView { ingredient, running: controller.pump_running(ingredient) })
+            .collect(),
+        pump_stats: controller.get_pump_stats(),
+        emergency_stop_pressed: controller.emergency_stop_pressed().await,
+        serving: controller.current_order_id(),
+        waiting: state.orders.waiting(),
+    })
+}
+
+async fn emergency_stop(State(state): State<ApiState>) -> StatusCode {
+    state.controller.press_emergency_stop();
+    StatusCode::ACCEPTED
+}
+
+#[derive(Serialize)]
+struct ResetView {
+    safety: String,
+}
+
+async fn reset(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult<Json<ResetView>> {
+    let Some(token) = &state.reset_token else {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            anyhow::anyhow!("Resetting over the network is disabled (no http.reset_token)"),
+        ));
+    };
+
+    let presented = headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if presented != Some(token.as_str()) {
+        warn!("Rejected emergency stop reset with a missing or wrong token");
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, anyhow::anyhow!("Missing or wrong reset token")));
+    }
+
+    info!("Emergency stop reset requested over the network");
+    state.orders.request_reset().await?;
+
+    let safety = format!("{:?}", *state.safety.borrow());
+    Ok(Json(ResetView { safety }))
+}
+
+async fn stream_telemetry(State(state): State<ApiState>, upgrade: WebSocketUpgrade) -> ApiResult<Response> {
+    let Some(telemetry) = state.telemetry else {
+        return Err(ApiError::new(StatusCode::NOT_FOUND, anyhow::anyhow!("Telemetry is not being recorded")));
+    };
+    Ok(telemetry::stream(telemetry, upgrade))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::tests::sim_config;
+    use crate::safety::SafetyMonitor;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tokio::time::{sleep, Duration};
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>, token: Option<&str>) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = match bytes.is_empty() {
+            true => Value::Null,
+            false => serde_json::from_slice(&bytes).unwrap(),
+        };
+        (status, body)
+    }
+
+    async fn setup() -> (Arc<DispenseController>, SafetyMonitor, OrderQueue, Router) {
+        let config = sim_config();
+        let controller = Arc::new(DispenseController::new(config.clone()).await.unwrap());
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        let state = ApiState::new(
+            Arc::clone(&controller),
+            orders.clone(),
+            safety.subscribe(),
+            Some("sesame".to_string()),
+        );
+        (controller, safety, orders, router(state))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_order_placed_listed_and_cancelled() {
+        let (_controller, _safety, orders, app) = setup().await;
+
+        let (status, placed) = call(&app, "POST", "/orders", Some(json!({ "recipe": "rich", "strength": 1.5 })), None).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(placed, json!({ "id": 1, "position": 1 }));
+
+        let (status, error) = call(&app, "POST", "/orders", Some(json!({ "recipe": "espresso" })), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], 401);
+
+        let (status, _) = call(&app, "POST", "/orders", Some(json!({ "recipe": "rich", "strength": 9.0 })), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, queue) = call(&app, "GET", "/orders", None, None).await;
+        assert_eq!(queue["waiting"][0]["recipe"], "rich");
+        assert_eq!(queue["waiting"][0]["strength"], 1.5);
+
+        let (status, _) = call(&app, "DELETE", "/orders/1", None, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(orders.waiting(), 0);
+
+        let (status, _) = call(&app, "DELETE", "/orders/1", None, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recipes_and_status() {
+        let (_controller, _safety, _orders, app) = setup().await;
+
+        let (status, recipes) = call(&app, "GET", "/recipes", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(recipes.as_array().unwrap().iter().any(|recipe| recipe["name"] == "standard"));
+
+        let (status, view) = call(&app, "GET", "/status", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(view["safety"], "Safe");
+        let milk = view["pumps"].as_array().unwrap().iter().find(|pump| pump["ingredient"] == "milk").unwrap();
+        assert_eq!(milk["running"], false);
+        assert!(view["pump_stats"]["milk_runtime_ms"].is_u64());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_and_reset_go_through_safety_monitor() {
+        let (controller, mut safety, orders, app) = setup().await;
+
+        let client = async {
+            sleep(Duration::from_secs(10)).await;
+
+            let (status, _) = call(&app, "POST", "/emergency-stop", None, None).await;
+            assert_eq!(status, StatusCode::ACCEPTED);
+            sleep(Duration::from_millis(100)).await;
+
+            // Latched even though nothing was being served
+            let (_, view) = call(&app, "GET", "/status", None, None).await;
+            assert_eq!(view["safety"], "Unsafe");
+
+            let (status, _) = call(&app, "POST", "/reset", None, None).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            let (status, _) = call(&app, "POST", "/reset", None, Some("guess")).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+            let (status, reset) = call(&app, "POST", "/reset", None, Some("sesame")).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(reset["safety"], "Safe");
+
+            orders.close();
+        };
+        let (served, ()) = tokio::join!(controller.run(&mut safety, &orders), client);
+
+        served.unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! Live telemetry over WebSocket
+//!
+//! For workshops where students watch the machine from their own laptops.
+//! The bot already narrates what it does through `tracing`; events tagged
+//! with a `telemetry` field (safety transitions, pump runs, temperature
+//! samples, display contents and pre-flight results) are captured by a
+//! layer, turned into JSON and broadcast to every browser on `/telemetry`.
+//! `GET /` serves a small page that plots them.
+//!
+//! ```json
+//! {"at_ms":5120,"kind":"pump_start","level":"INFO",
+//!  "message":"[MOCK] Milk pump dispensing for 6250ms",
+//!  "fields":{"pump":"Milk","duration_ms":6250}}
+//! ```
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::{Html, IntoResponse, Response};
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::time::Instant;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::field::{Field, Visit};
+use tracing::{debug, Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Field naming the kind of a telemetry event; events without it are not streamed
+const KIND_FIELD: &str = "telemetry";
+
+/// Events held for each client; one that falls further behind skips ahead
+const BACKLOG: usize = 256;
+
+/// Page plotting the stream, bundled into the binary
+const PAGE: &str = include_str!("telemetry.html");
+
+/// One tagged `tracing` event, as sent to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryEvent {
+    /// Milliseconds since telemetry started
+    pub at_ms: u64,
+    /// `transition`, `pump_start`, `pump_stop`, `temperature`, `display` or `safety_check`
+    pub kind: String,
+    pub level: String,
+    pub message: String,
+    /// The event's other fields, e.g. `pump` and `duration_ms`
+    pub fields: Map<String, Value>,
+}
+
+/// Broadcasts tagged events to WebSocket clients
+///
+/// Installed as a `tracing` layer; clones share the same stream.
+#[derive(Clone)]
+pub struct Telemetry {
+    events: broadcast::Sender<TelemetryEvent>,
+    started: Instant,
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(BACKLOG);
+        Self { events, started: Instant::now() }
+    }
+
+    /// Receive every event from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<TelemetryEvent> {
+        self.events.subscribe()
+    }
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Telemetry {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Cheap for untagged events and when nobody is watching
+        if event.metadata().fields().field(KIND_FIELD).is_none() || self.events.receiver_count() == 0 {
+            return;
+        }
+
+        let mut fields = FieldCollector::default();
+        event.record(&mut fields);
+        let Some(kind) = fields.kind else {
+            return;
+        };
+
+        // Only fails if the last client disconnected meanwhile
+        let _ = self.events.send(TelemetryEvent {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            kind,
+            level: event.metadata().level().to_string(),
+            message: fields.message,
+            fields: fields.values,
+        });
+    }
+}
+
+/// Gathers an event's fields as JSON values
+#[derive(Default)]
+struct FieldCollector {
+    kind: Option<String>,
+    message: String,
+    values: Map<String, Value>,
+}
+
+impl FieldCollector {
+    fn insert(&mut self, field: &Field, value: Value) {
+        self.values.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for FieldCollector {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // JSON has no NaN or infinity
+        let value = Number::from_f64(value).map_or(Value::Null, Value::Number);
+        self.insert(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            KIND_FIELD => self.kind = Some(value.to_string()),
+            _ => self.insert(field, value.into()),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            _ => self.insert(field, format!("{:?}", value).into()),
+        }
+    }
+}
+
+/// `GET /`: the plotting page
+pub async fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+/// `GET /telemetry`: upgrade to a WebSocket carrying one JSON event per message
+pub fn stream(telemetry: Telemetry, upgrade: WebSocketUpgrade) -> Response {
+    let events = telemetry.subscribe();
+    upgrade.on_upgrade(move |socket| forward(socket, events)).into_response()
+}
+
+async fn forward(mut socket: WebSocket, mut events: broadcast::Receiver<TelemetryEvent>) {
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(missed)) => {
+                        debug!("Telemetry client fell behind, skipped {} events", missed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let json = match serde_json::to_string(&event) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        debug!("Cannot serialize telemetry event: {}", e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(json.into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                // Clients only listen; anything but a close is ignored
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::tests::sim_config;
+    use crate::control::DispenseController;
+    use crate::safety::SafetyMonitor;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_only_tagged_events_are_streamed() {
+        let telemetry = Telemetry::new();
+        let mut events = telemetry.subscribe();
+        let subscriber = tracing_subscriber::registry().with(telemetry.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("Not for the stream");
+            info!(telemetry = "pump_start", pump = "milk", duration_ms = 6250u64, "milk pump dispensing");
+            info!(telemetry = "temperature", temp = 71.5f32, "Thermostat");
+        });
+
+        let started = events.try_recv().unwrap();
+        assert_eq!(started.kind, "pump_start");
+        assert_eq!(started.message, "milk pump dispensing");
+        assert_eq!(started.fields["pump"], "milk");
+        assert_eq!(started.fields["duration_ms"], 6250);
+        assert!(!started.fields.contains_key("telemetry"));
+
+        let sample = events.try_recv().unwrap();
+        assert_eq!(sample.fields["temp"], 71.5);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_preflight_streams_checks_and_transition() {
+        let telemetry = Telemetry::new();
+        let mut events = telemetry.subscribe();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(telemetry.clone()));
+
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let mut streamed = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            streamed.push(event);
+        }
+        let checks: Vec<_> = streamed.iter().filter(|event| event.kind == "safety_check").collect();
+        assert!(!checks.is_empty());
+        assert!(checks.iter().all(|event| event.fields["passed"] == true));
+        assert!(streamed.iter().any(|event| event.kind == "transition" && event.fields["to"] == "Safe"));
+    }
+}
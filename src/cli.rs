@@ -0,0 +1,505 @@
+//! Command-line interface
+//!
+//! `hotchocolabot [options] [command]`; without a command the bot serves
+//! orders. Every failure ends the process with the exit status for its
+//! class of fault (`HotChocolaBotError::exit_code`), so scripts driving the
+//! bot can tell a hardware fault from a bad recipe or a config error.
+
+use crate::config::{Backend, BotConfig, Ingredient};
+use crate::control::calibration::StdinOperator;
+use crate::control::maintenance::{self, MaintenanceStore, MaintenanceTask};
+use crate::control::orders::{spawn_source, LineOrderSource, OrderQueue, OrderRequest};
+use crate::control::DispenseController;
+use crate::error::HotChocolaBotError;
+use crate::safety::SafetyMonitor;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+#[cfg(feature = "http")]
+use crate::api::telemetry::Telemetry;
+#[cfg(feature = "http")]
+use crate::api::{self, ApiState};
+#[cfg(feature = "tui")]
+use crate::config::DisplayKind;
+#[cfg(feature = "tui")]
+use crate::hardware::factory::ControllerHardware;
+#[cfg(feature = "tui")]
+use crate::hardware::terminal::{Dashboard, LogPane};
+#[cfg(feature = "rpi")]
+use crate::control::orders::ButtonOrderSource;
+
+/// Over-engineered hot chocolate dispenser
+#[derive(Debug, Parser)]
+#[command(name = "hotchocolabot", version, about)]
+pub struct Cli {
+    /// Configuration file; defaults are used if it does not exist
+    #[arg(long, global = true, default_value = "config.toml")]
+    pub config: String,
+
+    /// Hardware backend, overriding the configuration
+    #[arg(long, global = true, value_name = "mock|rpi")]
+    pub backend: Option<Backend>,
+
+    /// Format of log lines, which go to standard error
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Run on simulated hardware and write nothing to disk
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve orders from the console or the terminal dashboard (the default)
+    Serve,
+    /// Make one drink, e.g. `dispense rich strength=1.5`
+    Dispense {
+        /// Recipe name followed by any modifiers
+        #[arg(required = true, num_args = 1..)]
+        order: Vec<String>,
+    },
+    /// Run the pre-flight checks and report each result
+    Preflight,
+    /// Measure a pump's flow rate and save it to the configuration
+    Calibrate {
+        /// Pump to calibrate (cocoa, milk, sugar)
+        pump: Ingredient,
+        /// How long to run the pump, in milliseconds
+        #[arg(long, default_value_t = 5000)]
+        run_ms: u64,
+    },
+    /// Fill each line with its ingredient, ready to serve
+    Prime,
+    /// Flush every line with water
+    Clean,
+    /// Check, print or create the configuration file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Show pump wear and the maintenance due
+    Stats,
+    /// Record maintenance carried out
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommand),
+    /// Exercise every device and report how each responded
+    Selftest,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Check the configuration file loads and passes validation
+    Validate,
+    /// Print the configuration in effect, defaults and overrides applied
+    Show,
+    /// Write a default configuration file
+    Init {
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MaintenanceCommand {
+    /// Acknowledge maintenance done on a pump
+    Ack {
+        /// Pump serviced (cocoa, milk, sugar)
+        pump: Ingredient,
+        /// What was done (tubing, cleaning, calibration)
+        task: MaintenanceTask,
+        /// Free-text note for the audit trail
+        note: Vec<String>,
+    },
+}
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Run the command line given
+pub async fn run(cli: Cli) -> Result<()> {
+    // Config commands must work on a missing or broken file, without hardware
+    if let Some(Command::Config(command)) = &cli.command {
+        return config_command(&cli, command);
+    }
+
+    let config = load_config(&cli)?;
+    let command = cli.command.as_ref().unwrap_or(&Command::Serve);
+
+    // Serving with `display = "terminal"` shows the dashboard, which takes over the log
+    #[cfg(feature = "tui")]
+    let log_pane = (matches!(command, Command::Serve) && config.hardware.display == DisplayKind::Terminal)
+        .then(LogPane::new);
+    #[cfg(feature = "tui")]
+    let log_to = log_pane.clone().map(|pane| BoxMakeWriter::new(move || pane.writer()));
+    #[cfg(not(feature = "tui"))]
+    let log_to = None;
+
+    // The API's telemetry stream is fed from the same events as the log
+    #[cfg(feature = "http")]
+    let telemetry = (matches!(command, Command::Serve) && config.http.enabled).then(Telemetry::new);
+    #[cfg(feature = "http")]
+    let extra = telemetry.clone().map(|telemetry| telemetry.boxed());
+    #[cfg(not(feature = "http"))]
+    let extra = None;
+    init_logging(cli.log_format, log_to, extra);
+
+    info!("HotChocolaBot v{} starting...", env!("CARGO_PKG_VERSION"));
+    if !Path::new(&cli.config).exists() {
+        info!("No config found at {}, using defaults", cli.config);
+    }
+    if cli.dry_run {
+        info!("Dry run: simulated hardware, nothing saved");
+    }
+    info!("Configuration loaded: {:?}", config);
+
+    match command {
+        Command::Stats => return stats(&config),
+        Command::Maintenance(MaintenanceCommand::Ack { pump, task, note }) => {
+            return acknowledge(&cli, &config, *pump, *task, &note.join(" "));
+        }
+        _ => {}
+    }
+
+    // A bad order is refused before any hardware moves
+    let request = match command {
+        Command::Dispense { order } => Some(order.join(" ").parse::<OrderRequest>()?),
+        _ => None,
+    };
+
+    // Initialize safety monitor (CNO - Certified Null Operations)
+    let mut safety_monitor = SafetyMonitor::new(&config.safety)?;
+
+    // Initialize hardware controller
+    let maintenance = match cli.dry_run {
+        true => MaintenanceStore::in_memory(),
+        false => MaintenanceStore::open(&config.maintenance.store_path)?,
+    };
+    #[cfg(feature = "tui")]
+    let mut dashboard = None;
+    #[cfg(feature = "tui")]
+    let controller = match log_pane {
+        Some(pane) => {
+            // The dashboard stands in for the simulated rig's LCD and emergency stop
+            let mut hardware = ControllerHardware::build(&config)?;
+            dashboard = Some(Dashboard::attach(&mut hardware, &config, pane));
+            DispenseController::with_hardware(config.clone(), hardware)
+        }
+        None => DispenseController::new(config.clone()).await?,
+    }
+    .with_maintenance(maintenance);
+    #[cfg(not(feature = "tui"))]
+    let controller = DispenseController::new(config.clone()).await?
+        .with_maintenance(maintenance);
+    let controller = Arc::new(controller);
+
+    // Status LED follows the safety state for as long as the bot runs
+    let _status_led = controller.show_safety_state(safety_monitor.subscribe());
+
+    if let Command::Preflight = command {
+        let passed = safety_monitor.run_preflight_checks(&controller).await?;
+        for result in safety_monitor.last_preflight() {
+            let mark = if result.passed { "PASS" } else { "FAIL" };
+            println!("{} {:<18} {:?}: {}", mark, result.check, result.severity, result.message);
+        }
+        return match passed {
+            true => Ok(()),
+            false => Err(safety_monitor.preflight_error()),
+        };
+    }
+
+    // Pre-flight safety checks
+    if !safety_monitor.run_preflight_checks(&controller).await? {
+        error!("Pre-flight safety checks failed. Aborting.");
+        let error = safety_monitor.preflight_error();
+        controller.show_fault(&error).await?;
+        return Err(error);
+    }
+
+    info!("Safety checks passed. System ready.");
+
+    match command {
+        Command::Dispense { .. } => {
+            let request = request.context("Dispense needs an order")?;
+
+            // Confirmation steps are answered on the console
+            let orders = OrderQueue::new();
+            let _console = spawn_source(Box::new(LineOrderSource::stdin()), orders.clone());
+            controller.serve_request(request, &orders, &mut safety_monitor).await?;
+        }
+        Command::Calibrate { pump, run_ms } => {
+            let mut operator = StdinOperator::new();
+            let calibration = controller
+                .calibrate_pump(*pump, *run_ms, &mut operator, &mut safety_monitor)
+                .await?;
+            println!("{} pump: {:.2} {}/s", pump, calibration.flow_rate, calibration.unit);
+
+            if cli.dry_run {
+                info!("Dry run: calibration not saved");
+            } else {
+                BotConfig::save_calibration(&cli.config, *pump, &calibration)?;
+                info!("Saved {} pump calibration to {}", pump, cli.config);
+            }
+        }
+        Command::Prime => {
+            let mut operator = StdinOperator::new();
+            controller.prime(&mut operator, &mut safety_monitor).await?;
+        }
+        Command::Clean => {
+            let mut operator = StdinOperator::new();
+            controller.clean(&mut operator, &mut safety_monitor).await?;
+        }
+        Command::Selftest => {
+            let results = controller.self_test(&mut safety_monitor).await?;
+            for result in &results {
+                let mark = if result.passed { "PASS" } else { "FAIL" };
+                let device = result.device.to_string();
+                println!("{} {:<20} {}", mark, device, result.detail);
+            }
+            if let Some(failed) = results.iter().find(|result| !result.passed) {
+                return Err(HotChocolaBotError::hardware(failed.device, &failed.detail).into());
+            }
+        }
+        _ => {
+            let orders = OrderQueue::new();
+
+            // Customers order with the buttons on the rig
+            #[cfg(feature = "rpi")]
+            let _buttons = match config.hardware.backend {
+                Backend::Rpi if !config.hardware.order_buttons.is_empty() => {
+                    let buttons = ButtonOrderSource::gpio(&config.hardware)?;
+                    Some(spawn_source(Box::new(buttons), orders.clone()))
+                }
+                _ => None,
+            };
+            #[cfg(not(feature = "rpi"))]
+            if !config.hardware.order_buttons.is_empty() {
+                tracing::warn!("order_buttons are set, but this build lacks the `rpi` feature");
+            }
+
+            // Tablets on the LAN order through the same queue
+            #[cfg(feature = "http")]
+            let _api = match config.http.enabled {
+                true => {
+                    let state = ApiState::new(
+                        Arc::clone(&controller),
+                        orders.clone(),
+                        safety_monitor.subscribe(),
+                        config.http.reset_token.clone(),
+                    );
+                    let state = match telemetry {
+                        Some(telemetry) => state.with_telemetry(telemetry),
+                        None => state,
+                    };
+                    Some(api::spawn(&config.http.bind, state).await?)
+                }
+                false => None,
+            };
+            #[cfg(not(feature = "http"))]
+            if config.http.enabled {
+                tracing::warn!("http.enabled is set, but this build lacks the `http` feature");
+            }
+
+            // With the dashboard up, orders and emergency stops come from its keys
+            #[cfg(feature = "tui")]
+            if let Some(mut dashboard) = dashboard {
+                let safety = safety_monitor.subscribe();
+                let (served, shown) = tokio::join!(
+                    controller.run(&mut safety_monitor, &orders),
+                    dashboard.run(&controller, safety, &orders),
+                );
+                shown?;
+                served?;
+
+                info!("HotChocolaBot shutting down gracefully.");
+                return Ok(());
+            }
+
+            // Orders are typed on the console, one per line, e.g. "rich strength=1.5"
+            let console = spawn_source(Box::new(LineOrderSource::stdin()), orders.clone());
+
+            // Stop taking orders when the console closes or on Ctrl-C
+            let closer = orders.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = console => {}
+                    _ = tokio::signal::ctrl_c() => info!("Interrupted"),
+                }
+                closer.close();
+            });
+
+            // Main control loop
+            controller.run(&mut safety_monitor, &orders).await?;
+        }
+    }
+
+    info!("HotChocolaBot shutting down gracefully.");
+    Ok(())
+}
+
+/// Configuration from `--config`, or defaults if there is no such file, with overrides applied
+fn load_config(cli: &Cli) -> Result<BotConfig> {
+    let mut config = match Path::new(&cli.config).exists() {
+        true => BotConfig::load(&cli.config)?,
+        false => BotConfig::default(),
+    };
+
+    if let Some(backend) = cli.backend {
+        config.hardware.backend = backend;
+    }
+    if cli.dry_run {
+        config.hardware.backend = Backend::Mock;
+    }
+
+    Ok(config)
+}
+
+/// A `tracing` layer fed alongside the log lines
+type ExtraLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Send log lines to standard error, or to `writer` if given, and events to `extra`
+fn init_logging(format: LogFormat, writer: Option<BoxMakeWriter>, extra: Option<ExtraLayer>) {
+    let ansi = writer.is_none();
+    let writer = writer.unwrap_or_else(|| BoxMakeWriter::new(std::io::stderr));
+    let lines = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_ansi(ansi).with_writer(writer).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().with_writer(writer).boxed(),
+    };
+
+    let mut layers = vec![lines];
+    layers.extend(extra);
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(EnvFilter::new("hotchocolabot=debug"))
+        .init();
+}
+
+fn config_command(cli: &Cli, command: &ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Validate => {
+            BotConfig::load(&cli.config)?;
+            println!("{} is valid", cli.config);
+        }
+        ConfigCommand::Show => {
+            let config = load_config(cli)?;
+            print!("{}", toml::to_string_pretty(&config).context("Failed to serialize config")?);
+        }
+        ConfigCommand::Init { force } => {
+            if Path::new(&cli.config).exists() && !force {
+                return Err(HotChocolaBotError::Config(format!(
+                    "{} already exists (use --force to overwrite)",
+                    cli.config
+                )).into());
+            }
+
+            let config = BotConfig::default();
+            if cli.dry_run {
+                print!("{}", toml::to_string_pretty(&config).context("Failed to serialize config")?);
+            } else {
+                config.save(&cli.config)?;
+                println!("Wrote default configuration to {}", cli.config);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Show pump wear and due maintenance
+fn stats(config: &BotConfig) -> Result<()> {
+    let store = MaintenanceStore::open(&config.maintenance.store_path)?;
+
+    for pump in Ingredient::ALL {
+        let record = store.log().get(pump);
+        println!(
+            "{:<6} tubing {:.2}h / {} runs (lifetime {:.2}h / {} runs)",
+            pump,
+            record.tubing_runtime_ms as f32 / 3_600_000.0,
+            record.tubing_cycles,
+            record.total_runtime_ms as f32 / 3_600_000.0,
+            record.total_cycles,
+        );
+    }
+    for due in store.due(&config.maintenance, maintenance::now()) {
+        println!("DUE: {}", due);
+    }
+
+    Ok(())
+}
+
+/// Record maintenance done on a pump
+fn acknowledge(cli: &Cli, config: &BotConfig, pump: Ingredient, task: MaintenanceTask, note: &str) -> Result<()> {
+    let mut store = MaintenanceStore::open(&config.maintenance.store_path)?;
+    let entry = store.acknowledge(pump, task, note, maintenance::now());
+
+    if cli.dry_run {
+        info!("Dry run: maintenance log not saved");
+    } else {
+        store.save()?;
+    }
+
+    println!(
+        "Recorded {} {} (tubing was at {} runs)",
+        entry.pump, entry.task, entry.tubing_cycles
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_options_after_subcommand() {
+        let cli = Cli::try_parse_from([
+            "hotchocolabot", "dispense", "rich", "strength=1.5", "--backend", "mock", "--dry-run",
+        ]).unwrap();
+
+        assert_eq!(cli.backend, Some(Backend::Mock));
+        assert!(cli.dry_run);
+        match cli.command {
+            Some(Command::Dispense { order }) => assert_eq!(order.join(" "), "rich strength=1.5"),
+            other => panic!("expected dispense, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bad_arguments_rejected() {
+        assert!(Cli::try_parse_from(["hotchocolabot", "--backend", "arduino"]).is_err());
+        assert!(Cli::try_parse_from(["hotchocolabot", "calibrate", "coffee"]).is_err());
+        assert!(Cli::try_parse_from(["hotchocolabot", "--log-format", "xml"]).is_err());
+
+        let cli = Cli::try_parse_from(["hotchocolabot"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.config, "config.toml");
+    }
+
+    #[test]
+    fn test_dry_run_forces_simulation() {
+        let cli = Cli::try_parse_from([
+            "hotchocolabot", "--config", "/nonexistent/config.toml", "--backend", "rpi", "--dry-run",
+        ]).unwrap();
+
+        assert_eq!(load_config(&cli).unwrap().hardware.backend, Backend::Mock);
+    }
+}
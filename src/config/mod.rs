@@ -3,8 +3,16 @@
 //! Handles loading and validation of system configuration from TOML files.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
+use std::ops::Index;
+use std::str::FromStr;
 use anyhow::{Result, Context};
+use crate::error::HotChocolaBotError;
+
+mod steps;
+pub use steps::{Dose, Step};
 
 /// Main bot configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,13 +26,37 @@ pub struct BotConfig {
     /// Recipe configuration
     pub recipes: RecipeConfig,
 
+    /// Heater control loop configuration
+    #[serde(default)]
+    pub heater: HeaterConfig,
+
     /// Educational mode settings
     pub education: EducationConfig,
+
+    /// Maintenance reminders
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+
+    /// Ingredient reservoirs
+    #[serde(default)]
+    pub reservoirs: Reservoirs,
+
+    /// Clean-in-place and priming cycles
+    #[serde(default)]
+    pub cleaning: CleaningConfig,
+
+    /// Network API for ordering and monitoring
+    #[serde(default)]
+    pub http: HttpConfig,
 }
 
 /// Hardware pin assignments and settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HardwareConfig {
+    /// Which device implementations to drive
+    #[serde(default)]
+    pub backend: Backend,
+
     /// GPIO pin for cocoa pump
     pub cocoa_pump_pin: u8,
 
@@ -40,11 +72,313 @@ pub struct HardwareConfig {
     /// I2C address for LCD display
     pub lcd_addr: u8,
 
+    /// Display fitted to the rig
+    #[serde(default)]
+    pub display: DisplayKind,
+
+    /// I2C address of an SSD1306 OLED, when `display = "ssd1306"`
+    #[serde(default = "default_oled_addr")]
+    pub oled_addr: u8,
+
+    /// LCD size in character rows and columns (16x2 and 20x4 are common)
+    #[serde(default = "default_lcd_rows")]
+    pub lcd_rows: u8,
+    #[serde(default = "default_lcd_cols")]
+    pub lcd_cols: u8,
+
     /// GPIO pin for emergency stop button
     pub emergency_stop_pin: u8,
 
     /// GPIO pin for LED status indicator
     pub status_led_pin: u8,
+
+    /// GPIO pin driving the heater solid state relay
+    #[serde(default = "default_heater_pin")]
+    pub heater_pin: u8,
+
+    /// GPIO pin switching the stirrer motor, if one is fitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mixer_pin: Option<u8>,
+
+    /// GPIO pin switching a dedicated water pump used to flush the lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub water_pump_pin: Option<u8>,
+
+    /// Push buttons on the rig that each order one recipe
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order_buttons: Vec<OrderButtonConfig>,
+
+    /// Per-pump flow rate calibration
+    #[serde(default)]
+    pub calibration: PumpCalibrations,
+
+    /// I2C address for the supply monitoring ADC
+    #[serde(default = "default_adc_addr")]
+    pub adc_addr: u8,
+
+    /// ADC channel wired to the supply voltage divider
+    #[serde(default)]
+    pub supply_adc_channel: u8,
+
+    /// Supply voltage divider ratio (supply volts per ADC volt)
+    #[serde(default = "default_supply_divider_ratio")]
+    pub supply_divider_ratio: f32,
+}
+
+/// A push button wired between a GPIO pin and ground
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderButtonConfig {
+    pub pin: u8,
+    /// Name of the recipe in `[recipes]` a press orders
+    pub recipe: String,
+}
+
+/// Kind of display the rig's status messages go to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayKind {
+    /// HD44780 character LCD with a PCF8574 I2C backpack
+    #[default]
+    Lcd,
+    /// 128x64 SSD1306 OLED on I2C
+    Ssd1306,
+    /// Dashboard drawn in the terminal (`tui` feature, simulated rig only)
+    Terminal,
+}
+
+/// Hardware backend selected at runtime
+///
+/// Each backend is only available if the matching cargo feature was built in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Simulated devices (`sim` feature)
+    Mock,
+    /// Raspberry Pi GPIO and I2C devices (`rpi` feature)
+    Rpi,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "mock" => Ok(Backend::Mock),
+            "rpi" => Ok(Backend::Rpi),
+            other => Err(HotChocolaBotError::Config(format!("Unknown backend '{}' (mock, rpi)", other)).into()),
+        }
+    }
+}
+
+impl Default for Backend {
+    /// Real hardware when built for the Pi, simulation otherwise
+    fn default() -> Self {
+        if cfg!(feature = "rpi") {
+            Backend::Rpi
+        } else {
+            Backend::Mock
+        }
+    }
+}
+
+fn default_heater_pin() -> u8 {
+    25
+}
+
+fn default_oled_addr() -> u8 {
+    0x3C
+}
+
+fn default_lcd_rows() -> u8 {
+    2
+}
+
+fn default_lcd_cols() -> u8 {
+    16
+}
+
+fn default_adc_addr() -> u8 {
+    0x49
+}
+
+fn default_supply_divider_ratio() -> f32 {
+    4.0
+}
+
+/// Ingredients handled by the dispenser, one pump each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ingredient {
+    Cocoa,
+    Milk,
+    Sugar,
+}
+
+impl Ingredient {
+    /// All ingredients in dispense order
+    pub const ALL: [Ingredient; 3] = [Ingredient::Milk, Ingredient::Cocoa, Ingredient::Sugar];
+
+    /// Lowercase name used in logs and config
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ingredient::Cocoa => "cocoa",
+            Ingredient::Milk => "milk",
+            Ingredient::Sugar => "sugar",
+        }
+    }
+}
+
+impl fmt::Display for Ingredient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Ingredient {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Ingredient::ALL.into_iter()
+            .find(|ingredient| ingredient.name() == name)
+            .with_context(|| format!("Unknown pump '{}' (cocoa, milk, sugar)", name))
+    }
+}
+
+/// Unit a pump is calibrated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DoseUnit {
+    /// Millilitres (liquids)
+    Ml,
+    /// Grams (powders)
+    G,
+}
+
+impl fmt::Display for DoseUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DoseUnit::Ml => f.write_str("ml"),
+            DoseUnit::G => f.write_str("g"),
+        }
+    }
+}
+
+/// Flow rate calibration for a single pump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PumpCalibration {
+    /// Amount delivered per second of pump runtime (ml/s or g/s)
+    pub flow_rate: f32,
+
+    /// Unit of `flow_rate` and of recipe amounts for this pump
+    #[serde(default = "default_dose_unit")]
+    pub unit: DoseUnit,
+}
+
+fn default_dose_unit() -> DoseUnit {
+    DoseUnit::Ml
+}
+
+impl PumpCalibration {
+    /// Derive a calibration from a timed test run and the measured output
+    pub fn from_measurement(run_ms: u64, measured: f32, unit: DoseUnit) -> Result<Self> {
+        if run_ms == 0 || measured <= 0.0 {
+            return Err(HotChocolaBotError::Config(
+                "Calibration needs a positive run time and measured amount".to_string()
+            ).into());
+        }
+
+        Ok(Self {
+            flow_rate: measured / (run_ms as f32 / 1000.0),
+            unit,
+        })
+    }
+
+    /// Pump runtime in milliseconds needed to deliver `amount`
+    pub fn duration_ms(&self, amount: f32) -> u64 {
+        (amount / self.flow_rate * 1000.0).round() as u64
+    }
+
+    /// Amount delivered by `duration_ms` of pump runtime
+    pub fn amount(&self, duration_ms: u64) -> f32 {
+        self.flow_rate * duration_ms as f32 / 1000.0
+    }
+}
+
+/// Calibration for every pump, stored under `[hardware.calibration]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PumpCalibrations {
+    pub cocoa: PumpCalibration,
+    pub milk: PumpCalibration,
+    pub sugar: PumpCalibration,
+}
+
+impl PumpCalibrations {
+    /// Calibration for the given ingredient's pump
+    pub fn get(&self, ingredient: Ingredient) -> &PumpCalibration {
+        match ingredient {
+            Ingredient::Cocoa => &self.cocoa,
+            Ingredient::Milk => &self.milk,
+            Ingredient::Sugar => &self.sugar,
+        }
+    }
+}
+
+impl Default for PumpCalibrations {
+    /// Nominal rates from the assembly guide's reference volumes
+    fn default() -> Self {
+        Self {
+            cocoa: PumpCalibration { flow_rate: 12.5, unit: DoseUnit::Ml },
+            milk: PumpCalibration { flow_rate: 25.0, unit: DoseUnit::Ml },
+            sugar: PumpCalibration { flow_rate: 12.5, unit: DoseUnit::Ml },
+        }
+    }
+}
+
+/// One ingredient reservoir, in its pump's calibrated unit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReservoirConfig {
+    /// Amount held when full
+    pub capacity: f32,
+
+    /// Amount left unused so the pump never runs dry
+    #[serde(default)]
+    pub reserve: f32,
+
+    /// GPIO pin of a float switch at the low mark (optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_pin: Option<u8>,
+}
+
+/// Every ingredient reservoir, stored under `[reservoirs]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reservoirs {
+    pub cocoa: ReservoirConfig,
+    pub milk: ReservoirConfig,
+    pub sugar: ReservoirConfig,
+}
+
+impl Reservoirs {
+    /// Reservoir for the given ingredient
+    pub fn get(&self, ingredient: Ingredient) -> &ReservoirConfig {
+        match ingredient {
+            Ingredient::Cocoa => &self.cocoa,
+            Ingredient::Milk => &self.milk,
+            Ingredient::Sugar => &self.sugar,
+        }
+    }
+}
+
+impl Default for Reservoirs {
+    /// Bottles from the assembly guide
+    fn default() -> Self {
+        let bottle = |capacity| ReservoirConfig { capacity, reserve: 0.05 * capacity, level_pin: None };
+
+        Self {
+            cocoa: bottle(500.0),
+            milk: bottle(2000.0),
+            sugar: bottle(500.0),
+        }
+    }
 }
 
 /// Safety system configuration (CNO - Certified Null Operations)
@@ -59,7 +393,9 @@ pub struct SafetyConfig {
     /// Maximum pump runtime in seconds
     pub max_pump_runtime: u64,
 
-    /// Timeout for operations in seconds
+    /// Deadline in seconds for a whole dispense, heating included
+    ///
+    /// Time spent waiting for the operator to confirm a step is not counted.
     pub operation_timeout: u64,
 
     /// Enable verbose safety logging
@@ -67,35 +403,350 @@ pub struct SafetyConfig {
 
     /// Emergency stop button enabled
     pub emergency_stop_enabled: bool,
+
+    /// Thermal watchdog sample interval in milliseconds
+    #[serde(default = "default_thermal_check_interval_ms")]
+    pub thermal_check_interval_ms: u64,
+
+    /// Fastest physically plausible temperature rise in °C per second
+    #[serde(default = "default_max_heating_rate")]
+    pub max_heating_rate: f32,
+
+    /// Minimum rise in °C expected over `heating_check_window` at high heater power
+    #[serde(default = "default_min_heating_rise")]
+    pub min_heating_rise: f32,
+
+    /// Window in seconds over which heating progress is checked
+    #[serde(default = "default_heating_check_window")]
+    pub heating_check_window: u64,
+
+    /// Seconds of identical readings while heating before the sensor counts as stuck
+    #[serde(default = "default_stuck_sensor_timeout")]
+    pub stuck_sensor_timeout: u64,
+
+    /// Lowest acceptable supply voltage for the pre-flight check
+    #[serde(default = "default_min_supply_voltage")]
+    pub min_supply_voltage: f32,
+
+    /// Highest acceptable supply voltage for the pre-flight check
+    #[serde(default = "default_max_supply_voltage")]
+    pub max_supply_voltage: f32,
+
+    /// Length of the pre-flight test pulse per pump in milliseconds (0 disables)
+    #[serde(default = "default_preflight_pump_pulse_ms")]
+    pub preflight_pump_pulse_ms: u64,
+}
+
+fn default_thermal_check_interval_ms() -> u64 {
+    1000
+}
+
+fn default_max_heating_rate() -> f32 {
+    5.0
+}
+
+fn default_min_heating_rise() -> f32 {
+    1.0
+}
+
+fn default_heating_check_window() -> u64 {
+    60
+}
+
+fn default_stuck_sensor_timeout() -> u64 {
+    30
+}
+
+fn default_min_supply_voltage() -> f32 {
+    11.0
+}
+
+fn default_max_supply_voltage() -> f32 {
+    13.5
+}
+
+fn default_preflight_pump_pulse_ms() -> u64 {
+    100
+}
+
+/// Heater PID control configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaterConfig {
+    /// Proportional gain (power fraction per °C of error)
+    pub kp: f32,
+
+    /// Integral gain (power fraction per °C·s of accumulated error)
+    pub ki: f32,
+
+    /// Derivative gain (power fraction per °C/s of temperature change)
+    pub kd: f32,
+
+    /// Control loop sample interval in milliseconds
+    pub sample_interval_ms: u64,
+
+    /// Band around the target (±°C) considered "at temperature"
+    pub tolerance: f32,
+
+    /// Maximum time in seconds allowed to reach the target temperature
+    ///
+    /// A heat step gets less if less of `SafetyConfig::operation_timeout` is left.
+    pub max_heat_time: u64,
+
+    /// SSR time-proportioning window in milliseconds
+    pub pwm_window_ms: u64,
+}
+
+impl Default for HeaterConfig {
+    fn default() -> Self {
+        Self {
+            kp: 0.08,
+            ki: 0.01,
+            kd: 0.0,
+            sample_interval_ms: 500,
+            tolerance: 1.5,
+            max_heat_time: 600,
+            pwm_window_ms: 2000,
+        }
+    }
+}
+
+/// Maintenance tracking; a threshold of 0 disables that reminder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// File holding pump usage and the maintenance audit trail
+    pub store_path: String,
+
+    /// Pump runtime in hours before its tubing should be replaced
+    pub tubing_hours: f32,
+
+    /// Pump runs before its tubing should be replaced
+    pub tubing_cycles: u64,
+
+    /// Hours allowed between cleans of each pump
+    pub cleaning_interval_hours: u64,
+
+    /// Days allowed between calibrations of each pump
+    pub calibration_interval_days: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            store_path: "maintenance.toml".to_string(),
+            tubing_hours: 25.0,
+            tubing_cycles: 2000,
+            cleaning_interval_hours: 24,
+            calibration_interval_days: 30,
+        }
+    }
+}
+
+/// Clean-in-place and priming cycles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleaningConfig {
+    /// Flush pulses run on each line during a clean, in milliseconds
+    pub flush_pulses_ms: Vec<u64>,
+
+    /// Pause between flush pulses, letting the line drain
+    pub pause_ms: u64,
+
+    /// Pump runtime that fills an empty line up to the outlet
+    pub prime_ms: u64,
+
+    /// Hours a clean may be overdue (past `maintenance.cleaning_interval_hours`)
+    /// before drinks are refused; 0 refuses as soon as it is due
+    pub grace_hours: u64,
+}
+
+impl Default for CleaningConfig {
+    fn default() -> Self {
+        Self {
+            flush_pulses_ms: vec![3000, 3000, 5000],
+            pause_ms: 2000,
+            prime_ms: 3000,
+            grace_hours: 12,
+        }
+    }
 }
 
-/// Recipe definitions
+/// Local HTTP/JSON API (`http` feature)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Serve the API while serving orders
+    pub enabled: bool,
+
+    /// Address and port to listen on
+    pub bind: String,
+
+    /// Bearer token required to reset an emergency stop; without one the
+    /// API cannot reset it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_token: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "0.0.0.0:8080".to_string(),
+            reset_token: None,
+        }
+    }
+}
+
+/// Recipe definitions, keyed by the name used to order them
+///
+/// Each `[recipes.<name>]` table in the config file is one recipe, so new
+/// drinks can be added without recompiling.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct RecipeConfig {
-    /// Standard hot chocolate recipe
-    pub standard: Recipe,
+    recipes: BTreeMap<String, Recipe>,
+}
+
+impl RecipeConfig {
+    /// Look up a recipe by name
+    pub fn get(&self, name: &str) -> Option<&Recipe> {
+        self.recipes.get(name)
+    }
+
+    /// Add or replace a recipe
+    pub fn insert(&mut self, name: impl Into<String>, recipe: Recipe) {
+        self.recipes.insert(name.into(), recipe);
+    }
+
+    /// Recipe names in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.recipes.keys().map(String::as_str)
+    }
+
+    /// Recipes with their names, in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Recipe)> {
+        self.recipes.iter().map(|(name, recipe)| (name.as_str(), recipe))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+}
 
-    /// Light recipe (less cocoa)
-    pub light: Recipe,
+impl Index<&str> for RecipeConfig {
+    type Output = Recipe;
 
-    /// Rich recipe (more cocoa)
-    pub rich: Recipe,
+    /// Panics if there is no recipe called `name`
+    fn index(&self, name: &str) -> &Recipe {
+        self.get(name).unwrap_or_else(|| panic!("No recipe named '{}'", name))
+    }
 }
 
 /// Single recipe definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Amounts are in the unit the pump is calibrated in (ml, or g for powder).
+/// The legacy `*_ms` fields are still accepted and are used for an
+/// ingredient when no amount is given.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Recipe {
-    /// Cocoa dispense time in milliseconds
-    pub cocoa_ms: u64,
+    /// Cocoa amount (ml or g)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cocoa: Option<f32>,
+
+    /// Milk amount (ml)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub milk: Option<f32>,
+
+    /// Sugar amount (ml or g)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sugar: Option<f32>,
 
-    /// Milk dispense time in milliseconds
-    pub milk_ms: u64,
+    /// Cocoa dispense time in milliseconds (legacy)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cocoa_ms: Option<u64>,
 
-    /// Sugar dispense time in milliseconds
-    pub sugar_ms: u64,
+    /// Milk dispense time in milliseconds (legacy)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub milk_ms: Option<u64>,
+
+    /// Sugar dispense time in milliseconds (legacy)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sugar_ms: Option<u64>,
 
     /// Target temperature in Celsius
     pub target_temp: f32,
+
+    /// Name shown on the display and in menus (defaults to the recipe key)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    /// One-line description for menus
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Allergens in the drink, e.g. "milk"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allergens: Vec<String>,
+
+    /// Cup size in ml; liquid ingredients must fit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cup_size_ml: Option<f32>,
+
+    /// Program to run instead of the default heat, milk, cocoa, sugar sequence
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+}
+
+impl Recipe {
+    /// Amount of an ingredient, if given volumetrically
+    pub fn amount(&self, ingredient: Ingredient) -> Option<f32> {
+        match ingredient {
+            Ingredient::Cocoa => self.cocoa,
+            Ingredient::Milk => self.milk,
+            Ingredient::Sugar => self.sugar,
+        }
+    }
+
+    /// Legacy raw pump time for an ingredient, if given
+    pub fn legacy_ms(&self, ingredient: Ingredient) -> Option<u64> {
+        match ingredient {
+            Ingredient::Cocoa => self.cocoa_ms,
+            Ingredient::Milk => self.milk_ms,
+            Ingredient::Sugar => self.sugar_ms,
+        }
+    }
+
+    /// Pump runtime for an ingredient, converting amounts via the pump calibration
+    #[cfg(test)]
+    pub fn dispense_ms(&self, ingredient: Ingredient, calibration: &PumpCalibrations) -> u64 {
+        match (self.amount(ingredient), self.legacy_ms(ingredient)) {
+            (Some(amount), _) => calibration.get(ingredient).duration_ms(amount),
+            (None, Some(ms)) => ms,
+            (None, None) => 0,
+        }
+    }
+
+    /// Steps to run for this recipe
+    ///
+    /// Recipes without `steps` heat to `target_temp`, then add each
+    /// ingredient they list in turn.
+    pub fn program(&self) -> Vec<Step> {
+        if !self.steps.is_empty() {
+            return self.steps.clone();
+        }
+
+        let doses = Ingredient::ALL.into_iter()
+            .filter(|&ingredient| self.amount(ingredient).is_some() || self.legacy_ms(ingredient).is_some())
+            .map(|ingredient| Step::Dispense(Dose {
+                ingredient,
+                amount: self.amount(ingredient),
+                ms: self.legacy_ms(ingredient),
+            }));
+
+        std::iter::once(Step::Heat { temp: self.target_temp })
+            .chain(doses)
+            .collect()
+    }
 }
 
 /// Educational mode configuration
@@ -118,10 +769,10 @@ impl BotConfig {
     /// Load configuration from TOML file
     pub fn load(path: &str) -> Result<Self> {
         let contents = fs::read_to_string(path)
-            .context("Failed to read config file")?;
+            .map_err(|e| HotChocolaBotError::Config(format!("Failed to read {}: {}", path, e)))?;
 
         let config: BotConfig = toml::from_str(&contents)
-            .context("Failed to parse config file")?;
+            .map_err(|e| HotChocolaBotError::Config(format!("Failed to parse {}: {}", path, e)))?;
 
         config.validate()?;
         Ok(config)
@@ -129,6 +780,11 @@ pub fn load(path: &str) -> Result<Self> {
 
     /// Validate configuration values
     fn validate(&self) -> Result<()> {
+        self.check_values()
+            .map_err(|e| HotChocolaBotError::Config(format!("{:#}", e)).into())
+    }
+
+    fn check_values(&self) -> Result<()> {
         if self.safety.max_temperature <= self.safety.min_temperature {
             anyhow::bail!("max_temperature must be greater than min_temperature");
         }
@@ -137,6 +793,123 @@ fn validate(&self) -> Result<()> {
             anyhow::bail!("max_pump_runtime must be greater than 0");
         }
 
+        if self.safety.operation_timeout == 0 {
+            anyhow::bail!("operation_timeout must be greater than 0");
+        }
+
+        if self.http.bind.parse::<std::net::SocketAddr>().is_err() {
+            anyhow::bail!("http.bind '{}' is not an address and port", self.http.bind);
+        }
+
+        if self.safety.thermal_check_interval_ms == 0 {
+            anyhow::bail!("thermal_check_interval_ms must be greater than 0");
+        }
+
+        if self.safety.max_heating_rate <= 0.0 {
+            anyhow::bail!("max_heating_rate must be greater than 0");
+        }
+
+        if self.safety.min_supply_voltage >= self.safety.max_supply_voltage {
+            anyhow::bail!("max_supply_voltage must be greater than min_supply_voltage");
+        }
+
+        if self.hardware.supply_adc_channel > 3 {
+            anyhow::bail!("supply_adc_channel must be between 0 and 3");
+        }
+
+        // The HD44780 has 80 characters of display memory across at most 4 rows
+        let (rows, cols) = (self.hardware.lcd_rows, self.hardware.lcd_cols);
+        if !(1..=4).contains(&rows) || cols == 0 || rows as u16 * cols as u16 > 80 {
+            anyhow::bail!("LCD must have 1 to 4 rows and at most 80 characters, not {}x{}", cols, rows);
+        }
+
+        if self.hardware.supply_divider_ratio <= 0.0 {
+            anyhow::bail!("supply_divider_ratio must be greater than 0");
+        }
+
+        for ingredient in Ingredient::ALL {
+            if self.hardware.calibration.get(ingredient).flow_rate <= 0.0 {
+                anyhow::bail!("{} pump flow_rate must be greater than 0", ingredient);
+            }
+
+            let reservoir = self.reservoirs.get(ingredient);
+            if reservoir.capacity <= 0.0 {
+                anyhow::bail!("{} reservoir capacity must be greater than 0", ingredient);
+            }
+            if !(0.0..reservoir.capacity).contains(&reservoir.reserve) {
+                anyhow::bail!("{} reservoir reserve must be at least 0 and below its capacity", ingredient);
+            }
+        }
+
+        let max_pump_ms = self.safety.max_pump_runtime * 1000;
+        if self.cleaning.flush_pulses_ms.is_empty() {
+            anyhow::bail!("cleaning flush_pulses_ms must list at least one pulse");
+        }
+        if self.cleaning.flush_pulses_ms.iter().any(|&ms| ms == 0 || ms > max_pump_ms) {
+            anyhow::bail!("cleaning flush pulses must be between 1ms and max_pump_runtime");
+        }
+        if self.cleaning.prime_ms > max_pump_ms {
+            anyhow::bail!("cleaning prime_ms must not exceed max_pump_runtime");
+        }
+
+        if self.recipes.is_empty() {
+            anyhow::bail!("at least one recipe must be defined");
+        }
+
+        for (name, recipe) in self.recipes.iter() {
+            self.check_recipe(recipe)
+                .with_context(|| format!("Recipe '{}'", name))?;
+        }
+
+        for button in &self.hardware.order_buttons {
+            if self.recipes.get(&button.recipe).is_none() {
+                anyhow::bail!("order button on GPIO pin {} orders unknown recipe '{}'", button.pin, button.recipe);
+            }
+        }
+
+        if self.heater.kp < 0.0 || self.heater.ki < 0.0 || self.heater.kd < 0.0 {
+            anyhow::bail!("heater PID gains must not be negative");
+        }
+
+        if self.heater.sample_interval_ms == 0 {
+            anyhow::bail!("heater sample_interval_ms must be greater than 0");
+        }
+
+        if self.heater.tolerance <= 0.0 {
+            anyhow::bail!("heater tolerance must be greater than 0");
+        }
+
+        Ok(())
+    }
+
+    /// Check a recipe can be made within the safety limits
+    pub fn validate_recipe(&self, recipe: &Recipe) -> Result<()> {
+        self.check_recipe(recipe)
+            .map_err(|e| HotChocolaBotError::RecipeRejected(format!("{:#}", e)).into())
+    }
+
+    fn check_recipe(&self, recipe: &Recipe) -> Result<()> {
+        if !(self.safety.min_temperature..=self.safety.max_temperature).contains(&recipe.target_temp) {
+            anyhow::bail!(
+                "target_temp {:.1}C outside safety limits ({:.1}C to {:.1}C)",
+                recipe.target_temp, self.safety.min_temperature, self.safety.max_temperature
+            );
+        }
+
+        let program = recipe.program();
+        steps::validate_program(&program, self)?;
+
+        if let Some(cup_size) = recipe.cup_size_ml {
+            if cup_size <= 0.0 {
+                anyhow::bail!("cup_size_ml must be greater than 0");
+            }
+
+            let liquid_ml = steps::total_amount(&program, &self.hardware.calibration, DoseUnit::Ml);
+            if liquid_ml > cup_size {
+                anyhow::bail!("{:.0}ml of liquid does not fit a {:.0}ml cup", liquid_ml, cup_size);
+            }
+        }
+
         Ok(())
     }
 
@@ -150,19 +923,93 @@ pub fn save(&self, path: &str) -> Result<()> {
 
         Ok(())
     }
+
+    /// Store one pump's calibration in a config file
+    ///
+    /// Only `[hardware.calibration.<pump>]` is rewritten; comments and the
+    /// rest of the file stay as written. A missing file is created from the
+    /// defaults first.
+    pub fn save_calibration(path: &str, pump: Ingredient, calibration: &PumpCalibration) -> Result<()> {
+        if !std::path::Path::new(path).exists() {
+            Self::default().save(path)?;
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| HotChocolaBotError::Config(format!("Failed to read {}: {}", path, e)))?;
+        let edited = with_calibration(&contents, pump, calibration)
+            .map_err(|e| HotChocolaBotError::Config(format!("Cannot update {}: {:#}", path, e)))?;
+
+        fs::write(path, edited)
+            .context("Failed to write config file")?;
+
+        Ok(())
+    }
+}
+
+/// Config file text with one pump's calibration table replaced
+///
+/// The result is checked to still load before it is returned.
+fn with_calibration(contents: &str, pump: Ingredient, calibration: &PumpCalibration) -> Result<String> {
+    let mut document: toml_edit::DocumentMut = contents.parse().context("not valid TOML")?;
+    let table: toml_edit::DocumentMut = toml::to_string(calibration)
+        .context("Failed to serialize calibration")?
+        .parse()?;
+
+    let hardware = document.entry("hardware")
+        .or_insert(toml_edit::table())
+        .as_table_mut()
+        .context("[hardware] is not a table")?;
+    let calibrations = hardware.entry("calibration")
+        .or_insert_with(|| {
+            let mut calibrations = toml_edit::Table::new();
+            calibrations.set_implicit(true);
+            calibrations.into()
+        })
+        .as_table_mut()
+        .context("[hardware.calibration] is not a table")?;
+    let fresh = table.as_table().clone();
+    match calibrations.get_mut(pump.name()) {
+        // Written as `milk = { ... }`; keep it on its line with its comment
+        Some(toml_edit::Item::Value(value)) => {
+            let mut inline = fresh.into_inline_table();
+            *inline.decor_mut() = value.decor().clone();
+            *value = inline.into();
+        }
+        _ => {
+            calibrations.insert(pump.name(), fresh.into());
+        }
+    }
+
+    let edited = document.to_string();
+    let config: BotConfig = toml::from_str(&edited)?;
+    config.validate()?;
+    Ok(edited)
 }
 
 impl Default for BotConfig {
     fn default() -> Self {
         Self {
             hardware: HardwareConfig {
+                backend: Backend::default(),
                 cocoa_pump_pin: 17,
                 milk_pump_pin: 27,
                 sugar_pump_pin: 22,
                 temp_sensor_addr: 0x48,
                 lcd_addr: 0x27,
+                display: DisplayKind::default(),
+                oled_addr: 0x3C,
+                lcd_rows: 2,
+                lcd_cols: 16,
                 emergency_stop_pin: 23,
                 status_led_pin: 24,
+                heater_pin: 25,
+                mixer_pin: None,
+                water_pump_pin: None,
+                order_buttons: Vec::new(),
+                calibration: PumpCalibrations::default(),
+                adc_addr: default_adc_addr(),
+                supply_adc_channel: 0,
+                supply_divider_ratio: default_supply_divider_ratio(),
             },
             safety: SafetyConfig {
                 max_temperature: 90.0,
@@ -171,37 +1018,69 @@ fn default() -> Self {
                 operation_timeout: 120,
                 verbose_logging: true,
                 emergency_stop_enabled: true,
+                thermal_check_interval_ms: default_thermal_check_interval_ms(),
+                max_heating_rate: default_max_heating_rate(),
+                min_heating_rise: default_min_heating_rise(),
+                heating_check_window: default_heating_check_window(),
+                stuck_sensor_timeout: default_stuck_sensor_timeout(),
+                min_supply_voltage: default_min_supply_voltage(),
+                max_supply_voltage: default_max_supply_voltage(),
+                preflight_pump_pulse_ms: default_preflight_pump_pulse_ms(),
             },
-            recipes: RecipeConfig {
-                standard: Recipe {
-                    cocoa_ms: 2000,
-                    milk_ms: 5000,
-                    sugar_ms: 1000,
-                    target_temp: 65.0,
-                },
-                light: Recipe {
-                    cocoa_ms: 1000,
-                    milk_ms: 6000,
-                    sugar_ms: 800,
-                    target_temp: 65.0,
-                },
-                rich: Recipe {
-                    cocoa_ms: 3000,
-                    milk_ms: 4000,
-                    sugar_ms: 1200,
-                    target_temp: 70.0,
-                },
-            },
+            recipes: default_recipes(),
+            heater: HeaterConfig::default(),
             education: EducationConfig {
                 challenge_mode: false,
                 show_internals: true,
                 enable_teaching_failures: false,
                 observation_delay_ms: 500,
             },
+            maintenance: MaintenanceConfig::default(),
+            reservoirs: Reservoirs::default(),
+            cleaning: CleaningConfig::default(),
+            http: HttpConfig::default(),
         }
     }
 }
 
+/// Built-in recipes used when no config file is present
+fn default_recipes() -> RecipeConfig {
+    let mut recipes = RecipeConfig::default();
+
+    recipes.insert("standard", Recipe {
+        cocoa: Some(25.0),
+        milk: Some(125.0),
+        sugar: Some(12.5),
+        target_temp: 65.0,
+        display_name: Some("Hot Chocolate".to_string()),
+        allergens: vec!["milk".to_string()],
+        cup_size_ml: Some(250.0),
+        ..Recipe::default()
+    });
+    recipes.insert("light", Recipe {
+        cocoa: Some(12.5),
+        milk: Some(150.0),
+        sugar: Some(10.0),
+        target_temp: 65.0,
+        display_name: Some("Light Chocolate".to_string()),
+        allergens: vec!["milk".to_string()],
+        cup_size_ml: Some(250.0),
+        ..Recipe::default()
+    });
+    recipes.insert("rich", Recipe {
+        cocoa: Some(37.5),
+        milk: Some(100.0),
+        sugar: Some(15.0),
+        target_temp: 70.0,
+        display_name: Some("Rich Chocolate".to_string()),
+        allergens: vec!["milk".to_string()],
+        cup_size_ml: Some(250.0),
+        ..Recipe::default()
+    });
+
+    recipes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,4 +1098,142 @@ fn test_invalid_temperature_range() {
         config.safety.min_temperature = 20.0;
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_calibration_saved_without_rewriting_file() {
+        let contents = include_str!("../../config.toml.example");
+        let calibration = PumpCalibration { flow_rate: 23.5, unit: DoseUnit::Ml };
+
+        let edited = with_calibration(contents, Ingredient::Milk, &calibration).unwrap();
+
+        let changed: Vec<_> = contents.lines().zip(edited.lines()).filter(|(before, after)| before != after).collect();
+        assert_eq!(changed, [("milk = { flow_rate = 25.0, unit = \"ml\" }", "milk = { flow_rate = 23.5, unit = \"ml\" }")]);
+        assert_eq!(contents.lines().count(), edited.lines().count());
+
+        let saved: BotConfig = toml::from_str(&edited).unwrap();
+        assert_eq!(saved.hardware.calibration.get(Ingredient::Milk).flow_rate, 23.5);
+    }
+
+    #[test]
+    fn test_calibration_added_to_file_without_one() {
+        let contents = toml::to_string_pretty(&BotConfig::default()).unwrap();
+        let calibration = PumpCalibration { flow_rate: 8.0, unit: DoseUnit::G };
+
+        let edited = with_calibration(&contents, Ingredient::Sugar, &calibration).unwrap();
+        let saved: BotConfig = toml::from_str(&edited).unwrap();
+        assert_eq!(saved.hardware.calibration.get(Ingredient::Sugar).unit, DoseUnit::G);
+        assert_eq!(saved.hardware.calibration.get(Ingredient::Milk).flow_rate, 25.0);
+    }
+
+    #[test]
+    fn test_heat_time_may_exceed_operation_timeout() {
+        let mut config = BotConfig::default();
+        config.safety.operation_timeout = 120;
+        config.heater.max_heat_time = 600;
+
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_order_button_needs_known_recipe() {
+        let mut config = BotConfig::default();
+        config.hardware.order_buttons = vec![OrderButtonConfig { pin: 16, recipe: "standard".to_string() }];
+        assert!(config.validate().is_ok());
+
+        config.hardware.order_buttons[0].recipe = "espresso".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(format!("{:#}", err).contains("espresso"));
+    }
+
+    #[test]
+    fn test_recipe_amount_converted_with_calibration() {
+        let config = BotConfig::default();
+        let recipe = &config.recipes["standard"];
+        let calibration = &config.hardware.calibration;
+
+        assert_eq!(recipe.dispense_ms(Ingredient::Milk, calibration), 5000);
+        assert_eq!(recipe.dispense_ms(Ingredient::Cocoa, calibration), 2000);
+    }
+
+    #[test]
+    fn test_legacy_ms_recipe_still_accepted() {
+        let recipe: Recipe = toml::from_str("cocoa_ms = 2000\nmilk_ms = 5000\nsugar_ms = 1000\ntarget_temp = 65.0").unwrap();
+        let calibration = PumpCalibrations::default();
+
+        assert_eq!(recipe.dispense_ms(Ingredient::Cocoa, &calibration), 2000);
+        assert_eq!(recipe.dispense_ms(Ingredient::Milk, &calibration), 5000);
+    }
+
+    #[test]
+    fn test_calibration_from_measurement() {
+        let calibration = PumpCalibration::from_measurement(2000, 50.0, DoseUnit::Ml).unwrap();
+        assert_eq!(calibration.flow_rate, 25.0);
+        assert_eq!(calibration.duration_ms(125.0), 5000);
+
+        assert!(PumpCalibration::from_measurement(2000, 0.0, DoseUnit::Ml).is_err());
+    }
+
+    #[test]
+    fn test_example_config_loads() {
+        let config: BotConfig = toml::from_str(include_str!("../../config.toml.example")).unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_named_recipe_with_metadata_loads() {
+        let recipes: RecipeConfig = toml::from_str(r#"
+            [workshop]
+            display_name = "Workshop Special"
+            description = "Extra sweet for the end of the day"
+            allergens = ["milk"]
+            cup_size_ml = 200.0
+            cocoa = 20.0
+            milk = 100.0
+            sugar = 20.0
+            target_temp = 60.0
+        "#).unwrap();
+
+        let recipe = &recipes["workshop"];
+        assert_eq!(recipe.display_name.as_deref(), Some("Workshop Special"));
+        assert_eq!(recipe.allergens, ["milk"]);
+
+        let config = BotConfig { recipes, ..BotConfig::default() };
+        assert!(config.validate().is_ok());
+        assert!(config.recipes.get("standard").is_none());
+    }
+
+    #[test]
+    fn test_recipe_outside_safety_limits_rejected() {
+        let mut config = BotConfig::default();
+        let mut recipe = config.recipes["standard"].clone();
+
+        recipe.target_temp = config.safety.max_temperature + 5.0;
+        config.recipes.insert("custom", recipe.clone());
+        let err = config.validate().unwrap_err();
+        assert!(format!("{:#}", err).contains("custom"));
+
+        // 25 ml/s for 30s at most
+        recipe.target_temp = 65.0;
+        recipe.milk = Some(1000.0);
+        recipe.cup_size_ml = None;
+        config.recipes.insert("custom", recipe.clone());
+        assert!(format!("{:#}", config.validate().unwrap_err()).contains("max_pump_runtime"));
+
+        recipe.milk = Some(300.0);
+        recipe.cup_size_ml = Some(250.0);
+        config.recipes.insert("custom", recipe);
+        assert!(format!("{:#}", config.validate().unwrap_err()).contains("cup"));
+    }
+
+    #[test]
+    fn test_config_without_heater_section_loads() {
+        let mut value = toml::Value::try_from(BotConfig::default()).unwrap();
+        let table = value.as_table_mut().unwrap();
+        table.remove("heater");
+        table["hardware"].as_table_mut().unwrap().remove("heater_pin");
+
+        let config: BotConfig = toml::from_str(&toml::to_string(&value).unwrap()).unwrap();
+        assert_eq!(config.hardware.heater_pin, 25);
+        assert!(config.validate().is_ok());
+    }
 }
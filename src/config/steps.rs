@@ -0,0 +1,237 @@
+//! Recipe programs: the steps the machine runs to make a drink
+//!
+//! A recipe may list its own `steps`; without them it runs the default
+//! program of heating to `target_temp`, then adding milk, cocoa and sugar in
+//! turn. Programs are checked against the safety limits before they run.
+
+use crate::config::{BotConfig, DoseUnit, Ingredient, PumpCalibrations};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// An amount of one ingredient
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dose {
+    pub ingredient: Ingredient,
+
+    /// Amount in the pump's calibrated unit (ml or g)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f32>,
+
+    /// Raw pump time in milliseconds, used when no amount is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ms: Option<u64>,
+}
+
+impl Dose {
+    /// Pump runtime for this dose, converting amounts via the pump calibration
+    pub fn dispense_ms(&self, calibration: &PumpCalibrations) -> u64 {
+        match (self.amount, self.ms) {
+            (Some(amount), _) => calibration.get(self.ingredient).duration_ms(amount),
+            (None, Some(ms)) => ms,
+            (None, None) => 0,
+        }
+    }
+}
+
+/// One instruction in a recipe program
+///
+/// Written in TOML as an inline table tagged with `action`, e.g.
+/// `{ action = "dispense", ingredient = "milk", amount = 125.0 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Step {
+    /// Heat the milk to `temp` °C and keep it there for the following steps
+    Heat { temp: f32 },
+
+    /// Dispense one ingredient
+    Dispense(Dose),
+
+    /// Dispense several ingredients at once, one pump each
+    Parallel { dispense: Vec<Dose> },
+
+    /// Wait, keeping any temperature already reached
+    Hold { seconds: f32 },
+
+    /// Run the stirrer
+    Stir { seconds: f32 },
+
+    /// Wait until the operator confirms
+    Confirm { prompt: String },
+}
+
+impl Step {
+    /// Short text for the display's first line
+    pub fn describe(&self) -> String {
+        match self {
+            Step::Heat { temp } => format!("Heating to {:.0}°C", temp),
+            Step::Dispense(dose) => format!("Adding {}...", dose.ingredient),
+            Step::Parallel { dispense } => {
+                let names: Vec<_> = dispense.iter().map(|dose| dose.ingredient.name()).collect();
+                format!("Adding {}", names.join("+"))
+            }
+            Step::Hold { seconds } => format!("Holding {:.0}s", seconds),
+            Step::Stir { .. } => "Stirring...".to_string(),
+            Step::Confirm { prompt } => prompt.clone(),
+        }
+    }
+
+    /// Doses dispensed by this step
+    pub fn doses(&self) -> &[Dose] {
+        match self {
+            Step::Dispense(dose) => std::slice::from_ref(dose),
+            Step::Parallel { dispense } => dispense,
+            _ => &[],
+        }
+    }
+
+    /// Mutable doses, for scaling a program
+    pub fn doses_mut(&mut self) -> &mut [Dose] {
+        match self {
+            Step::Dispense(dose) => std::slice::from_mut(dose),
+            Step::Parallel { dispense } => dispense,
+            _ => &mut [],
+        }
+    }
+}
+
+/// Check every step of a program against the safety limits
+///
+/// Whether the hardware can run a step (e.g. a stirrer is fitted) is
+/// checked by the controller, which knows what is connected.
+pub(super) fn validate_program(steps: &[Step], config: &BotConfig) -> Result<()> {
+    if steps.is_empty() {
+        anyhow::bail!("program has no steps");
+    }
+
+    for (index, step) in steps.iter().enumerate() {
+        validate_step(step, config)
+            .map_err(|e| e.context(format!("step {} ({})", index + 1, step.describe())))?;
+    }
+
+    Ok(())
+}
+
+fn validate_step(step: &Step, config: &BotConfig) -> Result<()> {
+    let safety = &config.safety;
+
+    match step {
+        Step::Heat { temp } => {
+            if !(safety.min_temperature..=safety.max_temperature).contains(temp) {
+                anyhow::bail!(
+                    "temperature {:.1}C outside safety limits ({:.1}C to {:.1}C)",
+                    temp, safety.min_temperature, safety.max_temperature
+                );
+            }
+        }
+        Step::Dispense(_) | Step::Parallel { .. } => {
+            let doses = step.doses();
+            if doses.is_empty() {
+                anyhow::bail!("nothing to dispense");
+            }
+
+            for (index, dose) in doses.iter().enumerate() {
+                if doses[..index].iter().any(|other| other.ingredient == dose.ingredient) {
+                    anyhow::bail!("{} pump used twice in one step", dose.ingredient);
+                }
+                validate_dose(dose, config)?;
+            }
+        }
+        Step::Hold { seconds } => {
+            if !(0.0..=safety.operation_timeout as f32).contains(seconds) {
+                anyhow::bail!("hold must be between 0 and operation_timeout ({}s)", safety.operation_timeout);
+            }
+        }
+        Step::Stir { seconds } => {
+            if !(0.0..=safety.max_pump_runtime as f32).contains(seconds) {
+                anyhow::bail!("stir must be between 0 and max_pump_runtime ({}s)", safety.max_pump_runtime);
+            }
+        }
+        Step::Confirm { prompt } => {
+            if prompt.trim().is_empty() {
+                anyhow::bail!("confirmation needs a prompt");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_dose(dose: &Dose, config: &BotConfig) -> Result<()> {
+    match (dose.amount, dose.ms) {
+        (None, None) => anyhow::bail!("{} dose needs an amount or ms", dose.ingredient),
+        (Some(amount), _) if amount < 0.0 => {
+            anyhow::bail!("{} amount must not be negative", dose.ingredient)
+        }
+        _ => {}
+    }
+
+    let runtime_ms = dose.dispense_ms(&config.hardware.calibration);
+    if runtime_ms > config.safety.max_pump_runtime * 1000 {
+        anyhow::bail!(
+            "{} needs {}ms of pumping, over max_pump_runtime ({}s)",
+            dose.ingredient, runtime_ms, config.safety.max_pump_runtime
+        );
+    }
+
+    Ok(())
+}
+
+/// Total amount a program dispenses of ingredients calibrated in `unit`
+pub fn total_amount(steps: &[Step], calibration: &PumpCalibrations, unit: DoseUnit) -> f32 {
+    steps.iter()
+        .flat_map(Step::doses)
+        .filter(|dose| calibration.get(dose.ingredient).unit == unit)
+        .filter_map(|dose| dose.amount)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_parse_from_toml() {
+        #[derive(Deserialize)]
+        struct Program {
+            steps: Vec<Step>,
+        }
+
+        let program: Program = toml::from_str(r#"
+            steps = [
+                { action = "heat", temp = 65.0 },
+                { action = "dispense", ingredient = "milk", amount = 125.0 },
+                { action = "parallel", dispense = [
+                    { ingredient = "cocoa", amount = 25.0 },
+                    { ingredient = "sugar", ms = 800 },
+                ] },
+                { action = "stir", seconds = 5.0 },
+                { action = "hold", seconds = 10.0 },
+                { action = "confirm", prompt = "Take your cup" },
+            ]
+        "#).unwrap();
+
+        assert_eq!(program.steps.len(), 6);
+        assert_eq!(program.steps[0], Step::Heat { temp: 65.0 });
+        assert_eq!(program.steps[2].doses()[1].ms, Some(800));
+        assert!(validate_program(&program.steps, &BotConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_program_checked_against_safety_limits() {
+        let config = BotConfig::default();
+        let milk = |amount| Dose { ingredient: Ingredient::Milk, amount: Some(amount), ms: None };
+
+        let too_hot = [Step::Heat { temp: config.safety.max_temperature + 1.0 }];
+        assert!(validate_program(&too_hot, &config).is_err());
+
+        // 25 ml/s for at most 30s
+        let too_long = [Step::Dispense(milk(1000.0))];
+        let err = validate_program(&too_long, &config).unwrap_err();
+        assert!(format!("{:#}", err).contains("step 1"));
+
+        let same_pump_twice = [Step::Parallel { dispense: vec![milk(10.0), milk(10.0)] }];
+        assert!(validate_program(&same_pump_twice, &config).is_err());
+
+        assert!(validate_program(&[], &config).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Pump flow rate calibration
+//!
+//! Tubing wears and pumps get swapped, so recipes are written in ml and each
+//! pump carries a flow rate. Calibration runs a pump for a known time, asks
+//! the operator how much came out, and derives the new flow rate.
+
+use crate::config::{Ingredient, PumpCalibration};
+use crate::control::maintenance::MaintenanceTask;
+use crate::control::DispenseController;
+use crate::error::{Device, DeviceContext, FaultContext, HotChocolaBotError};
+use crate::safety::SafetyMonitor;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::info;
+
+/// Source of operator answers during interactive routines
+#[async_trait]
+pub trait OperatorInput: Send {
+    /// Ask the operator for a measured amount
+    async fn ask_amount(&mut self, prompt: &str) -> Result<f32>;
+
+    /// Ask the operator to confirm before continuing
+    async fn confirm(&mut self, prompt: &str) -> Result<bool>;
+}
+
+/// Operator input from the terminal
+pub struct StdinOperator {
+    lines: tokio::io::Lines<BufReader<tokio::io::Stdin>>,
+}
+
+impl StdinOperator {
+    pub fn new() -> Self {
+        Self {
+            lines: BufReader::new(tokio::io::stdin()).lines(),
+        }
+    }
+
+    async fn ask(&mut self, prompt: &str) -> Result<String> {
+        let mut stdout = tokio::io::stdout();
+        stdout.write_all(format!("{} ", prompt).as_bytes()).await?;
+        stdout.flush().await?;
+
+        let line = self.lines.next_line().await?
+            .context("No operator input (stdin closed)")
+            .fault(HotChocolaBotError::OperatorDeclined)?;
+        Ok(line.trim().to_string())
+    }
+}
+
+impl Default for StdinOperator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl OperatorInput for StdinOperator {
+    async fn ask_amount(&mut self, prompt: &str) -> Result<f32> {
+        let answer = self.ask(prompt).await?;
+        answer.parse()
+            .context(format!("'{}' is not a number", answer))
+            .fault(HotChocolaBotError::InvalidInput)
+    }
+
+    async fn confirm(&mut self, prompt: &str) -> Result<bool> {
+        let answer = self.ask(&format!("{} [y/N]", prompt)).await?;
+        Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+    }
+}
+
+impl DispenseController {
+    /// Run a pump for `run_ms` and derive its flow rate from the operator's measurement
+    ///
+    /// The returned calibration is not persisted; store it in
+    /// `BotConfig::hardware.calibration` and save the config.
+    pub async fn calibrate_pump(
+        &self,
+        ingredient: Ingredient,
+        run_ms: u64,
+        operator: &mut dyn OperatorInput,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<PumpCalibration> {
+        safety_monitor.begin_operation()?;
+        let result = self.run_calibration(ingredient, run_ms, operator, safety_monitor).await;
+        Self::finish_operation(result, safety_monitor)
+    }
+
+    async fn run_calibration(
+        &self,
+        ingredient: Ingredient,
+        run_ms: u64,
+        operator: &mut dyn OperatorInput,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<PumpCalibration> {
+        let unit = self.config.hardware.calibration.get(ingredient).unit;
+
+        info!("Calibrating {} pump with a {}ms run", ingredient, run_ms);
+        self.display.lock().await.show_message(&format!("Calibrate {}\nPlace cylinder", ingredient)).await.device(Device::Display)?;
+
+        let ready = operator
+            .confirm(&format!("Place a measuring cylinder under the {} outlet. Ready?", ingredient))
+            .await?;
+        if !ready {
+            return Err(HotChocolaBotError::OperatorDeclined(format!("calibration of {} pump", ingredient)).into());
+        }
+
+        self.display.lock().await.show_message(&format!("Calibrate {}\nPumping...", ingredient)).await.device(Device::Display)?;
+        let presses = self.emergency_stop_presses();
+        tokio::select! {
+            result = self.dispense_ingredient(ingredient, run_ms, safety_monitor) => result?,
+            _ = Self::wait_for_press(presses) => return self.abort_for_emergency_stop(safety_monitor).await,
+        }
+
+        let measured = operator
+            .ask_amount(&format!("How much {} was dispensed ({})?", ingredient, unit))
+            .await?;
+
+        let calibration = PumpCalibration::from_measurement(run_ms, measured, unit)?;
+        info!("{} pump calibrated: {:.2} {}/s", ingredient, calibration.flow_rate, unit);
+        self.acknowledge_maintenance(
+            ingredient,
+            MaintenanceTask::Calibration,
+            &format!("{:.2} {}/s", calibration.flow_rate, unit),
+        ).await?;
+        self.display.lock().await.show_message(&format!("{} calibrated\n{:.1} {}/s", ingredient, calibration.flow_rate, unit)).await.device(Device::Display)?;
+
+        Ok(calibration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::tests::sim_config;
+
+    /// Operator that answers from a script
+    struct ScriptedOperator {
+        ready: bool,
+        measured: f32,
+    }
+
+    #[async_trait]
+    impl OperatorInput for ScriptedOperator {
+        async fn ask_amount(&mut self, _prompt: &str) -> Result<f32> {
+            Ok(self.measured)
+        }
+
+        async fn confirm(&mut self, _prompt: &str) -> Result<bool> {
+            Ok(self.ready)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_calibrate_pump_from_measurement() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let mut operator = ScriptedOperator { ready: true, measured: 40.0 };
+        let before = controller.get_pump_stats().milk_runtime_ms;
+
+        let calibration = controller
+            .calibrate_pump(Ingredient::Milk, 2000, &mut operator, &mut safety)
+            .await
+            .unwrap();
+
+        assert_eq!(calibration.flow_rate, 20.0);
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms - before, 2000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_calibration_cancelled_runs_no_pump() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let mut operator = ScriptedOperator { ready: false, measured: 40.0 };
+        let before = controller.get_pump_stats().milk_runtime_ms;
+
+        let result = controller
+            .calibrate_pump(Ingredient::Milk, 2000, &mut operator, &mut safety)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms, before);
+    }
+}
@@ -0,0 +1,306 @@
+//! Clean-in-place and priming cycles
+//!
+//! A clean flushes water through the lines in a configurable series of
+//! pulses, through a dedicated water pump if one is fitted or otherwise
+//! through each ingredient pump with its intake in water. Priming fills
+//! empty lines up to the outlet after a clean or a refill. Drinks are
+//! refused once a clean is overdue by more than the grace period.
+
+use crate::config::Ingredient;
+use crate::control::calibration::OperatorInput;
+use crate::control::maintenance::{now, MaintenanceTask};
+use crate::control::DispenseController;
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::safety::SafetyMonitor;
+use anyhow::Result;
+use tokio::time::{sleep, Duration};
+use tracing::{info, warn};
+
+impl DispenseController {
+    /// Flush every line with water and record the clean in the maintenance log
+    pub async fn clean(
+        &self,
+        operator: &mut dyn OperatorInput,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        safety_monitor.begin_operation()?;
+        let result = self.run_clean(operator, safety_monitor).await;
+        Self::finish_operation(result, safety_monitor)
+    }
+
+    /// Fill each line with its ingredient, ready to serve
+    pub async fn prime(
+        &self,
+        operator: &mut dyn OperatorInput,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        safety_monitor.begin_operation()?;
+        let result = self.run_prime(operator, safety_monitor).await;
+        Self::finish_operation(result, safety_monitor)
+    }
+
+    /// Refuse to serve once any line is overdue for a clean by more than the grace period
+    ///
+    /// A pump that has never been cleaned is counted from when it first ran,
+    /// so a new rig or a wiped log still gets locked out in time.
+    pub(super) fn check_clean_due(&self) -> Result<()> {
+        let interval_hours = self.config.maintenance.cleaning_interval_hours;
+        if interval_hours == 0 {
+            return Ok(());
+        }
+
+        let limit_secs = (interval_hours + self.config.cleaning.grace_hours) * 3600;
+        let at = now();
+        let store = self.maintenance.lock().unwrap();
+
+        for ingredient in Ingredient::ALL {
+            let record = store.log().get(ingredient);
+            // Nothing has gone through a line that has never run
+            let Some(last) = record.last_cleaned.or(record.first_used) else {
+                continue;
+            };
+
+            let since = at.saturating_sub(last);
+            if since > limit_secs {
+                return Err(HotChocolaBotError::CleanOverdue { ingredient, hours: since / 3600 }.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_clean(
+        &self,
+        operator: &mut dyn OperatorInput,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        // Ingredient pumped before the clean still comes off the reservoirs
+        self.account_pump_usage().await;
+
+        info!("Starting clean cycle");
+        self.display.lock().await.show_message("Cleaning\nPlace container").await.device(Device::Display)?;
+
+        let ready = operator
+            .confirm("Place the cleaning container under the outlet. Cleaning container present?")
+            .await?;
+        if !ready {
+            return Err(HotChocolaBotError::OperatorDeclined("clean cycle".to_string()).into());
+        }
+
+        self.display.lock().await.show_message("Cleaning\nFlushing...").await.device(Device::Display)?;
+        let presses = self.emergency_stop_presses();
+        tokio::select! {
+            result = self.flush(safety_monitor) => result?,
+            _ = Self::wait_for_press(presses) => return self.abort_for_emergency_stop(safety_monitor).await,
+        }
+
+        // Water went through the pumps: wear the tubing, not the reservoirs
+        {
+            let mut levels = self.levels.lock().unwrap();
+            for ingredient in Ingredient::ALL {
+                levels.skip_runtime(ingredient, self.pump(ingredient).total_runtime_ms());
+            }
+        }
+        self.record_pump_usage().await?;
+
+        let note = match self.water_pump {
+            Some(_) => "clean cycle (water pump)",
+            None => "clean cycle",
+        };
+        for ingredient in Ingredient::ALL {
+            self.acknowledge_maintenance(ingredient, MaintenanceTask::Cleaning, note).await?;
+        }
+
+        info!("Clean cycle complete");
+        self.display.lock().await.show_message("Clean complete\nPrime before use").await.device(Device::Display)?;
+
+        Ok(())
+    }
+
+    /// Run the configured flush pulses, pausing between them so the lines drain
+    async fn flush(&self, safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        let cleaning = &self.config.cleaning;
+
+        match &self.water_pump {
+            Some(water_pump) => {
+                for &pulse_ms in &cleaning.flush_pulses_ms {
+                    if safety_monitor.is_emergency_stop() {
+                        return Err(HotChocolaBotError::EmergencyStop("cannot flush".to_string()).into());
+                    }
+
+                    info!("Flushing with water pump for {}ms", pulse_ms);
+                    water_pump.dispense(pulse_ms).await.device(Device::WaterPump)?;
+                    sleep(Duration::from_millis(cleaning.pause_ms)).await;
+                }
+            }
+            None => {
+                warn!("No water pump fitted; flushing through the ingredient pumps");
+                for ingredient in Ingredient::ALL {
+                    for &pulse_ms in &cleaning.flush_pulses_ms {
+                        self.dispense_ingredient(ingredient, pulse_ms, safety_monitor).await?;
+                        sleep(Duration::from_millis(cleaning.pause_ms)).await;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_prime(
+        &self,
+        operator: &mut dyn OperatorInput,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        info!("Priming pumps for {}ms each", self.config.cleaning.prime_ms);
+        self.display.lock().await.show_message("Priming\nPlace cup").await.device(Device::Display)?;
+
+        let ready = operator
+            .confirm("Place a waste cup under the outlet. Ready to prime?")
+            .await?;
+        if !ready {
+            return Err(HotChocolaBotError::OperatorDeclined("priming".to_string()).into());
+        }
+
+        let presses = self.emergency_stop_presses();
+        tokio::select! {
+            result = async {
+                for ingredient in Ingredient::ALL {
+                    self.display.lock().await.show_message(&format!("Priming\n{}...", ingredient)).await.device(Device::Display)?;
+                    self.dispense_ingredient(ingredient, self.config.cleaning.prime_ms, safety_monitor).await?;
+                }
+                Ok::<_, anyhow::Error>(())
+            } => result?,
+            _ = Self::wait_for_press(presses) => return self.abort_for_emergency_stop(safety_monitor).await,
+        }
+
+        // Priming draws real ingredient
+        self.account_pump_usage().await;
+
+        info!("Priming complete");
+        self.display.lock().await.show_message("Primed\nReady to serve").await.device(Device::Display)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::orders::{OrderEvent, OrderQueue, OrderRequest};
+    use crate::control::tests::sim_config;
+    use crate::hardware::factory::ControllerHardware;
+    use crate::hardware::mock::{MockDisplay, MockPump};
+    use crate::hardware::Pump;
+    use async_trait::async_trait;
+
+    /// Operator that answers every confirmation the same way
+    struct ScriptedOperator {
+        ready: bool,
+    }
+
+    #[async_trait]
+    impl OperatorInput for ScriptedOperator {
+        async fn ask_amount(&mut self, _prompt: &str) -> Result<f32> {
+            anyhow::bail!("no amounts scripted")
+        }
+
+        async fn confirm(&mut self, _prompt: &str) -> Result<bool> {
+            Ok(self.ready)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clean_flushes_and_records() {
+        let config = sim_config();
+        let water = MockPump::new("Water");
+        let hardware = ControllerHardware { water_pump: Some(Box::new(water.clone())), ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let declined = controller.clean(&mut ScriptedOperator { ready: false }, &mut safety).await;
+        assert_eq!(HotChocolaBotError::code_of(&declined.unwrap_err()), 404);
+        assert_eq!(water.total_runtime_ms(), 0);
+        safety.recover().unwrap();
+
+        let milk_before = controller.reservoir_remaining(Ingredient::Milk);
+        controller.clean(&mut ScriptedOperator { ready: true }, &mut safety).await.unwrap();
+
+        let pulses: u64 = config.cleaning.flush_pulses_ms.iter().sum();
+        assert_eq!(water.total_runtime_ms(), pulses);
+        assert_eq!(controller.reservoir_remaining(Ingredient::Milk), milk_before);
+        let store = controller.maintenance.lock().unwrap();
+        assert!(Ingredient::ALL.into_iter().all(|pump| store.log().get(pump).last_cleaned.is_some()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overdue_clean_refuses_orders() {
+        let config = sim_config();
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware { display: Box::new(display.clone()), ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        // Past the 24h interval and the 12h grace period
+        let long_ago = now() - 40 * 3600;
+        controller.maintenance.lock().unwrap()
+            .acknowledge(Ingredient::Milk, MaintenanceTask::Cleaning, "", long_ago);
+
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+
+        let failed = std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| matches!(event, OrderEvent::Failed { id: 1, .. }));
+        assert!(failed);
+        assert_eq!(display.get_buffer(), "CLEAN NEEDED\nCode E211");
+
+        // Without a water pump the ingredient pumps flush themselves
+        let milk_before = controller.get_pump_stats().milk_runtime_ms;
+        controller.clean(&mut ScriptedOperator { ready: true }, &mut safety).await.unwrap();
+        let pulses: u64 = config.cleaning.flush_pulses_ms.iter().sum();
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms - milk_before, pulses);
+        assert!(controller.check_clean_due().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_never_cleaned_counts_from_first_use() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+
+        // Lines that have never run have nothing in them to clean
+        assert!(controller.check_clean_due().is_ok());
+
+        // Sugar first ran past the 24h interval and the 12h grace period, and was never cleaned
+        let long_ago = now() - 40 * 3600;
+        controller.maintenance.lock().unwrap().record_runtime(Ingredient::Sugar, 1000, long_ago);
+        let error = controller.check_clean_due().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(HotChocolaBotError::CleanOverdue { ingredient: Ingredient::Sugar, hours: 40 })
+        ));
+
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        controller.clean(&mut ScriptedOperator { ready: true }, &mut safety).await.unwrap();
+        assert!(controller.check_clean_due().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prime_draws_from_reservoirs() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let before = controller.get_pump_stats().cocoa_runtime_ms;
+
+        controller.prime(&mut ScriptedOperator { ready: true }, &mut safety).await.unwrap();
+
+        assert_eq!(controller.get_pump_stats().cocoa_runtime_ms - before, config.cleaning.prime_ms);
+        assert!(controller.reservoir_remaining(Ingredient::Cocoa) < config.reservoirs.cocoa.capacity);
+    }
+}
@@ -0,0 +1,318 @@
+//! Interpreter for recipe programs
+//!
+//! Runs the steps of a `Recipe::program` one after another. Heating keeps
+//! the milk at temperature for the remaining steps; the thermal watchdog and
+//! emergency stop around `run_dispense` cover the whole program.
+
+use crate::config::{Dose, Recipe, Step};
+use crate::control::thermostat::TemperatureHold;
+use crate::control::DispenseController;
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::safety::SafetyMonitor;
+use anyhow::{Context, Result};
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::Poll;
+use tokio::time::{Duration, Instant};
+use tracing::info;
+
+/// A pump run borrowed from the controller
+type PumpRun<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+impl DispenseController {
+    /// Program for a recipe, checked against the safety limits and the fitted hardware
+    pub(super) fn prepare_program(&self, recipe: &Recipe) -> Result<Vec<Step>> {
+        self.config.validate_recipe(recipe)
+            .context("Recipe outside safety limits")?;
+
+        let program = recipe.program();
+        if self.mixer.is_none() && program.iter().any(|step| matches!(step, Step::Stir { .. })) {
+            return Err(HotChocolaBotError::MissingHardware(Device::Mixer))
+                .context("Recipe has a stir step but no mixer_pin is configured");
+        }
+
+        Ok(program)
+    }
+
+    /// Run every step of a program, switching the heater off afterwards
+    pub(super) async fn run_program(
+        &self,
+        program: &[Step],
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        let mut hold = None;
+        let result = self.run_steps(program, &mut hold, safety_monitor).await;
+
+        let hold_result = match hold {
+            Some(hold) => hold.stop().await,
+            None => Ok(()),
+        };
+
+        result?;
+        hold_result
+    }
+
+    async fn run_steps(
+        &self,
+        program: &[Step],
+        hold: &mut Option<TemperatureHold>,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        for (index, step) in program.iter().enumerate() {
+            info!("Step {}/{}: {:?}", index + 1, program.len(), step);
+            self.show_step(&step.describe()).await?;
+
+            match step {
+                Step::Heat { temp } => {
+                    // Hand over from any earlier hold to the new target
+                    if let Some(previous) = hold.take() {
+                        previous.stop().await?;
+                    }
+
+                    let reached = self.thermostat.heat_to(*temp, self.heat_limit()).await?;
+                    safety_monitor.validate_temperature(reached)?;
+                    *hold = Some(self.thermostat.hold(*temp));
+                }
+                Step::Dispense(dose) => {
+                    let duration_ms = dose.dispense_ms(&self.config.hardware.calibration);
+                    self.dispense_ingredient(dose.ingredient, duration_ms, safety_monitor).await?;
+                }
+                Step::Parallel { dispense } => {
+                    self.dispense_parallel(dispense, safety_monitor).await?;
+                }
+                Step::Hold { seconds } => {
+                    tokio::time::sleep(Duration::from_secs_f32(*seconds)).await;
+                }
+                Step::Stir { seconds } => {
+                    let mixer = self.mixer.as_ref().ok_or(HotChocolaBotError::MissingHardware(Device::Mixer))?;
+                    mixer.dispense(Duration::from_secs_f32(*seconds).as_millis() as u64).await
+                        .device(Device::Mixer)?;
+                }
+                Step::Confirm { prompt } => self.wait_for_confirmation(prompt).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run several pumps at once; the first failure stops the others
+    async fn dispense_parallel(&self, doses: &[Dose], safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        let calibration = &self.config.hardware.calibration;
+        let mut runs: Vec<PumpRun<'_>> = Vec::new();
+
+        for dose in doses {
+            let duration_ms = dose.dispense_ms(calibration);
+            Self::check_dispense(dose.ingredient, duration_ms, &self.config, safety_monitor)?;
+
+            if duration_ms > 0 {
+                info!("Dispensing {} for {}ms", dose.ingredient, duration_ms);
+                let ingredient = dose.ingredient;
+                runs.push(Box::pin(async move {
+                    self.pump(ingredient).dispense(duration_ms).await.device(Device::Pump(ingredient))
+                }));
+            }
+        }
+
+        join_all(runs).await?;
+        self.observation_delay().await;
+
+        Ok(())
+    }
+
+    /// Pause the program until the operator confirms through the order queue
+    async fn wait_for_confirmation(&self, prompt: &str) -> Result<()> {
+        let current = self.current_order.lock().unwrap()
+            .as_ref()
+            .map(|current| (current.id, current.queue.clone()));
+        let (id, queue) = current.ok_or_else(|| {
+            HotChocolaBotError::RecipeRejected("a confirmation step needs an operator (run the recipe as an order)".to_string())
+        })?;
+
+        info!("Waiting for operator: {}", prompt);
+        self.operator_wait.lock().unwrap().start(Instant::now());
+        let confirmed = queue.confirm(id, prompt).await;
+        self.operator_wait.lock().unwrap().stop(Instant::now());
+
+        if !confirmed? {
+            return Err(HotChocolaBotError::OperatorDeclined(prompt.to_string()).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Drive all runs concurrently, returning the first error
+///
+/// Returning early drops the remaining runs, which halts their pumps.
+async fn join_all(mut runs: Vec<PumpRun<'_>>) -> Result<()> {
+    poll_fn(|cx| {
+        let mut index = 0;
+        while index < runs.len() {
+            match runs[index].as_mut().poll(cx) {
+                Poll::Ready(Ok(())) => drop(runs.swap_remove(index)),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => index += 1,
+            }
+        }
+
+        if runs.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Ingredient;
+    use crate::control::orders::{OrderEvent, OrderQueue, OrderRequest};
+    use crate::control::tests::sim_config;
+    use crate::hardware::factory::ControllerHardware;
+    use crate::hardware::Pump;
+    use crate::hardware::mock::MockPump;
+    use crate::safety::SafetyStates;
+    use tokio::time::Instant;
+
+    fn dose(ingredient: Ingredient, amount: f32) -> Dose {
+        Dose { ingredient, amount: Some(amount), ms: None }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parallel_step_runs_pumps_together() {
+        let mut config = sim_config();
+        config.education.observation_delay_ms = 0;
+        let pumps = [MockPump::new("Cocoa"), MockPump::new("Milk"), MockPump::new("Sugar")];
+        let mixer = MockPump::new("Mixer");
+        let hardware = ControllerHardware {
+            cocoa_pump: Box::new(pumps[0].clone()),
+            milk_pump: Box::new(pumps[1].clone()),
+            sugar_pump: Box::new(pumps[2].clone()),
+            mixer: Some(Box::new(mixer.clone())),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let before: Vec<_> = pumps.iter().map(|pump| pump.total_runtime_ms()).collect();
+
+        // 2s of milk alongside 2s of cocoa, then a 3s stir
+        let recipe = Recipe {
+            target_temp: 65.0,
+            steps: vec![
+                Step::Parallel { dispense: vec![dose(Ingredient::Milk, 50.0), dose(Ingredient::Cocoa, 25.0)] },
+                Step::Stir { seconds: 3.0 },
+            ],
+            ..Recipe::default()
+        };
+
+        let started = Instant::now();
+        controller.dispense_recipe(&recipe, &mut safety).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_millis(5500));
+        assert_eq!(pumps[0].total_runtime_ms() - before[0], 2000);
+        assert_eq!(pumps[1].total_runtime_ms() - before[1], 2000);
+        assert_eq!(pumps[2].total_runtime_ms(), before[2]);
+        assert_eq!(mixer.total_runtime_ms(), 3000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stir_refused_without_mixer() {
+        let config = sim_config();
+        let hardware = ControllerHardware { mixer: None, ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let before = controller.get_pump_stats().milk_runtime_ms;
+
+        let recipe = Recipe {
+            target_temp: 65.0,
+            steps: vec![Step::Dispense(dose(Ingredient::Milk, 50.0)), Step::Stir { seconds: 1.0 }],
+            ..Recipe::default()
+        };
+
+        let err = controller.dispense_recipe(&recipe, &mut safety).await.unwrap_err();
+        assert_eq!(HotChocolaBotError::code_of(&err), 403);
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms, before);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_confirm_step_waits_for_operator() {
+        let mut config = sim_config();
+        let mut recipe = config.recipes["standard"].clone();
+        recipe.steps = vec![
+            Step::Confirm { prompt: "Cup in place?".to_string() },
+            Step::Dispense(dose(Ingredient::Milk, 50.0)),
+        ];
+        config.recipes.insert("confirmed", recipe);
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("confirmed")).unwrap();
+        orders.close();
+
+        let operator = async {
+            loop {
+                if let Ok(OrderEvent::AwaitingConfirmation { id: 1, .. }) = events.recv().await {
+                    break;
+                }
+            }
+            // Nothing dispensed until the operator answers
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            assert!(!controller.pump_running(Ingredient::Milk));
+            orders.answer(true).unwrap();
+        };
+
+        let (result, ()) = tokio::join!(controller.run(&mut safety, &orders), operator);
+        result.unwrap();
+
+        let completed = std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| event == OrderEvent::Completed { id: 1 });
+        assert!(completed);
+        assert!(matches!(safety.state(), SafetyStates::Safe));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_operator_wait_not_counted_against_timeout() {
+        let mut config = sim_config();
+        config.safety.operation_timeout = 30;
+        let mut recipe = config.recipes["standard"].clone();
+        recipe.steps = vec![
+            Step::Confirm { prompt: "Cup in place?".to_string() },
+            Step::Dispense(dose(Ingredient::Milk, 50.0)),
+        ];
+        config.recipes.insert("confirmed", recipe);
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("confirmed")).unwrap();
+        orders.close();
+
+        // The operator takes twice the whole operation timeout to answer
+        let operator = async {
+            loop {
+                if let Ok(OrderEvent::AwaitingConfirmation { id: 1, .. }) = events.recv().await {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            orders.answer(true).unwrap();
+        };
+
+        let (result, ()) = tokio::join!(controller.run(&mut safety, &orders), operator);
+        result.unwrap();
+
+        let completed = std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| event == OrderEvent::Completed { id: 1 });
+        assert!(completed);
+        assert!(matches!(safety.state(), SafetyStates::Safe));
+    }
+}
@@ -0,0 +1,631 @@
+//! Persistent pump maintenance tracking
+//!
+//! Pump runtime counters only live as long as the process. The maintenance
+//! store keeps each pump's tubing wear, cleaning and calibration dates in a
+//! small TOML file that survives restarts, together with an audit trail of
+//! the maintenance operators have acknowledged. The reservoir estimates are
+//! saved in the same file, so a restart does not take them to be full.
+
+use crate::config::{Ingredient, MaintenanceConfig};
+use crate::control::DispenseController;
+use crate::error::{Device, DeviceContext, FaultContext, HotChocolaBotError};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Seconds since the Unix epoch
+pub type Timestamp = u64;
+
+/// Current wall-clock time
+pub fn now() -> Timestamp {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Maintenance carried out on a pump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceTask {
+    /// New tubing fitted; resets the wear counters
+    Tubing,
+    Cleaning,
+    Calibration,
+}
+
+impl MaintenanceTask {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaintenanceTask::Tubing => "tubing",
+            MaintenanceTask::Cleaning => "cleaning",
+            MaintenanceTask::Calibration => "calibration",
+        }
+    }
+}
+
+impl fmt::Display for MaintenanceTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for MaintenanceTask {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "tubing" => Ok(MaintenanceTask::Tubing),
+            "clean" | "cleaning" => Ok(MaintenanceTask::Cleaning),
+            "calibrate" | "calibration" => Ok(MaintenanceTask::Calibration),
+            other => Err(HotChocolaBotError::InvalidInput(
+                format!("unknown maintenance task '{}' (tubing, cleaning, calibration)", other)
+            ).into()),
+        }
+    }
+}
+
+/// Wear and service history of one pump
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PumpRecord {
+    /// Runtime since the tubing was last replaced
+    pub tubing_runtime_ms: u64,
+
+    /// Runs since the tubing was last replaced
+    pub tubing_cycles: u64,
+
+    /// Runtime over the pump's whole life
+    pub total_runtime_ms: u64,
+
+    /// Runs over the pump's whole life
+    pub total_cycles: u64,
+
+    /// When the pump first ran, for a pump that has never been cleaned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_used: Option<Timestamp>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_cleaned: Option<Timestamp>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_calibrated: Option<Timestamp>,
+}
+
+/// One acknowledged maintenance task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: Timestamp,
+    pub pump: Ingredient,
+    pub task: MaintenanceTask,
+
+    /// Who did the work, and anything worth remembering
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+
+    /// Tubing counters at the time, before any reset
+    pub tubing_runtime_ms: u64,
+    pub tubing_cycles: u64,
+}
+
+/// Estimated amount left in each reservoir, in the pump's calibrated unit
+///
+/// `None` for a reservoir that has never been estimated; it is taken to be full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReservoirRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cocoa: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milk: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sugar: Option<f32>,
+}
+
+impl ReservoirRecord {
+    pub fn get(&self, ingredient: Ingredient) -> Option<f32> {
+        match ingredient {
+            Ingredient::Cocoa => self.cocoa,
+            Ingredient::Milk => self.milk,
+            Ingredient::Sugar => self.sugar,
+        }
+    }
+
+    pub fn set(&mut self, ingredient: Ingredient, remaining: f32) {
+        let slot = match ingredient {
+            Ingredient::Cocoa => &mut self.cocoa,
+            Ingredient::Milk => &mut self.milk,
+            Ingredient::Sugar => &mut self.sugar,
+        };
+        *slot = Some(remaining);
+    }
+}
+
+/// Contents of the maintenance file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceLog {
+    pub cocoa: PumpRecord,
+    pub milk: PumpRecord,
+    pub sugar: PumpRecord,
+
+    /// Reservoir estimates when the log was last saved
+    pub reservoirs: ReservoirRecord,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub audit: Vec<AuditEntry>,
+}
+
+impl MaintenanceLog {
+    /// Record for the given ingredient's pump
+    pub fn get(&self, ingredient: Ingredient) -> &PumpRecord {
+        match ingredient {
+            Ingredient::Cocoa => &self.cocoa,
+            Ingredient::Milk => &self.milk,
+            Ingredient::Sugar => &self.sugar,
+        }
+    }
+
+    fn get_mut(&mut self, ingredient: Ingredient) -> &mut PumpRecord {
+        match ingredient {
+            Ingredient::Cocoa => &mut self.cocoa,
+            Ingredient::Milk => &mut self.milk,
+            Ingredient::Sugar => &mut self.sugar,
+        }
+    }
+}
+
+/// Maintenance a pump needs now
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaintenanceDue {
+    pub pump: Ingredient,
+    pub task: MaintenanceTask,
+    /// Which threshold was crossed
+    pub reason: String,
+}
+
+impl MaintenanceDue {
+    /// Two-line reminder for the LCD
+    pub fn screen(&self) -> String {
+        match self.task {
+            MaintenanceTask::Tubing => format!("Replace {}\ntubing", self.pump),
+            MaintenanceTask::Cleaning => format!("Clean {}\npump", self.pump),
+            MaintenanceTask::Calibration => format!("Calibrate {}\npump", self.pump),
+        }
+    }
+}
+
+impl fmt::Display for MaintenanceDue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.screen().replace('\n', " "), self.reason)
+    }
+}
+
+/// The maintenance log and where it is kept
+pub struct MaintenanceStore {
+    /// `None` keeps the log in memory only
+    path: Option<PathBuf>,
+    log: MaintenanceLog,
+    /// Pump counter readings already added to the log
+    counted_ms: HashMap<Ingredient, u64>,
+}
+
+impl MaintenanceStore {
+    /// Store that is never written to disk
+    pub fn in_memory() -> Self {
+        Self { path: None, log: MaintenanceLog::default(), counted_ms: HashMap::new() }
+    }
+
+    /// Open the log at `path`, starting a fresh one if the file does not exist yet
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let log = match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| {
+                HotChocolaBotError::Config(format!("Failed to parse {}: {}", path.display(), e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("No maintenance log at {}, starting a new one", path.display());
+                MaintenanceLog::default()
+            }
+            Err(e) => {
+                return Err(HotChocolaBotError::Storage(format!("Failed to read {}: {}", path.display(), e)).into());
+            }
+        };
+
+        Ok(Self { path: Some(path.to_path_buf()), log, counted_ms: HashMap::new() })
+    }
+
+    pub fn log(&self) -> &MaintenanceLog {
+        &self.log
+    }
+
+    /// Add a pump's usage since the last call at time `at`, given its runtime counter
+    ///
+    /// Returns whether the pump ran; each call in which it did counts as one cycle.
+    pub fn record_runtime(&mut self, pump: Ingredient, counter_ms: u64, at: Timestamp) -> bool {
+        let counted = self.counted_ms.insert(pump, counter_ms).unwrap_or(0);
+        // A counter that went backwards was reset; all of it is new
+        let ran_ms = counter_ms.checked_sub(counted).unwrap_or(counter_ms);
+        if ran_ms == 0 {
+            return false;
+        }
+
+        let record = self.log.get_mut(pump);
+        record.tubing_runtime_ms += ran_ms;
+        record.tubing_cycles += 1;
+        record.total_runtime_ms += ran_ms;
+        record.total_cycles += 1;
+        record.first_used.get_or_insert(at);
+        true
+    }
+
+    /// Keep the latest reservoir estimates, returning whether they changed
+    pub fn record_reservoirs(&mut self, reservoirs: ReservoirRecord) -> bool {
+        let changed = self.log.reservoirs != reservoirs;
+        self.log.reservoirs = reservoirs;
+        changed
+    }
+
+    /// Take a pump's runtime counter as counted without adding to the log
+    ///
+    /// For runs that are not dispenses, such as pre-flight test pulses.
+    pub fn skip_runtime(&mut self, pump: Ingredient, counter_ms: u64) {
+        self.counted_ms.insert(pump, counter_ms);
+    }
+
+    /// Record that maintenance was done, resetting the counters it covers
+    pub fn acknowledge(&mut self, pump: Ingredient, task: MaintenanceTask, note: &str, at: Timestamp) -> AuditEntry {
+        let record = self.log.get_mut(pump);
+        let entry = AuditEntry {
+            at,
+            pump,
+            task,
+            note: note.to_string(),
+            tubing_runtime_ms: record.tubing_runtime_ms,
+            tubing_cycles: record.tubing_cycles,
+        };
+
+        match task {
+            MaintenanceTask::Tubing => {
+                record.tubing_runtime_ms = 0;
+                record.tubing_cycles = 0;
+            }
+            MaintenanceTask::Cleaning => record.last_cleaned = Some(at),
+            MaintenanceTask::Calibration => record.last_calibrated = Some(at),
+        }
+
+        info!("Maintenance acknowledged: {} {}", pump, task);
+        self.log.audit.push(entry.clone());
+        entry
+    }
+
+    /// Maintenance past its threshold at time `at`
+    pub fn due(&self, config: &MaintenanceConfig, at: Timestamp) -> Vec<MaintenanceDue> {
+        let mut due = Vec::new();
+
+        for pump in Ingredient::ALL {
+            let record = self.log.get(pump);
+            let mut add = |task, reason: String| due.push(MaintenanceDue { pump, task, reason });
+
+            let hours = record.tubing_runtime_ms as f32 / 3_600_000.0;
+            if config.tubing_hours > 0.0 && hours >= config.tubing_hours {
+                add(MaintenanceTask::Tubing, format!("{:.1}h of {:.1}h", hours, config.tubing_hours));
+            } else if config.tubing_cycles > 0 && record.tubing_cycles >= config.tubing_cycles {
+                add(MaintenanceTask::Tubing, format!("{} of {} runs", record.tubing_cycles, config.tubing_cycles));
+            }
+
+            if let Some(reason) = overdue(record.last_cleaned, config.cleaning_interval_hours * 3600, at, "cleaned") {
+                add(MaintenanceTask::Cleaning, reason);
+            }
+
+            if let Some(reason) = overdue(record.last_calibrated, config.calibration_interval_days * 86_400, at, "calibrated") {
+                add(MaintenanceTask::Calibration, reason);
+            }
+        }
+
+        due
+    }
+
+    /// Write the log to disk; an in-memory store has nowhere to write
+    pub fn save(&self) -> Result<()> {
+        match self.snapshot() {
+            Some(snapshot) => snapshot.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Copy of the log to save once the store is no longer locked; `None` if kept in memory
+    pub fn snapshot(&self) -> Option<LogSnapshot> {
+        let path = self.path.clone()?;
+        Some(LogSnapshot { path, log: self.log.clone() })
+    }
+}
+
+/// The maintenance log as it was at one moment, ready to be written out
+pub struct LogSnapshot {
+    path: PathBuf,
+    log: MaintenanceLog,
+}
+
+impl LogSnapshot {
+    /// Write the log to disk
+    ///
+    /// The new contents go to a temporary file that then replaces the old
+    /// one, so a power cut never leaves a half-written log behind.
+    pub fn save(&self) -> Result<()> {
+        self.write().fault(HotChocolaBotError::Storage)
+    }
+
+    fn write(&self) -> Result<()> {
+        let path = &self.path;
+        let contents = toml::to_string_pretty(&self.log)
+            .context("Failed to serialize maintenance log")?;
+
+        let mut temp_name = path.as_os_str().to_owned();
+        temp_name.push(".tmp");
+        let temp_path = PathBuf::from(temp_name);
+
+        let mut file = File::create(&temp_path)
+            .with_context(|| format!("Failed to create {}", temp_path.display()))?;
+        file.write_all(contents.as_bytes())
+            .and_then(|()| file.sync_all())
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+
+        fs::rename(&temp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Why a dated task is overdue, if it is; an interval of 0 disables the check
+fn overdue(last: Option<Timestamp>, interval_secs: u64, at: Timestamp, done: &str) -> Option<String> {
+    if interval_secs == 0 {
+        return None;
+    }
+
+    match last {
+        None => Some(format!("never {}", done)),
+        Some(last) if at.saturating_sub(last) >= interval_secs => {
+            Some(format!("last {} {}h ago", done, at.saturating_sub(last) / 3600))
+        }
+        Some(_) => None,
+    }
+}
+
+impl DispenseController {
+    /// Keep maintenance records in `store` instead of in memory
+    ///
+    /// The reservoir estimates pick up where the store's last save left them.
+    pub fn with_maintenance(self, store: MaintenanceStore) -> Self {
+        self.levels.lock().unwrap().restore(&store.log().reservoirs, &self.config.reservoirs);
+        *self.maintenance.lock().unwrap() = store;
+        self
+    }
+
+    /// Add pump runtime since the last call, and the reservoir estimates, to
+    /// the maintenance log and save it
+    pub async fn record_pump_usage(&self) -> Result<()> {
+        let reservoirs = self.levels.lock().unwrap().record();
+        let at = now();
+        let mut changed = false;
+        {
+            let mut store = self.maintenance.lock().unwrap();
+            for ingredient in Ingredient::ALL {
+                changed |= store.record_runtime(ingredient, self.pump(ingredient).total_runtime_ms(), at);
+            }
+            changed |= store.record_reservoirs(reservoirs);
+        }
+
+        if changed {
+            self.save_maintenance().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Leave pump runtime since the last call out of the maintenance log
+    pub(super) fn skip_pump_usage(&self) {
+        let mut store = self.maintenance.lock().unwrap();
+        for ingredient in Ingredient::ALL {
+            store.skip_runtime(ingredient, self.pump(ingredient).total_runtime_ms());
+        }
+    }
+
+    /// Maintenance past its configured threshold
+    pub fn maintenance_due(&self) -> Vec<MaintenanceDue> {
+        self.maintenance.lock().unwrap().due(&self.config.maintenance, now())
+    }
+
+    /// Record maintenance in the log and save it
+    pub async fn acknowledge_maintenance(&self, pump: Ingredient, task: MaintenanceTask, note: &str) -> Result<AuditEntry> {
+        let entry = self.maintenance.lock().unwrap().acknowledge(pump, task, note, now());
+        self.save_maintenance().await?;
+        Ok(entry)
+    }
+
+    /// Write the maintenance log without holding its lock or blocking the runtime
+    async fn save_maintenance(&self) -> Result<()> {
+        // One write at a time, each of the latest log, so an older copy never lands last
+        let _writing = self.maintenance_writes.lock().await;
+        let Some(snapshot) = self.maintenance.lock().unwrap().snapshot() else {
+            return Ok(());
+        };
+
+        tokio::task::spawn_blocking(move || snapshot.save())
+            .await
+            .context("Maintenance log writer panicked")
+            .fault(HotChocolaBotError::Storage)?
+    }
+
+    /// Warn about due maintenance, showing the first reminder on the display
+    pub(super) async fn show_maintenance_reminders(&self) -> Result<bool> {
+        let due = self.maintenance_due();
+        for item in &due {
+            warn!("Maintenance due: {}", item);
+        }
+
+        match due.first() {
+            Some(first) => {
+                self.display.lock().await.show_message(&first.screen()).await.device(Device::Display)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::orders::{OrderQueue, OrderRequest};
+    use crate::control::tests::sim_config;
+    use crate::hardware::factory::ControllerHardware;
+    use crate::hardware::mock::MockDisplay;
+    use crate::safety::SafetyMonitor;
+
+    const DAY: Timestamp = 86_400;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("hotchocolabot-{}-{}.toml", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_log_survives_restart() {
+        let path = temp_path("maintenance-restart");
+        let _ = fs::remove_file(&path);
+
+        let mut store = MaintenanceStore::open(&path).unwrap();
+        assert!(store.record_runtime(Ingredient::Milk, 5000, DAY));
+        assert!(!store.record_runtime(Ingredient::Milk, 5000, 2 * DAY));
+        store.acknowledge(Ingredient::Cocoa, MaintenanceTask::Cleaning, "Sam", 10 * DAY);
+        store.save().unwrap();
+
+        let reopened = MaintenanceStore::open(&path).unwrap();
+        assert_eq!(reopened.log(), store.log());
+        assert_eq!(reopened.log().milk.tubing_runtime_ms, 5000);
+        assert_eq!(reopened.log().milk.first_used, Some(DAY));
+        assert_eq!(reopened.log().audit[0].note, "Sam");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_thresholds_and_acknowledgement() {
+        let config = MaintenanceConfig {
+            tubing_hours: 1.0,
+            tubing_cycles: 0,
+            cleaning_interval_hours: 24,
+            calibration_interval_days: 0,
+            ..MaintenanceConfig::default()
+        };
+        let mut store = MaintenanceStore::in_memory();
+        for pump in Ingredient::ALL {
+            store.acknowledge(pump, MaintenanceTask::Cleaning, "", 0);
+        }
+
+        store.record_runtime(Ingredient::Milk, 3_600_000, DAY / 4);
+        let due = store.due(&config, DAY / 2);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].screen(), "Replace milk\ntubing");
+
+        let entry = store.acknowledge(Ingredient::Milk, MaintenanceTask::Tubing, "new 3mm tube", DAY / 2);
+        assert_eq!(entry.tubing_runtime_ms, 3_600_000);
+        assert_eq!(store.log().milk.tubing_runtime_ms, 0);
+        assert_eq!(store.log().milk.total_runtime_ms, 3_600_000);
+        assert!(store.due(&config, DAY / 2).is_empty());
+
+        // A day later every pump needs cleaning again
+        assert_eq!(store.due(&config, DAY).len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_orders_counted_and_reminder_shown() {
+        let mut config = sim_config();
+        config.maintenance.tubing_cycles = 2;
+        config.maintenance.cleaning_interval_hours = 0;
+        config.maintenance.calibration_interval_days = 0;
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware { display: Box::new(display.clone()), ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        // Pre-flight test pulses are not dispenses; only the orders count
+        let orders = OrderQueue::new();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+        assert_eq!(controller.maintenance.lock().unwrap().log().milk.tubing_cycles, 1);
+        assert!(display.screens().iter().all(|screen| !screen.starts_with("Replace")));
+
+        let orders = OrderQueue::new();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+        assert_eq!(controller.maintenance.lock().unwrap().log().milk.tubing_cycles, 2);
+
+        let orders = OrderQueue::new();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+        assert!(display.get_buffer().starts_with("Replace milk\ntubing"));
+        assert_eq!(controller.maintenance_due().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_order_usage_saved_to_file() {
+        let path = temp_path("maintenance-order");
+        let _ = fs::remove_file(&path);
+
+        let config = sim_config();
+        let controller = DispenseController::with_hardware(config.clone(), ControllerHardware::mock())
+            .with_maintenance(MaintenanceStore::open(&path).unwrap());
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        controller.serve_request(OrderRequest::new("standard"), &orders, &mut safety).await.unwrap();
+
+        let saved = MaintenanceStore::open(&path).unwrap();
+        assert_eq!(saved.log().milk.tubing_cycles, 1);
+        assert!(saved.log().milk.tubing_runtime_ms > 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reservoir_levels_survive_restart() {
+        let path = temp_path("maintenance-reservoirs");
+        let _ = fs::remove_file(&path);
+
+        let config = sim_config();
+        let controller = DispenseController::with_hardware(config.clone(), ControllerHardware::mock())
+            .with_maintenance(MaintenanceStore::open(&path).unwrap());
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        controller.serve_request(OrderRequest::new("standard"), &orders, &mut safety).await.unwrap();
+        let milk = controller.reservoir_remaining(Ingredient::Milk);
+        assert!(milk < config.reservoirs.milk.capacity);
+
+        let restarted = DispenseController::with_hardware(config.clone(), ControllerHardware::mock())
+            .with_maintenance(MaintenanceStore::open(&path).unwrap());
+        assert_eq!(restarted.reservoir_remaining(Ingredient::Milk), milk);
+
+        // A refill is saved even if the order that reported it fails
+        orders.refill(Ingredient::Milk);
+        let _ = controller.serve_request(OrderRequest::new("espresso"), &orders, &mut safety).await;
+        let saved = MaintenanceStore::open(&path).unwrap();
+        assert_eq!(saved.log().reservoirs.milk, Some(config.reservoirs.milk.capacity));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,188 +1,807 @@
 //! Main dispense control logic for HotChocolaBot
 
-use crate::config::{BotConfig, Recipe};
-use crate::hardware::{Pump, TemperatureSensor, Display};
-use crate::hardware::mock::{MockPump, MockTemperatureSensor, MockDisplay};
-use crate::safety::SafetyMonitor;
+pub mod calibration;
+mod cleaning;
+mod interpreter;
+pub mod maintenance;
+pub mod orders;
+pub mod pid;
+pub mod reservoirs;
+pub mod selftest;
+pub mod status;
+pub mod thermostat;
+
+use crate::config::{BotConfig, Ingredient, Recipe};
+#[cfg(feature = "http")]
+use crate::config::RecipeConfig;
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::hardware::{Pump, Display, EmergencyStop, Adc, LevelSensor};
+use crate::hardware::factory::ControllerHardware;
+use crate::hardware::glyphs::Glyph;
+use crate::safety::{OperationTimeout, SafetyMonitor, SafetyStates};
+use crate::safety::thermal::{ThermalFault, ThermalWatchdogHandle};
+use crate::control::maintenance::MaintenanceStore;
+use crate::control::orders::{Order, OrderEvent, OrderId, OrderQueue, OrderRequest};
+use crate::control::reservoirs::ReservoirLevels;
+use crate::control::status::{SharedStatusLed, StatusLedHandle};
+use crate::control::thermostat::{SharedHeater, SharedSensor, Thermostat};
 use anyhow::{Result, Context};
+#[cfg(any(feature = "http", test))]
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+use tokio::time::{Duration, Instant};
 use tracing::{info, warn, error};
 
+/// How often the progress bar is redrawn while a pump runs
+const PROGRESS_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_millis(250);
+
 /// Main controller for the hot chocolate dispensing system
 pub struct DispenseController {
     config: BotConfig,
     cocoa_pump: Box<dyn Pump>,
     milk_pump: Box<dyn Pump>,
     sugar_pump: Box<dyn Pump>,
-    temp_sensor: Box<dyn TemperatureSensor>,
-    display: Box<dyn Display>,
+    temp_sensor: SharedSensor,
+    heater: SharedHeater,
+    mixer: Option<Box<dyn Pump>>,
+    water_pump: Option<Box<dyn Pump>>,
+    thermostat: Thermostat,
+    display: Mutex<Box<dyn Display>>,
+    emergency_stop: Option<Box<dyn EmergencyStop>>,
+    /// Count of emergency stop presses, bumped from the button callback
+    /// and by remote stops
+    emergency_stop_press: Arc<watch::Sender<u64>>,
+    status_led: SharedStatusLed,
+    /// Heater watchdog, kept running from the first operation until it trips
+    thermal_watchdog: Mutex<Option<ThermalWatchdogHandle>>,
+    adc: Mutex<Box<dyn Adc>>,
+    /// Order being served, for step events and the display's queue line
+    current_order: std::sync::Mutex<Option<CurrentOrder>>,
+    /// Last step shown, reported if an operation times out
+    current_step: std::sync::Mutex<String>,
+    /// Time the current dispense has spent waiting for the operator
+    operator_wait: std::sync::Mutex<OperatorWait>,
+    /// When the current dispense times out, not counting operator waits
+    operation_deadline: std::sync::Mutex<Option<Instant>>,
+    /// Pump wear and service history
+    maintenance: std::sync::Mutex<MaintenanceStore>,
+    /// Held while the maintenance log is written out
+    maintenance_writes: Mutex<()>,
+    /// Estimated ingredient left in each reservoir
+    levels: std::sync::Mutex<ReservoirLevels>,
+    level_sensors: Mutex<Vec<(Ingredient, Box<dyn LevelSensor>)>>,
 }
 
-impl DispenseController {
-    /// Create new dispense controller with real hardware
-    #[cfg(target_os = "linux")]
-    pub async fn new(config: BotConfig) -> Result<Self> {
-        use crate::hardware::pump::GpioPump;
-        use crate::hardware::sensor::I2cTemperatureSensor;
-        use crate::hardware::display::I2cLcdDisplay;
+/// The order the service loop is working on
+struct CurrentOrder {
+    id: OrderId,
+    queue: OrderQueue,
+}
 
-        info!("Initializing hardware...");
+/// Time spent waiting for the operator, which the operation timeout does not count
+#[derive(Debug, Default)]
+struct OperatorWait {
+    waited: Duration,
+    since: Option<Instant>,
+}
 
-        let cocoa_pump = Box::new(GpioPump::new(config.hardware.cocoa_pump_pin, "Cocoa")?);
-        let milk_pump = Box::new(GpioPump::new(config.hardware.milk_pump_pin, "Milk")?);
-        let sugar_pump = Box::new(GpioPump::new(config.hardware.sugar_pump_pin, "Sugar")?);
+impl OperatorWait {
+    fn start(&mut self, now: Instant) {
+        self.since = Some(now);
+    }
 
-        let temp_sensor = Box::new(I2cTemperatureSensor::new(config.hardware.temp_sensor_addr)?);
-        let display = Box::new(I2cLcdDisplay::new(config.hardware.lcd_addr, 2, 16)?);
+    fn stop(&mut self, now: Instant) {
+        self.waited = self.waited_by(now);
+        self.since = None;
+    }
 
-        Ok(Self {
-            config,
-            cocoa_pump,
-            milk_pump,
-            sugar_pump,
-            temp_sensor,
-            display,
-        })
+    /// Total waited by `now`, including a wait still in progress
+    fn waited_by(&self, now: Instant) -> Duration {
+        self.waited + self.since.map_or(Duration::ZERO, |since| now - since)
     }
+}
+
+/// Outcome of a short test pulse on one pump
+#[derive(Debug, Clone, Copy)]
+pub struct PumpPulse {
+    /// Runtime the pump accounted for the pulse
+    pub runtime_ms: u64,
+    /// Whether the pump still reports running after the pulse
+    pub running_after: bool,
+}
 
-    /// Create new dispense controller with mock hardware (for testing/development)
-    #[cfg(not(target_os = "linux"))]
+impl DispenseController {
+    /// Create new dispense controller on the configured hardware backend
     pub async fn new(config: BotConfig) -> Result<Self> {
-        info!("Initializing MOCK hardware for testing...");
+        let hardware = ControllerHardware::build(&config)?;
+        let controller = Self::with_hardware(config, hardware);
 
-        let cocoa_pump = Box::new(MockPump::new("Cocoa"));
-        let milk_pump = Box::new(MockPump::new("Milk"));
-        let sugar_pump = Box::new(MockPump::new("Sugar"));
+        controller.display.lock().await.load_glyphs(&Glyph::ALL).await
+            .device(Device::Display)?;
 
-        let temp_sensor = Box::new(MockTemperatureSensor::new(20.0));
-        let display = Box::new(MockDisplay::new());
+        Ok(controller)
+    }
 
-        Ok(Self {
+    /// Assemble a controller from already-initialised hardware
+    pub fn with_hardware(config: BotConfig, mut hardware: ControllerHardware) -> Self {
+        let temp_sensor: SharedSensor = Arc::new(Mutex::new(hardware.temp_sensor));
+        let heater: SharedHeater = Arc::new(Mutex::new(hardware.heater));
+        let thermostat = Thermostat::new(
+            Arc::clone(&heater),
+            Arc::clone(&temp_sensor),
+            config.heater.clone(),
+        );
+
+        let emergency_stop_press = Arc::new(watch::Sender::new(0));
+        let press_tx = Arc::clone(&emergency_stop_press);
+        match hardware.emergency_stop.as_mut() {
+            Some(button) => button.on_press(Box::new(move || {
+                press_tx.send_modify(|presses| *presses += 1);
+            })),
+            None => warn!("No emergency stop button fitted"),
+        }
+
+        let levels = ReservoirLevels::full(&config.reservoirs);
+
+        Self {
             config,
-            cocoa_pump,
-            milk_pump,
-            sugar_pump,
+            cocoa_pump: hardware.cocoa_pump,
+            milk_pump: hardware.milk_pump,
+            sugar_pump: hardware.sugar_pump,
             temp_sensor,
-            display,
-        })
+            heater,
+            mixer: hardware.mixer,
+            water_pump: hardware.water_pump,
+            thermostat,
+            display: Mutex::new(hardware.display),
+            emergency_stop: hardware.emergency_stop,
+            emergency_stop_press,
+            status_led: Arc::new(Mutex::new(hardware.status_led)),
+            thermal_watchdog: Mutex::new(None),
+            adc: Mutex::new(hardware.adc),
+            current_order: std::sync::Mutex::new(None),
+            current_step: std::sync::Mutex::new(String::new()),
+            operator_wait: std::sync::Mutex::new(OperatorWait::default()),
+            operation_deadline: std::sync::Mutex::new(None),
+            maintenance: std::sync::Mutex::new(MaintenanceStore::in_memory()),
+            maintenance_writes: Mutex::new(()),
+            levels: std::sync::Mutex::new(levels),
+            level_sensors: Mutex::new(hardware.level_sensors),
+        }
+    }
+
+    /// Keep the status LED in step with the safety state until the handle is dropped
+    pub fn show_safety_state(&self, states: watch::Receiver<SafetyStates>) -> StatusLedHandle {
+        StatusLedHandle::spawn(Arc::clone(&self.status_led), states)
     }
 
-    /// Main control loop
-    pub async fn run(&self, safety_monitor: &mut SafetyMonitor) -> Result<()> {
-        info!("HotChocolaBot ready. Waiting for commands...");
+    /// Main control loop: serve orders one at a time until the queue closes
+    pub async fn run(&self, safety_monitor: &mut SafetyMonitor, orders: &OrderQueue) -> Result<()> {
+        info!("HotChocolaBot ready. Waiting for orders...");
+        self.account_test_pulses();
 
-        self.display.show_message("HotChocolaBot\nReady!").await?;
+        self.display.lock().await.show_message("HotChocolaBot\nReady!").await.device(Device::Display)?;
 
         // In educational mode, show system status
         if self.config.education.show_internals {
             self.show_system_status().await?;
         }
 
-        // For demonstration, dispense one standard hot chocolate
-        info!("Dispensing standard hot chocolate...");
-        self.dispense_recipe(&self.config.recipes.standard, safety_monitor).await?;
+        self.show_maintenance_reminders().await?;
+
+        // One receiver for the whole loop, so a press while an order is
+        // finishing or a reset runs is still pending at the next turn
+        let mut presses = self.emergency_stop_presses();
+
+        loop {
+            // The heater may have been left on between orders
+            self.watch_heater(safety_monitor).await;
+
+            tokio::select! {
+                biased;
+
+                _ = Self::wait_for_press(presses.clone()) => self.latch_emergency_stop(safety_monitor).await?,
+                fault = self.thermal_trip() => self.latch_thermal_fault(fault, safety_monitor).await?,
+                _ = orders.reset_requested() => self.reset_emergency_stop(orders, safety_monitor).await?,
+                order = orders.next() => {
+                    let Some(order) = order else { break };
+
+                    // A failed order does not stop the loop; only display errors do
+                    let _outcome = self.serve_order(order, orders, safety_monitor).await?;
+                }
+            }
+
+            // A latched stop has been dealt with, whichever way the press came in
+            if safety_monitor.is_emergency_stop() {
+                presses.borrow_and_update();
+            }
+        }
+
+        info!("Order queue closed");
+        Ok(())
+    }
+
+    /// Stop as if the emergency stop button had been pressed
+    ///
+    /// For stops from outside the machine, such as the network API; an
+    /// operation in progress is aborted and the safety monitor latched.
+    #[cfg(any(feature = "http", test))]
+    pub fn press_emergency_stop(&self) {
+        warn!("Remote emergency stop");
+        self.emergency_stop_press.send_modify(|presses| *presses += 1);
+    }
+
+    /// Latch an emergency stop pressed between orders
+    async fn latch_emergency_stop(&self, safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        self.shutdown_outputs().await;
+        safety_monitor.trigger_emergency_stop("Emergency stop pressed while idle");
+        let error = HotChocolaBotError::EmergencyStop("pressed while idle".to_string()).into();
+        self.show_fault(&error).await
+    }
+
+    /// Reset a latched emergency stop on request and run pre-flight again
+    ///
+    /// The outcome goes back to whoever asked; only display errors stop the loop.
+    async fn reset_emergency_stop(&self, orders: &OrderQueue, safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        let Some(reply) = orders.take_reset() else {
+            return Ok(());
+        };
+
+        let outcome = self.reset_and_preflight(safety_monitor).await;
+        match &outcome {
+            Ok(()) => self.display.lock().await.show_message("HotChocolaBot\nReady!").await.device(Device::Display)?,
+            Err(e) => {
+                error!("Reset failed: {:#}", e);
+                self.show_fault(e).await?;
+            }
+        }
+
+        // The requester may have given up waiting
+        let _ = reply.send(outcome);
+        Ok(())
+    }
+
+    async fn reset_and_preflight(&self, safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        if !matches!(safety_monitor.state(), SafetyStates::Unsafe) {
+            return Err(HotChocolaBotError::NotReady(format!(
+                "nothing to reset (state {:?})",
+                safety_monitor.state()
+            )).into());
+        }
 
-        self.display.show_message("Complete!\nEnjoy!").await?;
+        safety_monitor.reset_emergency_stop()?;
+        if !safety_monitor.run_preflight_checks(self).await? {
+            return Err(safety_monitor.preflight_error());
+        }
 
         Ok(())
     }
 
+    /// Serve a single order straight away and return how it went
+    ///
+    /// Confirmation steps are answered through `orders`, as for queued orders.
+    pub async fn serve_request(
+        &self,
+        request: OrderRequest,
+        orders: &OrderQueue,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        orders.submit(request)?;
+        let order = orders.next().await.ok_or(HotChocolaBotError::QueueClosed)?;
+
+        self.serve_order(order, orders, safety_monitor).await?
+    }
+
+    /// Serve one order, reporting the outcome as an event
+    ///
+    /// The inner result is the order's outcome; the outer one fails only if
+    /// the display could not show it.
+    async fn serve_order(
+        &self,
+        order: Order,
+        orders: &OrderQueue,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<Result<()>> {
+        info!("Serving order #{}: {:?}", order.id, order.request);
+        orders.emit(OrderEvent::Started { id: order.id });
+        // Pumping since the last order was pre-flight or self-test pulses
+        self.account_test_pulses();
+
+        for ingredient in orders.take_refills() {
+            self.refill(ingredient);
+        }
+
+        *self.current_order.lock().unwrap() = Some(CurrentOrder {
+            id: order.id,
+            queue: orders.clone(),
+        });
+        let result = self.dispense_order(&order, safety_monitor).await;
+        *self.current_order.lock().unwrap() = None;
+        self.account_pump_usage().await;
+
+        match &result {
+            Ok(()) => {
+                orders.emit(OrderEvent::Completed { id: order.id });
+                self.display.lock().await.show_message("Complete!\nEnjoy!").await.device(Device::Display)?;
+            }
+            Err(e) => {
+                error!("Order #{} failed: {:#}", order.id, e);
+                orders.emit(OrderEvent::Failed { id: order.id, reason: format!("{:#}", e) });
+
+                self.show_fault(e).await?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Check the system is fit to serve, then dispense the order's recipe
+    async fn dispense_order(&self, order: &Order, safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        Self::ready_for_order(safety_monitor)?;
+        self.check_clean_due()?;
+
+        let recipe = order.request.modifiers.apply(self.recipe(&order.request.recipe)?)?;
+        self.check_reservoirs(&recipe.program()).await?;
+
+        self.dispense_recipe(&recipe, safety_monitor).await
+    }
+
+    /// Every configured recipe
+    #[cfg(feature = "http")]
+    pub fn recipes(&self) -> &RecipeConfig {
+        &self.config.recipes
+    }
+
+    /// Look up a configured recipe by name
+    pub fn recipe(&self, name: &str) -> Result<&Recipe> {
+        self.config.recipes.get(name).ok_or_else(|| {
+            HotChocolaBotError::UnknownRecipe {
+                name: name.to_string(),
+                known: self.config.recipes.names().map(str::to_string).collect(),
+            }
+            .into()
+        })
+    }
+
+    /// Safety gate between orders
+    ///
+    /// An anomaly left by the previous order is recovered from if the safety
+    /// monitor allows it; anything else short of `Safe` refuses the order.
+    fn ready_for_order(safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        match safety_monitor.state() {
+            SafetyStates::Safe => Ok(()),
+            SafetyStates::Anomaly => {
+                warn!("Recovering from previous anomaly before next order");
+                safety_monitor.recover()
+            }
+            SafetyStates::Unsafe => {
+                Err(HotChocolaBotError::ManualResetRequired("system unsafe".to_string()).into())
+            }
+            state => Err(HotChocolaBotError::NotReady(format!("safety state {:?}", state)).into()),
+        }
+    }
+
+    /// Take pump runtime off the reservoirs and add it to the maintenance log
+    ///
+    /// Failing to save the log must not stop service.
+    async fn account_pump_usage(&self) {
+        self.record_reservoir_usage();
+
+        if let Err(e) = self.record_pump_usage().await {
+            error!("Failed to save maintenance log: {:#}", e);
+        }
+    }
+
+    /// Take test pulses off the reservoirs without counting them as pump wear
+    fn account_test_pulses(&self) {
+        self.record_reservoir_usage();
+        self.skip_pump_usage();
+    }
+
+    /// Show a failure's label and fault code for students to look up
+    pub async fn show_fault(&self, error: &anyhow::Error) -> Result<()> {
+        self.display.lock().await.show_message(&HotChocolaBotError::screen_for(error)).await
+            .device(Device::Display)
+    }
+
+    /// Show the current step, with the queue status on the second line
+    async fn show_step(&self, step: &str) -> Result<()> {
+        *self.current_step.lock().unwrap() = step.to_string();
+
+        let current = self.current_order.lock().unwrap()
+            .as_ref()
+            .map(|current| (current.id, current.queue.clone()));
+
+        let Some((id, queue)) = current else {
+            return self.display.lock().await.show_message(step).await.device(Device::Display);
+        };
+
+        let status = format!("#{} ({} waiting)", id, queue.waiting());
+        self.display.lock().await.show_message(&format!("{}\n{}", step, status)).await
+            .device(Device::Display)?;
+        queue.emit(OrderEvent::Step { id, step: step.to_string() });
+        Ok(())
+    }
+
     /// Dispense hot chocolate according to recipe
+    ///
+    /// Refused unless the safety state machine is in `Safe`; the machine is
+    /// `Operating` for the duration and returns to `Safe` on success. The
+    /// whole sequence, less any wait for the operator, must finish within
+    /// `SafetyConfig::operation_timeout`.
     pub async fn dispense_recipe(
         &self,
         recipe: &Recipe,
         safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        safety_monitor.begin_operation()?;
+        *self.current_step.lock().unwrap() = "Starting".to_string();
+        let result = self.run_with_deadline(recipe, safety_monitor).await;
+        Self::finish_operation(result, safety_monitor)
+    }
+
+    /// Run a dispense, aborting it if it overruns the operation timeout
+    ///
+    /// Time spent waiting for the operator to confirm a step is not counted.
+    /// On expiry the dispense future is dropped, which halts a running pump
+    /// mid-call; every output is then forced off explicitly as well.
+    async fn run_with_deadline(
+        &self,
+        recipe: &Recipe,
+        safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        let timeout_secs = self.config.safety.operation_timeout;
+        let limit = Duration::from_secs(timeout_secs);
+        let started = Instant::now();
+        *self.operator_wait.lock().unwrap() = OperatorWait::default();
+        *self.operation_deadline.lock().unwrap() = Some(started + limit);
+
+        let finished = {
+            let dispense = self.run_dispense(recipe, safety_monitor);
+            tokio::pin!(dispense);
+            let mut deadline = started + limit;
+
+            loop {
+                tokio::select! {
+                    // A heat step cut short to the deadline reports its own fault
+                    biased;
+                    result = &mut dispense => break Some(result),
+                    _ = tokio::time::sleep_until(deadline) => {
+                        let now = Instant::now();
+                        let extended = started + limit + self.operator_wait.lock().unwrap().waited_by(now);
+                        if extended <= now {
+                            break None;
+                        }
+                        deadline = extended;
+                    }
+                }
+            }
+        };
+        *self.operation_deadline.lock().unwrap() = None;
+
+        match finished {
+            Some(result) => result,
+            None => {
+                let step = self.current_step.lock().unwrap().clone();
+                error!("Dispense timed out after {}s at: {}", timeout_secs, step);
+
+                self.shutdown_outputs().await;
+                let error = HotChocolaBotError::Timeout(OperationTimeout { timeout_secs, step }).into();
+                self.show_fault(&error).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// How long a heat step may take
+    ///
+    /// `HeaterConfig::max_heat_time`, cut short to end a sample before the
+    /// operation deadline, so milk that heats too slowly for the timeout is
+    /// reported as a heat timeout rather than a generic one.
+    fn heat_limit(&self) -> Duration {
+        let limit = Duration::from_secs(self.config.heater.max_heat_time);
+        let Some(deadline) = *self.operation_deadline.lock().unwrap() else {
+            return limit;
+        };
+
+        let now = Instant::now();
+        let left = (deadline + self.operator_wait.lock().unwrap().waited_by(now)).saturating_duration_since(now);
+        limit.min(left.saturating_sub(Duration::from_millis(self.config.heater.sample_interval_ms)))
+    }
+
+    /// Close an operation in the safety state machine according to its outcome
+    fn finish_operation<T>(result: Result<T>, safety_monitor: &mut SafetyMonitor) -> Result<T> {
+        match result {
+            Ok(value) => {
+                safety_monitor.complete_operation()?;
+                Ok(value)
+            }
+            Err(e) => {
+                // An emergency stop has already taken the machine to Unsafe
+                if !safety_monitor.is_emergency_stop() {
+                    if let Err(transition) = safety_monitor.report_anomaly(&format!("{:#}", e)) {
+                        error!("Failed to record anomaly: {:?}", transition);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Dispense sequence proper, run while the safety machine is `Operating`
+    async fn run_dispense(
+        &self,
+        recipe: &Recipe,
+        safety_monitor: &mut SafetyMonitor,
     ) -> Result<()> {
         info!("Starting dispense sequence");
 
         // Check temperature before starting
-        let temp = self.temp_sensor.read_temperature().await
-            .context("Failed to read temperature")?;
+        let temp = self.read_temperature().await?;
 
         safety_monitor.validate_temperature(temp)?;
 
-        // Display recipe info
-        self.display.show_message(&format!("Temp: {:.1}C\nPreparing...", temp)).await?;
+        // Every step must be within the safety limits before anything moves
+        let program = self.prepare_program(recipe)?;
 
-        // Add observation delay in educational mode
-        if self.config.education.observation_delay_ms > 0 {
-            tokio::time::sleep(tokio::time::Duration::from_millis(
-                self.config.education.observation_delay_ms
-            )).await;
+        // Watch the button and the heater for the whole heated part of the sequence
+        let presses = self.emergency_stop_presses();
+        self.watch_heater(safety_monitor).await;
+
+        tokio::select! {
+            result = self.run_program(&program, safety_monitor) => result?,
+            fault = self.thermal_trip() => {
+                // Dropping the dispense future has already halted any running pump
+                self.latch_thermal_fault(fault.clone(), safety_monitor).await?;
+                return Err(HotChocolaBotError::Thermal(fault).into());
+            }
+            _ = Self::wait_for_press(presses) => {
+                return self.abort_for_emergency_stop(safety_monitor).await;
+            }
         }
 
-        // Phase 1: Milk (base)
-        self.display.show_message("Adding milk...").await?;
-        self.dispense_ingredient("milk", self.milk_pump.as_ref(), recipe.milk_ms, safety_monitor).await?;
+        info!("Dispense complete!");
 
-        // Phase 2: Cocoa
-        self.display.show_message("Adding cocoa...").await?;
-        self.dispense_ingredient("cocoa", self.cocoa_pump.as_ref(), recipe.cocoa_ms, safety_monitor).await?;
+        Ok(())
+    }
 
-        // Phase 3: Sugar
-        self.display.show_message("Adding sugar...").await?;
-        self.dispense_ingredient("sugar", self.sugar_pump.as_ref(), recipe.sugar_ms, safety_monitor).await?;
+    /// Force every output into its safe (off) state
+    async fn shutdown_outputs(&self) {
+        warn!("Forcing all outputs off");
 
-        info!("Dispense complete!");
-        safety_monitor.record_success();
+        for ingredient in Ingredient::ALL {
+            if let Err(e) = self.pump(ingredient).stop().await {
+                error!("Failed to stop {} pump: {:?}", ingredient, e);
+            }
+        }
 
-        Ok(())
+        if let Some(mixer) = &self.mixer {
+            if let Err(e) = mixer.stop().await {
+                error!("Failed to stop mixer: {:?}", e);
+            }
+        }
+
+        if let Some(water_pump) = &self.water_pump {
+            if let Err(e) = water_pump.stop().await {
+                error!("Failed to stop water pump: {:?}", e);
+            }
+        }
+
+        if let Err(e) = self.thermostat.off().await {
+            error!("Failed to switch heater off: {:?}", e);
+        }
+    }
+
+    /// Start the thermal watchdog unless it is already running
+    ///
+    /// It then keeps watching, between operations too, until it trips or
+    /// the controller is dropped; once its fault is dealt with the next call
+    /// starts a fresh one.
+    async fn watch_heater(&self, safety_monitor: &SafetyMonitor) {
+        let mut watchdog = self.thermal_watchdog.lock().await;
+        if watchdog.is_none() {
+            *watchdog = Some(safety_monitor.spawn_thermal_watchdog(
+                Arc::clone(&self.temp_sensor),
+                Arc::clone(&self.heater),
+            ));
+        }
+    }
+
+    /// Resolve when the thermal watchdog trips, taking its fault so it is dealt with once
+    async fn thermal_trip(&self) -> ThermalFault {
+        let mut watchdog = self.thermal_watchdog.lock().await;
+        let Some(running) = watchdog.as_mut() else {
+            return std::future::pending().await;
+        };
+
+        let fault = running.tripped().await;
+        *watchdog = None;
+        fault
+    }
+
+    /// Stop everything after the thermal watchdog tripped
+    async fn latch_thermal_fault(&self, fault: ThermalFault, safety_monitor: &mut SafetyMonitor) -> Result<()> {
+        // The watchdog has already switched the heater off
+        self.shutdown_outputs().await;
+        safety_monitor.handle_thermal_fault(&fault);
+        self.show_fault(&HotChocolaBotError::Thermal(fault).into()).await
+    }
+
+    /// Receiver that sees only presses from now on
+    fn emergency_stop_presses(&self) -> watch::Receiver<u64> {
+        self.emergency_stop_press.subscribe()
+    }
+
+    /// Resolve on the next emergency stop press
+    async fn wait_for_press(mut presses: watch::Receiver<u64>) {
+        if presses.changed().await.is_err() {
+            // The controller holds the sender, so this cannot happen; never resolve
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Stop everything after the button was pressed mid-operation
+    ///
+    /// Called once the operation future has been dropped, so pumps have
+    /// already halted; each is stopped again explicitly regardless.
+    async fn abort_for_emergency_stop<T>(&self, safety_monitor: &mut SafetyMonitor) -> Result<T> {
+        self.shutdown_outputs().await;
+        safety_monitor.trigger_emergency_stop("Emergency stop button pressed");
+        let error = HotChocolaBotError::EmergencyStop("operation aborted by button".to_string()).into();
+        self.show_fault(&error).await?;
+        Err(error)
+    }
+
+    /// Pump delivering the given ingredient
+    fn pump(&self, ingredient: Ingredient) -> &dyn Pump {
+        match ingredient {
+            Ingredient::Cocoa => self.cocoa_pump.as_ref(),
+            Ingredient::Milk => self.milk_pump.as_ref(),
+            Ingredient::Sugar => self.sugar_pump.as_ref(),
+        }
     }
 
     /// Dispense a single ingredient with safety monitoring
     async fn dispense_ingredient(
         &self,
-        name: &str,
-        pump: &dyn Pump,
+        ingredient: Ingredient,
         duration_ms: u64,
         safety_monitor: &mut SafetyMonitor,
+    ) -> Result<()> {
+        Self::check_dispense(ingredient, duration_ms, &self.config, safety_monitor)?;
+
+        if duration_ms == 0 {
+            info!("No {} in recipe, skipping", ingredient);
+            return Ok(());
+        }
+
+        info!("Dispensing {} for {}ms", ingredient, duration_ms);
+
+        let dispense = self.pump(ingredient).dispense(duration_ms);
+        tokio::pin!(dispense);
+        let started = tokio::time::Instant::now();
+        // The step just shown stays up for the first interval
+        let mut ticks = tokio::time::interval_at(started + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+
+        // Fill the progress bar while the pump runs
+        loop {
+            tokio::select! {
+                result = &mut dispense => {
+                    result.device(Device::Pump(ingredient))?;
+                    break;
+                }
+                _ = ticks.tick() => {
+                    let fraction = started.elapsed().as_millis() as f32 / duration_ms as f32;
+                    self.show_dispense_progress(ingredient, fraction).await;
+                }
+            }
+        }
+        self.show_dispense_progress(ingredient, 1.0).await;
+        self.observation_delay().await;
+
+        Ok(())
+    }
+
+    /// Show how far a dispense has got; a display fault must not stop the pump
+    async fn show_dispense_progress(&self, ingredient: Ingredient, fraction: f32) {
+        let label = format!("{} {}", Glyph::Cup.symbol(), ingredient);
+        if let Err(e) = self.display.lock().await.show_progress(&label, fraction.min(1.0)).await {
+            warn!("Failed to show dispense progress: {:#}", e);
+        }
+    }
+
+    /// Refuse a pump run that is too long or follows an emergency stop
+    fn check_dispense(
+        ingredient: Ingredient,
+        duration_ms: u64,
+        config: &BotConfig,
+        safety_monitor: &mut SafetyMonitor,
     ) -> Result<()> {
         // Safety check: ensure not exceeding max runtime
-        if duration_ms > self.config.safety.max_pump_runtime * 1000 {
-            let msg = format!("Pump runtime {} exceeds maximum {}",
-                            duration_ms,
-                            self.config.safety.max_pump_runtime * 1000);
-            error!("{}", msg);
-            safety_monitor.trigger_emergency_stop(&msg);
-            anyhow::bail!(msg);
+        let max_ms = config.safety.max_pump_runtime * 1000;
+        if duration_ms > max_ms {
+            let error = HotChocolaBotError::PumpRuntimeExceeded { ingredient, requested_ms: duration_ms, max_ms };
+            error!("{}", error);
+            safety_monitor.trigger_emergency_stop(&error.to_string());
+            return Err(error.into());
         }
 
         // Check for emergency stop
         if safety_monitor.is_emergency_stop() {
-            anyhow::bail!("Emergency stop active - cannot dispense");
+            return Err(HotChocolaBotError::EmergencyStop(format!("cannot dispense {}", ingredient)).into());
         }
 
-        info!("Dispensing {} for {}ms", name, duration_ms);
-
-        // Cast to mutable to call dispense
-        // Note: This is a limitation of the current design - in production,
-        // we'd use interior mutability or refactor the trait
-        let pump_mut = unsafe {
-            let ptr = pump as *const dyn Pump as *mut dyn Pump;
-            &mut *ptr
-        };
-
-        pump_mut.dispense(duration_ms).await?;
+        Ok(())
+    }
 
-        // Add observation delay in educational mode
+    /// Pause so students can see what just happened, in educational mode
+    async fn observation_delay(&self) {
         if self.config.education.observation_delay_ms > 0 {
             tokio::time::sleep(tokio::time::Duration::from_millis(
                 self.config.education.observation_delay_ms
             )).await;
         }
+    }
 
-        Ok(())
+    /// Briefly run one pump and report how it responded (pre-flight check)
+    pub async fn pulse_pump(&self, ingredient: Ingredient, duration_ms: u64) -> Result<PumpPulse> {
+        let pump = self.pump(ingredient);
+        let before = pump.total_runtime_ms();
+
+        pump.dispense(duration_ms).await
+            .context(format!("{} pump test pulse failed", ingredient))
+            .device(Device::Pump(ingredient))?;
+
+        Ok(PumpPulse {
+            runtime_ms: pump.total_runtime_ms().saturating_sub(before),
+            running_after: pump.is_running(),
+        })
+    }
+
+    /// Read the milk temperature sensor
+    pub async fn read_temperature(&self) -> Result<f32> {
+        self.temp_sensor.lock().await.read_temperature().await
+            .context("Failed to read temperature")
+            .device(Device::TemperatureSensor)
+    }
+
+    /// Whether the temperature sensor reports itself healthy
+    pub async fn temperature_sensor_healthy(&self) -> bool {
+        self.temp_sensor.lock().await.is_healthy().await
+    }
+
+    /// Emergency stop input state, or `None` if no button is fitted
+    pub async fn emergency_stop_pressed(&self) -> Option<bool> {
+        match &self.emergency_stop {
+            Some(button) => Some(button.is_pressed().await),
+            None => None,
+        }
+    }
+
+    /// Read the supply rail voltage through the ADC divider
+    pub async fn supply_voltage(&self) -> Result<f32> {
+        let hardware = &self.config.hardware;
+        let volts = self.adc.lock().await
+            .read_voltage(hardware.supply_adc_channel).await
+            .context("Failed to read supply voltage ADC")
+            .device(Device::Adc)?;
+
+        Ok(volts * hardware.supply_divider_ratio)
     }
 
     /// Show system status on display
     async fn show_system_status(&self) -> Result<()> {
-        let temp = self.temp_sensor.read_temperature().await?;
+        let temp = self.read_temperature().await?;
 
         let status = format!(
-            "Temp: {:.1}C\nPumps: Ready",
-            temp
+            "{}Temp: {:.1}{}C\nPumps: Ready",
+            Glyph::Thermometer.symbol(),
+            temp,
+            Glyph::Degree.symbol(),
         );
 
-        self.display.show_message(&status).await?;
+        self.display.lock().await.show_message(&status).await.device(Device::Display)?;
 
         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 
@@ -190,6 +809,7 @@ async fn show_system_status(&self) -> Result<()> {
     }
 
     /// Get pump runtime statistics (for educational display)
+    #[cfg(any(feature = "http", test))]
     pub fn get_pump_stats(&self) -> PumpStats {
         PumpStats {
             cocoa_runtime_ms: self.cocoa_pump.total_runtime_ms(),
@@ -197,10 +817,29 @@ pub fn get_pump_stats(&self) -> PumpStats {
             sugar_runtime_ms: self.sugar_pump.total_runtime_ms(),
         }
     }
+
+    /// Order being served right now, if any
+    #[cfg(feature = "http")]
+    pub fn current_order_id(&self) -> Option<OrderId> {
+        self.current_order.lock().unwrap().as_ref().map(|current| current.id)
+    }
+
+    /// Whether the pump for an ingredient is running right now
+    #[cfg(any(feature = "tui", feature = "http", test))]
+    pub fn pump_running(&self, ingredient: Ingredient) -> bool {
+        self.pump(ingredient).is_running()
+    }
+
+    /// Get current heater output (0.0 - 1.0)
+    #[cfg(any(feature = "tui", feature = "http", test))]
+    pub async fn heater_power(&self) -> f32 {
+        self.heater.lock().await.power()
+    }
 }
 
 /// Statistics about pump usage
-#[derive(Debug)]
+#[cfg(any(feature = "http", test))]
+#[derive(Debug, Serialize)]
 pub struct PumpStats {
     pub cocoa_runtime_ms: u64,
     pub milk_runtime_ms: u64,
@@ -208,24 +847,520 @@ pub struct PumpStats {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use crate::config::SafetyConfig;
+    use crate::config::{Backend, Dose, Step};
+    use crate::hardware::TemperatureSensor;
+    use crate::control::maintenance::MaintenanceTask;
+    use crate::control::orders::OrderRequest;
+    use crate::hardware::mock::{MockDisplay, MockEmergencyStop, MockPump, MockTemperatureSensor};
+    use tokio::time::{Duration, Instant};
+
+    /// Default config on simulated hardware, whichever features are built
+    pub(crate) fn sim_config() -> BotConfig {
+        let mut config = BotConfig::default();
+        config.hardware.backend = Backend::Mock;
+        config
+    }
 
     #[tokio::test]
     async fn test_controller_creation() {
-        let config = BotConfig::default();
+        let config = sim_config();
         let controller = DispenseController::new(config).await;
         assert!(controller.is_ok());
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn test_dispense_recipe() {
-        let config = BotConfig::default();
+        let config = sim_config();
         let controller = DispenseController::new(config.clone()).await.unwrap();
         let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
 
-        let result = controller.dispense_recipe(&config.recipes.standard, &mut safety).await;
+        let result = controller.dispense_recipe(&config.recipes["standard"], &mut safety).await;
         assert!(result.is_ok());
+        assert!(matches!(safety.state(), SafetyStates::Safe));
+
+        // Heater must be off once the drink is done
+        assert_eq!(controller.heater_power().await, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_thermal_fault_aborts_dispense() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        // Heater element that has come loose: full power, no temperature rise
+        let sensor: Box<dyn TemperatureSensor> = Box::new(MockTemperatureSensor::new(20.0));
+        *controller.temp_sensor.lock().await = sensor;
+
+        let result = controller.dispense_recipe(&config.recipes["standard"], &mut safety).await;
+        assert!(result.is_err());
+        assert!(safety.is_emergency_stop());
+        assert!(matches!(safety.state(), SafetyStates::Unsafe));
+        assert_eq!(controller.heater_power().await, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_thermal_fault_between_orders_latches() {
+        let config = sim_config();
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware {
+            // Heater left on with its element come loose: no temperature rise
+            temp_sensor: Box::new(MockTemperatureSensor::new(20.0)),
+            display: Box::new(display.clone()),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let mut states = safety.subscribe();
+        let orders = OrderQueue::new();
+        let remote = orders.clone();
+
+        let operator = async {
+            controller.heater.lock().await.set_power(1.0).await.unwrap();
+            let tripped = states.wait_for(|state| matches!(state, SafetyStates::Unsafe));
+            let _ = tokio::time::timeout(Duration::from_secs(60), tripped).await;
+            remote.close();
+        };
+        let (served, ()) = tokio::join!(controller.run(&mut safety, &orders), operator);
+
+        served.unwrap();
+        assert!(safety.is_emergency_stop());
+        assert_eq!(controller.heater_power().await, 0.0);
+        assert!(display.get_buffer().starts_with("THERMAL FAULT"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dispense_refused_unless_safe() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+
+        // Initialized: pre-flight has not run
+        assert!(controller.dispense_recipe(&config.recipes["standard"], &mut safety).await.is_err());
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms, 0);
+        assert!(matches!(safety.state(), SafetyStates::Initialized));
+
+        // Unsafe: emergency stop pressed after pre-flight
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let after_preflight = controller.get_pump_stats().milk_runtime_ms;
+        safety.trigger_emergency_stop("test");
+        assert!(controller.dispense_recipe(&config.recipes["standard"], &mut safety).await.is_err());
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms, after_preflight);
+
+        // Back to Safe only via reset and a fresh pre-flight
+        safety.reset_emergency_stop().unwrap();
+        assert!(controller.dispense_recipe(&config.recipes["standard"], &mut safety).await.is_err());
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        assert!(controller.dispense_recipe(&config.recipes["standard"], &mut safety).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_dispense_reports_anomaly() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        // Milk too cold to start
+        let sensor: Box<dyn TemperatureSensor> = Box::new(MockTemperatureSensor::new(1.0));
+        *controller.temp_sensor.lock().await = sensor;
+
+        assert!(controller.dispense_recipe(&config.recipes["standard"], &mut safety).await.is_err());
+        assert!(matches!(safety.state(), SafetyStates::Anomaly));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recipe_target_outside_safety_window_rejected() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let mut recipe = config.recipes["standard"].clone();
+        recipe.target_temp = config.safety.max_temperature + 10.0;
+
+        assert!(controller.dispense_recipe(&recipe, &mut safety).await.is_err());
+        assert_eq!(controller.heater_power().await, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_emergency_stop_interrupts_dispense() {
+        let config = sim_config();
+        let estop = MockEmergencyStop::new();
+        let pumps = [MockPump::new("Cocoa"), MockPump::new("Milk"), MockPump::new("Sugar")];
+        let hardware = ControllerHardware {
+            cocoa_pump: Box::new(pumps[0].clone()),
+            milk_pump: Box::new(pumps[1].clone()),
+            sugar_pump: Box::new(pumps[2].clone()),
+            emergency_stop: Some(Box::new(estop.clone())),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let before = controller.get_pump_stats().milk_runtime_ms;
+
+        let press = async {
+            // Wait until milk is flowing, then hit the button mid-pour
+            while !pumps[1].is_running() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            estop.clone().press();
+            Instant::now()
+        };
+
+        let (result, pressed_at) = tokio::join!(
+            controller.dispense_recipe(&config.recipes["standard"], &mut safety),
+            press,
+        );
+
+        assert!(result.is_err());
+        assert!(pressed_at.elapsed() < Duration::from_millis(50), "pumps stopped too late");
+        assert!(pumps.iter().all(|pump| !pump.is_running()));
+        assert!(controller.get_pump_stats().milk_runtime_ms - before < 1000);
+        assert!(safety.is_emergency_stop());
+        assert!(matches!(safety.state(), SafetyStates::Unsafe));
+        assert_eq!(controller.heater_power().await, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_status_readable_while_pump_runs() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let observer = async {
+            while !controller.pump_running(Ingredient::Milk) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            // None of these may wait for the pump to finish
+            let started = Instant::now();
+            let _stats = controller.get_pump_stats();
+            controller.read_temperature().await.unwrap();
+            controller.display.lock().await.show_message("Status\nMilk running").await.unwrap();
+            assert!(controller.pump_running(Ingredient::Milk));
+            started.elapsed()
+        };
+
+        let (result, observed_in) = tokio::join!(
+            controller.dispense_recipe(&config.recipes["standard"], &mut safety),
+            observer,
+        );
+
+        result.unwrap();
+        assert!(observed_in < Duration::from_millis(10), "readers blocked for {:?}", observed_in);
+    }
+
+    /// Drain every event already published
+    fn drain(events: &mut tokio::sync::broadcast::Receiver<OrderEvent>) -> Vec<OrderEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dispense_shows_progress_bar() {
+        let config = sim_config();
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware { display: Box::new(display.clone()), ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        controller.dispense_recipe(&config.recipes["standard"], &mut safety).await.unwrap();
+        let screens = display.screens();
+
+        let steps: Vec<_> = screens.iter().filter(|screen| !screen.ends_with('%')).collect();
+        assert_eq!(steps, ["Heating to 65°C", "Adding milk...", "Adding cocoa...", "Adding sugar..."]);
+
+        // The bar only ever grows, ending full
+        let milk: Vec<_> = screens.iter().filter(|screen| screen.starts_with("☕ milk\n")).collect();
+        assert!(milk.windows(2).all(|pair| pair[0].matches('█').count() <= pair[1].matches('█').count()));
+        assert_eq!(milk.last().unwrap().as_str(), "☕ milk\n███████████ 100%");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_serves_orders_in_turn() {
+        let config = sim_config();
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware {
+            display: Box::new(display.clone()),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.submit(OrderRequest::new("espresso")).unwrap();
+        orders.submit("light sweetness=0".parse().unwrap()).unwrap();
+        orders.close();
+
+        let mut watcher = orders.subscribe();
+        let watch_first_order = async {
+            // While order #1 pours, the display shows the step and the queue behind it
+            loop {
+                if let Ok(OrderEvent::Step { id: 1, step }) = watcher.recv().await {
+                    if step == "Adding milk..." {
+                        return display.get_buffer();
+                    }
+                }
+            }
+        };
+
+        let (result, screen) = tokio::join!(controller.run(&mut safety, &orders), watch_first_order);
+        result.unwrap();
+        assert_eq!(screen, "Adding milk...\n#1 (2 waiting)");
+
+        let outcomes: Vec<_> = drain(&mut events).into_iter()
+            .filter(|event| !matches!(event, OrderEvent::Queued { .. } | OrderEvent::Step { .. }))
+            .collect();
+        assert_eq!(outcomes[..2], [OrderEvent::Started { id: 1 }, OrderEvent::Completed { id: 1 }]);
+        assert!(matches!(&outcomes[3], OrderEvent::Failed { id: 2, reason } if reason.contains("Unknown recipe")));
+        assert_eq!(outcomes[5], OrderEvent::Completed { id: 3 });
+        assert!(matches!(safety.state(), SafetyStates::Safe));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_order_shows_fault_code() {
+        let config = sim_config();
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware {
+            display: Box::new(display.clone()),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        orders.submit(OrderRequest::new("espresso")).unwrap();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+
+        assert_eq!(display.get_buffer(), "UNKNOWN RECIPE\nCode E401");
+    }
+
+    /// Serve one order, returning the fault screen it left on the display
+    async fn fault_screen(config: BotConfig, hardware: ControllerHardware, preflight: bool) -> String {
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware { display: Box::new(display.clone()), ..hardware };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        if preflight {
+            assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        }
+
+        let orders = OrderQueue::new();
+        let error = controller.serve_request(OrderRequest::new("standard"), &orders, &mut safety).await.unwrap_err();
+        assert_ne!(HotChocolaBotError::code_of(&error), 999, "unclassified: {:#}", error);
+        display.get_buffer()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_driver_failure_shows_hardware_code() {
+        let mut config = sim_config();
+        config.safety.preflight_pump_pulse_ms = 0;
+        let milk = MockPump::new("Milk");
+        milk.set_failing(true);
+        let hardware = ControllerHardware { milk_pump: Box::new(milk), ..ControllerHardware::mock() };
+
+        assert_eq!(fault_screen(config, hardware, true).await, "PUMP FAULT\nCode E101");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_order_before_preflight_shows_safety_code() {
+        let config = sim_config();
+
+        assert_eq!(fault_screen(config, ControllerHardware::mock(), false).await, "NOT READY\nCode E207");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heat_timeout_shows_timeout_code() {
+        let mut config = sim_config();
+        config.heater.max_heat_time = 5;
+
+        assert_eq!(fault_screen(config, ControllerHardware::mock(), true).await, "HEAT TIMEOUT\nCode E502");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heat_cut_short_by_operation_timeout() {
+        let mut config = sim_config();
+        config.safety.operation_timeout = 5;
+        assert_eq!(config.heater.max_heat_time, 600);
+
+        assert_eq!(fault_screen(config, ControllerHardware::mock(), true).await, "HEAT TIMEOUT\nCode E502");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unwritable_log_shows_storage_code() {
+        let path = std::env::temp_dir().join("hotchocolabot-missing-dir").join("maintenance.toml");
+        let controller = DispenseController::with_hardware(sim_config(), ControllerHardware::mock())
+            .with_maintenance(MaintenanceStore::open(&path).unwrap());
+
+        let error = controller.acknowledge_maintenance(Ingredient::Milk, MaintenanceTask::Tubing, "").await.unwrap_err();
+
+        assert_eq!(HotChocolaBotError::code_of(&error), 302);
+        assert_eq!(HotChocolaBotError::screen_for(&error), "STORAGE ERROR\nCode E302");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_stop_latches_until_reset() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let mut states = safety.subscribe();
+        let orders = OrderQueue::new();
+        let remote = orders.clone();
+
+        let operator = async {
+            // Let the loop settle into waiting for orders
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            controller.press_emergency_stop();
+            states.wait_for(|state| matches!(state, SafetyStates::Unsafe)).await.unwrap();
+
+            remote.request_reset().await.unwrap();
+            assert!(matches!(*states.borrow(), SafetyStates::Safe));
+
+            // Nothing left to reset
+            assert!(remote.request_reset().await.is_err());
+
+            remote.submit(OrderRequest::new("standard")).unwrap();
+            remote.close();
+        };
+        let (served, ()) = tokio::join!(controller.run(&mut safety, &orders), operator);
+
+        served.unwrap();
+        assert!(controller.get_pump_stats().milk_runtime_ms > 0);
+    }
+
+    /// Display that presses the emergency stop as an order completes
+    struct PressOnComplete {
+        display: MockDisplay,
+        button: MockEmergencyStop,
+    }
+
+    #[async_trait::async_trait]
+    impl Display for PressOnComplete {
+        async fn write(&mut self, text: &str) -> Result<()> {
+            if text.starts_with("Complete!") {
+                self.button.press();
+            }
+            self.display.write(text).await
+        }
+
+        async fn clear(&mut self) -> Result<()> {
+            self.display.clear().await
+        }
+
+        async fn set_cursor(&mut self, row: u8, col: u8) -> Result<()> {
+            self.display.set_cursor(row, col).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_between_orders_refuses_next_order() {
+        let config = sim_config();
+        let button = MockEmergencyStop::new();
+        let hardware = ControllerHardware {
+            display: Box::new(PressOnComplete { display: MockDisplay::new(), button: button.clone() }),
+            emergency_stop: Some(Box::new(button)),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+
+        controller.run(&mut safety, &orders).await.unwrap();
+
+        let events = drain(&mut events);
+        assert!(events.contains(&OrderEvent::Completed { id: 1 }));
+        assert!(events.iter().any(|event| matches!(event, OrderEvent::Failed { id: 2, .. })));
+        assert!(matches!(safety.state(), SafetyStates::Unsafe));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_serve_request_returns_order_outcome() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        let orders = OrderQueue::new();
+
+        controller.serve_request(OrderRequest::new("standard"), &orders, &mut safety).await.unwrap();
+
+        let error = controller.serve_request(OrderRequest::new("espresso"), &orders, &mut safety).await.unwrap_err();
+        assert_eq!(HotChocolaBotError::code_of(&error), 401);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_orders_refused_after_emergency_stop() {
+        let config = sim_config();
+        let controller = DispenseController::new(config.clone()).await.unwrap();
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+        safety.trigger_emergency_stop("test");
+        let before = controller.get_pump_stats().milk_runtime_ms;
+
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+
+        controller.run(&mut safety, &orders).await.unwrap();
+
+        let failed = drain(&mut events).into_iter().any(|event| {
+            matches!(event, OrderEvent::Failed { id: 1, reason } if reason.contains("manual reset"))
+        });
+        assert!(failed);
+        assert_eq!(controller.get_pump_stats().milk_runtime_ms, before);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hung_pump_aborted_at_operation_timeout() {
+        let mut config = sim_config();
+        config.safety.operation_timeout = 10;
+        let milk = MockPump::new("Milk");
+        let hardware = ControllerHardware {
+            milk_pump: Box::new(milk.clone()),
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        milk.set_hung(true);
+        let recipe = Recipe {
+            target_temp: 65.0,
+            steps: vec![Step::Dispense(Dose { ingredient: Ingredient::Milk, amount: Some(50.0), ms: None })],
+            ..Recipe::default()
+        };
+
+        let started = Instant::now();
+        let err = controller.dispense_recipe(&recipe, &mut safety).await.unwrap_err();
+
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        let Some(HotChocolaBotError::Timeout(timeout)) = HotChocolaBotError::find(&err) else {
+            panic!("expected a typed timeout error, got {:#}", err);
+        };
+        assert_eq!(timeout.step, "Adding milk...");
+        assert!(!milk.is_running());
+        assert!(matches!(safety.state(), SafetyStates::Anomaly));
+        assert_eq!(controller.heater_power().await, 0.0);
     }
 }
@@ -0,0 +1,654 @@
+//! Order queue feeding the dispense service loop
+//!
+//! Orders arrive from any number of sources (buttons, the CLI, the network)
+//! and are served one at a time, in arrival order, by
+//! `DispenseController::run`. Progress is published as `OrderEvent`s.
+
+use crate::config::{Ingredient, Recipe, Step};
+use crate::error::HotChocolaBotError;
+#[cfg(any(feature = "rpi", test))]
+use crate::hardware::OrderButton;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::sync::{broadcast, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// Largest scale factor a modifier may apply to an ingredient
+pub const MAX_MODIFIER_SCALE: f32 = 2.0;
+
+/// Identifier assigned to an order when it is queued
+pub type OrderId = u64;
+
+/// Per-order adjustments to a recipe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modifiers {
+    /// Scale factor for the cocoa amount
+    pub strength: f32,
+    /// Scale factor for the sugar amount
+    pub sweetness: f32,
+    /// Target temperature overriding the recipe's
+    pub target_temp: Option<f32>,
+}
+
+impl Default for Modifiers {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            sweetness: 1.0,
+            target_temp: None,
+        }
+    }
+}
+
+impl Modifiers {
+    /// Recipe with these modifiers applied
+    ///
+    /// The target temperature is not checked here; the safety monitor
+    /// validates it before heating like any other recipe target.
+    pub fn apply(&self, recipe: &Recipe) -> Result<Recipe> {
+        for (name, scale) in [("strength", self.strength), ("sweetness", self.sweetness)] {
+            if !(0.0..=MAX_MODIFIER_SCALE).contains(&scale) {
+                return Err(HotChocolaBotError::InvalidOrder(format!(
+                    "{} must be between 0 and {}", name, MAX_MODIFIER_SCALE
+                )).into());
+            }
+        }
+
+        let mut recipe = recipe.clone();
+        scale_ingredient(&mut recipe, Ingredient::Cocoa, self.strength);
+        scale_ingredient(&mut recipe, Ingredient::Sugar, self.sweetness);
+
+        // Every heat step of a custom program goes to the requested temperature
+        if let Some(target_temp) = self.target_temp {
+            recipe.target_temp = target_temp;
+            for step in &mut recipe.steps {
+                if let Step::Heat { temp } = step {
+                    *temp = target_temp;
+                }
+            }
+        }
+
+        Ok(recipe)
+    }
+}
+
+/// Scale both the amount and the legacy pump time of one ingredient,
+/// in the recipe fields and in every step that dispenses it
+fn scale_ingredient(recipe: &mut Recipe, ingredient: Ingredient, scale: f32) {
+    let (amount, legacy_ms) = match ingredient {
+        Ingredient::Cocoa => (&mut recipe.cocoa, &mut recipe.cocoa_ms),
+        Ingredient::Milk => (&mut recipe.milk, &mut recipe.milk_ms),
+        Ingredient::Sugar => (&mut recipe.sugar, &mut recipe.sugar_ms),
+    };
+    scale_dose(amount, legacy_ms, scale);
+
+    for dose in recipe.steps.iter_mut().flat_map(Step::doses_mut) {
+        if dose.ingredient == ingredient {
+            scale_dose(&mut dose.amount, &mut dose.ms, scale);
+        }
+    }
+}
+
+fn scale_dose(amount: &mut Option<f32>, ms: &mut Option<u64>, scale: f32) {
+    if let Some(amount) = amount {
+        *amount *= scale;
+    }
+    if let Some(ms) = ms {
+        *ms = (*ms as f32 * scale).round() as u64;
+    }
+}
+
+/// What a customer asked for
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    /// Name of a recipe in `[recipes]`
+    pub recipe: String,
+    pub modifiers: Modifiers,
+}
+
+impl OrderRequest {
+    /// Order for a recipe without modifiers
+    pub fn new(recipe: impl Into<String>) -> Self {
+        Self {
+            recipe: recipe.into(),
+            modifiers: Modifiers::default(),
+        }
+    }
+}
+
+impl FromStr for OrderRequest {
+    type Err = anyhow::Error;
+
+    /// Parse `<recipe> [strength=<x>] [sweetness=<x>] [temp=<celsius>]`
+    fn from_str(line: &str) -> Result<Self> {
+        parse_request(line).map_err(|e| HotChocolaBotError::InvalidOrder(format!("{:#}", e)).into())
+    }
+}
+
+/// Parse an order line, before classifying any failure as an invalid order
+fn parse_request(line: &str) -> Result<OrderRequest> {
+    let mut words = line.split_whitespace();
+    let recipe = words.next().context("Order needs a recipe name")?;
+    let mut request = OrderRequest::new(recipe);
+
+    for word in words {
+        let (key, value) = word.split_once('=')
+            .with_context(|| format!("Expected key=value, got '{}'", word))?;
+        let value: f32 = value.parse()
+            .with_context(|| format!("Invalid number for {}: '{}'", key, value))?;
+
+        match key {
+            "strength" => request.modifiers.strength = value,
+            "sweetness" => request.modifiers.sweetness = value,
+            "temp" => request.modifiers.target_temp = Some(value),
+            other => anyhow::bail!("Unknown modifier '{}'", other),
+        }
+    }
+
+    Ok(request)
+}
+
+/// An accepted order waiting for, or being, served
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub id: OrderId,
+    pub request: OrderRequest,
+}
+
+/// Progress of an order through the queue
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderEvent {
+    /// Accepted at `position` in the queue (1 = next to be served)
+    Queued { id: OrderId, position: usize },
+    Started { id: OrderId },
+    /// The order moved on to a new step of the dispense
+    Step { id: OrderId, step: String },
+    /// The order is paused until the operator answers `prompt`
+    AwaitingConfirmation { id: OrderId, prompt: String },
+    Completed { id: OrderId },
+    Failed { id: OrderId, reason: String },
+    /// Withdrawn before it was served
+    #[cfg(any(feature = "http", test))]
+    Cancelled { id: OrderId },
+}
+
+struct QueueState {
+    orders: VecDeque<Order>,
+    next_id: OrderId,
+    closed: bool,
+}
+
+struct QueueShared {
+    state: Mutex<QueueState>,
+    available: Notify,
+    events: broadcast::Sender<OrderEvent>,
+    /// Where the operator's answer goes while a step awaits confirmation
+    confirmation: Mutex<Option<oneshot::Sender<bool>>>,
+    /// Reservoirs the operator has refilled since the controller last looked
+    refills: Mutex<Vec<Ingredient>>,
+    /// Where the outcome goes while an emergency stop reset is pending
+    reset: Mutex<Option<oneshot::Sender<Result<()>>>>,
+    reset_requested: Notify,
+}
+
+/// First-in, first-out queue of orders, cheap to clone for each source
+#[derive(Clone)]
+pub struct OrderQueue {
+    shared: Arc<QueueShared>,
+}
+
+impl OrderQueue {
+    /// Create an empty, open queue
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(64);
+
+        Self {
+            shared: Arc::new(QueueShared {
+                state: Mutex::new(QueueState {
+                    orders: VecDeque::new(),
+                    next_id: 1,
+                    closed: false,
+                }),
+                available: Notify::new(),
+                events,
+                confirmation: Mutex::new(None),
+                refills: Mutex::new(Vec::new()),
+                reset: Mutex::new(None),
+                reset_requested: Notify::new(),
+            }),
+        }
+    }
+
+    /// Add an order to the back of the queue
+    pub fn submit(&self, request: OrderRequest) -> Result<OrderId> {
+        let (id, position) = {
+            let mut state = self.shared.state.lock().unwrap();
+            if state.closed {
+                return Err(HotChocolaBotError::QueueClosed.into());
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            state.orders.push_back(Order { id, request });
+            (id, state.orders.len())
+        };
+
+        info!("Order #{} queued at position {}", id, position);
+        self.emit(OrderEvent::Queued { id, position });
+        self.shared.available.notify_one();
+
+        Ok(id)
+    }
+
+    /// Number of orders waiting to be served
+    pub fn waiting(&self) -> usize {
+        self.shared.state.lock().unwrap().orders.len()
+    }
+
+    /// Position of a waiting order (1 = next), if still queued
+    #[cfg(any(feature = "http", test))]
+    pub fn position(&self, id: OrderId) -> Option<usize> {
+        self.shared.state.lock().unwrap()
+            .orders.iter()
+            .position(|order| order.id == id)
+            .map(|index| index + 1)
+    }
+
+    /// Orders waiting to be served, next first
+    #[cfg(any(feature = "http", test))]
+    pub fn pending(&self) -> Vec<Order> {
+        self.shared.state.lock().unwrap().orders.iter().cloned().collect()
+    }
+
+    /// Withdraw a waiting order; one already being served cannot be cancelled
+    #[cfg(any(feature = "http", test))]
+    pub fn cancel(&self, id: OrderId) -> Result<()> {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            let index = state.orders.iter().position(|order| order.id == id)
+                .ok_or_else(|| HotChocolaBotError::InvalidOrder(format!("order #{} is not waiting", id)))?;
+            state.orders.remove(index);
+        }
+
+        info!("Order #{} cancelled", id);
+        self.emit(OrderEvent::Cancelled { id });
+        Ok(())
+    }
+
+    /// Stop accepting orders; those already queued are still served
+    pub fn close(&self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.available.notify_one();
+    }
+
+    /// Next order to serve, or `None` once closed and drained
+    pub async fn next(&self) -> Option<Order> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(order) = state.orders.pop_front() {
+                    return Some(order);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+
+            self.shared.available.notified().await;
+        }
+    }
+
+    /// Answer the confirmation the current order is waiting for
+    pub fn answer(&self, confirmed: bool) -> Result<()> {
+        let reply = self.shared.confirmation.lock().unwrap().take()
+            .ok_or_else(|| HotChocolaBotError::InvalidOrder("nothing is waiting for confirmation".to_string()))?;
+
+        // The order may have been aborted meanwhile
+        let _ = reply.send(confirmed);
+        Ok(())
+    }
+
+    /// Ask the operator to confirm `prompt` for order `id`
+    pub(crate) async fn confirm(&self, id: OrderId, prompt: &str) -> Result<bool> {
+        let (reply, answer) = oneshot::channel();
+        *self.shared.confirmation.lock().unwrap() = Some(reply);
+        self.emit(OrderEvent::AwaitingConfirmation { id, prompt: prompt.to_string() });
+
+        answer.await.map_err(|_| HotChocolaBotError::OperatorDeclined(format!("{} (abandoned)", prompt)).into())
+    }
+
+    /// Ask the service loop to reset a latched emergency stop
+    ///
+    /// Resolves once the loop has reset the safety monitor and re-run
+    /// pre-flight, with the outcome of both. The reset goes through the same
+    /// safety state machine as a reset at the machine.
+    #[cfg(any(feature = "http", test))]
+    pub async fn request_reset(&self) -> Result<()> {
+        let (reply, outcome) = oneshot::channel();
+        {
+            let mut reset = self.shared.reset.lock().unwrap();
+            if reset.is_some() {
+                return Err(HotChocolaBotError::NotReady("a reset is already in progress".to_string()).into());
+            }
+            *reset = Some(reply);
+        }
+        self.shared.reset_requested.notify_one();
+
+        outcome.await.map_err(|_| HotChocolaBotError::NotReady("service loop stopped before the reset".to_string()))?
+    }
+
+    /// Resolve once a reset has been requested
+    pub(crate) async fn reset_requested(&self) {
+        self.shared.reset_requested.notified().await;
+    }
+
+    /// Where to send the outcome of the pending reset, if one is pending
+    pub(crate) fn take_reset(&self) -> Option<oneshot::Sender<Result<()>>> {
+        self.shared.reset.lock().unwrap().take()
+    }
+
+    /// Report that an ingredient's reservoir has been filled up
+    pub fn refill(&self, ingredient: Ingredient) {
+        info!("{} reservoir refilled", ingredient);
+        self.shared.refills.lock().unwrap().push(ingredient);
+    }
+
+    /// Refills reported since the last call
+    pub(crate) fn take_refills(&self) -> Vec<Ingredient> {
+        std::mem::take(&mut *self.shared.refills.lock().unwrap())
+    }
+
+    /// Receive events for every order from now on
+    #[cfg(test)]
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderEvent> {
+        self.shared.events.subscribe()
+    }
+
+    /// Publish an event; nobody listening is not an error
+    pub(crate) fn emit(&self, event: OrderEvent) {
+        let _ = self.shared.events.send(event);
+    }
+}
+
+impl Default for OrderQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Somewhere orders come from
+#[async_trait]
+pub trait OrderSource: Send {
+    /// Short name for logs
+    fn name(&self) -> &str;
+
+    /// Submit orders to `queue` until the source is exhausted
+    async fn run(&mut self, queue: OrderQueue) -> Result<()>;
+}
+
+/// Run a source in the background, logging rather than propagating its failure
+pub fn spawn_source(mut source: Box<dyn OrderSource>, queue: OrderQueue) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!("Accepting orders from {}", source.name());
+        match source.run(queue).await {
+            Ok(()) => info!("Order source {} finished", source.name()),
+            Err(e) => error!("Order source {} failed: {:#}", source.name(), e),
+        }
+    })
+}
+
+/// Orders typed one per line, e.g. on the console
+pub struct LineOrderSource<R> {
+    name: String,
+    lines: R,
+}
+
+impl LineOrderSource<BufReader<tokio::io::Stdin>> {
+    /// Read orders from standard input
+    pub fn stdin() -> Self {
+        Self::new("stdin", BufReader::new(tokio::io::stdin()))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin + Send> LineOrderSource<R> {
+    /// Read orders from any line-oriented reader
+    pub fn new(name: &str, lines: R) -> Self {
+        Self {
+            name: name.to_string(),
+            lines,
+        }
+    }
+}
+
+#[async_trait]
+impl<R: AsyncBufRead + Unpin + Send> OrderSource for LineOrderSource<R> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn run(&mut self, queue: OrderQueue) -> Result<()> {
+        let mut lines = (&mut self.lines).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let confirmed = match line.trim().to_lowercase().as_str() {
+                "" => continue,
+                "y" | "yes" => Some(true),
+                "n" | "no" => Some(false),
+                _ => None,
+            };
+
+            // Yes/no lines answer a waiting confirmation step
+            if let Some(confirmed) = confirmed {
+                if let Err(e) = queue.answer(confirmed) {
+                    warn!("Ignoring '{}' from {}: {:#}", line.trim(), self.name, e);
+                }
+                continue;
+            }
+
+            // "refill <ingredient>" reports a topped-up reservoir
+            if let Some(name) = line.trim().strip_prefix("refill ") {
+                match name.trim().parse::<Ingredient>() {
+                    Ok(ingredient) => queue.refill(ingredient),
+                    Err(e) => warn!("Ignoring refill from {}: {:#}", self.name, e),
+                }
+                continue;
+            }
+
+            // A typo should not end the session
+            match line.parse::<OrderRequest>() {
+                Ok(request) => {
+                    queue.submit(request)?;
+                }
+                Err(e) => warn!("Ignoring order from {}: {:#}", self.name, e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Push buttons on the rig, each ordering one recipe
+///
+/// A press while a confirmation step is waiting answers it instead, so the
+/// same buttons tell the bot the cup is in place.
+#[cfg(any(feature = "rpi", test))]
+pub struct ButtonOrderSource {
+    buttons: Vec<(String, Box<dyn OrderButton>)>,
+}
+
+#[cfg(any(feature = "rpi", test))]
+impl ButtonOrderSource {
+    /// Buttons paired with the recipe each one orders
+    pub fn new(buttons: Vec<(String, Box<dyn OrderButton>)>) -> Self {
+        Self { buttons }
+    }
+
+    /// GPIO buttons from `hardware.order_buttons`
+    #[cfg(feature = "rpi")]
+    pub fn gpio(config: &crate::config::HardwareConfig) -> Result<Self> {
+        use crate::error::{Device, DeviceContext};
+        use crate::hardware::button::GpioButton;
+
+        let mut buttons: Vec<(String, Box<dyn OrderButton>)> = Vec::new();
+        for button in &config.order_buttons {
+            let gpio = GpioButton::new(button.pin).device(Device::OrderButton)?;
+            buttons.push((button.recipe.clone(), Box::new(gpio)));
+        }
+
+        Ok(Self::new(buttons))
+    }
+}
+
+#[cfg(any(feature = "rpi", test))]
+#[async_trait]
+impl OrderSource for ButtonOrderSource {
+    fn name(&self) -> &str {
+        "buttons"
+    }
+
+    async fn run(&mut self, queue: OrderQueue) -> Result<()> {
+        // Presses arrive on the GPIO interrupt thread
+        let (press_tx, mut presses) = tokio::sync::mpsc::unbounded_channel();
+        for (recipe, button) in &mut self.buttons {
+            let press_tx = press_tx.clone();
+            let recipe = recipe.clone();
+            button.on_press(Box::new(move || {
+                let _ = press_tx.send(recipe.clone());
+            }));
+        }
+        drop(press_tx);
+
+        while let Some(recipe) = presses.recv().await {
+            if queue.answer(true).is_ok() {
+                info!("Confirmed with the {} button", recipe);
+                continue;
+            }
+            queue.submit(OrderRequest::new(recipe))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BotConfig;
+    use crate::hardware::mock::MockButton;
+
+    #[test]
+    fn test_parse_order_with_modifiers() {
+        let request: OrderRequest = "rich strength=1.5 sweetness=0 temp=60".parse().unwrap();
+
+        assert_eq!(request.recipe, "rich");
+        assert_eq!(request.modifiers.strength, 1.5);
+        assert_eq!(request.modifiers.sweetness, 0.0);
+        assert_eq!(request.modifiers.target_temp, Some(60.0));
+
+        assert!("".parse::<OrderRequest>().is_err());
+        assert!("standard milk=2".parse::<OrderRequest>().is_err());
+    }
+
+    #[test]
+    fn test_modifiers_scale_recipe() {
+        let config = BotConfig::default();
+        let base = &config.recipes["standard"];
+        let modifiers = Modifiers { strength: 2.0, sweetness: 0.0, target_temp: Some(55.0) };
+
+        let recipe = modifiers.apply(base).unwrap();
+        assert_eq!(recipe.cocoa, base.cocoa.map(|cocoa| cocoa * 2.0));
+        assert_eq!(recipe.sugar, base.sugar.map(|_| 0.0));
+        assert_eq!(recipe.milk, base.milk);
+        assert_eq!(recipe.target_temp, 55.0);
+
+        let too_strong = Modifiers { strength: 5.0, ..Modifiers::default() };
+        assert!(too_strong.apply(base).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queue_is_first_in_first_out() {
+        let queue = OrderQueue::new();
+        let mut events = queue.subscribe();
+
+        let first = queue.submit(OrderRequest::new("standard")).unwrap();
+        let second = queue.submit(OrderRequest::new("rich")).unwrap();
+        assert_eq!(queue.position(second), Some(2));
+        assert_eq!(events.recv().await.unwrap(), OrderEvent::Queued { id: first, position: 1 });
+        assert_eq!(events.recv().await.unwrap(), OrderEvent::Queued { id: second, position: 2 });
+
+        queue.close();
+        assert!(queue.submit(OrderRequest::new("light")).is_err());
+
+        assert_eq!(queue.next().await.unwrap().id, first);
+        assert_eq!(queue.next().await.unwrap().id, second);
+        assert!(queue.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_only_waiting_orders() {
+        let queue = OrderQueue::new();
+        let mut events = queue.subscribe();
+        let first = queue.submit(OrderRequest::new("standard")).unwrap();
+        let second = queue.submit(OrderRequest::new("rich")).unwrap();
+
+        queue.cancel(second).unwrap();
+        assert_eq!(queue.pending().len(), 1);
+        assert!(queue.cancel(second).is_err());
+
+        // Being served, so no longer waiting
+        assert_eq!(queue.next().await.unwrap().id, first);
+        let error = queue.cancel(first).unwrap_err();
+        assert_eq!(HotChocolaBotError::code_of(&error), 405);
+
+        let cancelled = std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| event == OrderEvent::Cancelled { id: second });
+        assert!(cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_line_source_skips_bad_lines() {
+        let queue = OrderQueue::new();
+        let input: &[u8] = b"standard\n\nstandard extra\nrefill milk\nrefill water\nlight sweetness=0.5\n";
+        let mut source = LineOrderSource::new("test", input);
+
+        source.run(queue.clone()).await.unwrap();
+
+        assert_eq!(queue.waiting(), 2);
+        assert_eq!(queue.next().await.unwrap().request.recipe, "standard");
+        assert_eq!(queue.next().await.unwrap().request.modifiers.sweetness, 0.5);
+        assert_eq!(queue.take_refills(), [Ingredient::Milk]);
+    }
+
+    #[tokio::test]
+    async fn test_buttons_order_or_confirm() {
+        let queue = OrderQueue::new();
+        let standard = MockButton::new();
+        let rich = MockButton::new();
+        let source = ButtonOrderSource::new(vec![
+            ("standard".to_string(), Box::new(standard.clone())),
+            ("rich".to_string(), Box::new(rich.clone())),
+        ]);
+        let buttons = spawn_source(Box::new(source), queue.clone());
+        tokio::task::yield_now().await;
+
+        rich.press();
+        standard.press();
+        assert_eq!(queue.next().await.unwrap().request.recipe, "rich");
+        assert_eq!(queue.next().await.unwrap().request.recipe, "standard");
+
+        // A press while a confirmation step waits answers it
+        let operator = queue.clone();
+        let confirmed = tokio::spawn(async move { operator.confirm(2, "Cup in?").await });
+        tokio::task::yield_now().await;
+        rich.press();
+        assert!(confirmed.await.unwrap().unwrap());
+        assert_eq!(queue.waiting(), 0);
+
+        buttons.abort();
+    }
+}
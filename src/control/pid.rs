@@ -0,0 +1,82 @@
+//! PID controller used for closed-loop heater regulation
+//!
+//! Output is clamped to the heater's power range (0.0 - 1.0). The integral
+//! term is clamped as well so the controller does not "wind up" while the
+//! heater is saturated during the initial warm-up.
+
+/// Discrete PID controller with output clamping and anti-windup
+#[derive(Debug, Clone)]
+pub struct PidController {
+    kp: f32,
+    ki: f32,
+    kd: f32,
+    output_min: f32,
+    output_max: f32,
+    integral: f32,
+    last_measurement: Option<f32>,
+}
+
+impl PidController {
+    /// Create a new PID controller with output limited to 0.0 - 1.0
+    pub fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            output_min: 0.0,
+            output_max: 1.0,
+            integral: 0.0,
+            last_measurement: None,
+        }
+    }
+
+    /// Compute the next output for a measurement taken `dt_secs` after the previous one
+    pub fn update(&mut self, setpoint: f32, measurement: f32, dt_secs: f32) -> f32 {
+        let error = setpoint - measurement;
+
+        // Derivative on measurement avoids a kick when the setpoint changes
+        let derivative = match self.last_measurement {
+            Some(last) if dt_secs > 0.0 => -(measurement - last) / dt_secs,
+            _ => 0.0,
+        };
+        self.last_measurement = Some(measurement);
+
+        if self.ki > 0.0 && dt_secs > 0.0 {
+            self.integral += error * dt_secs;
+
+            // Anti-windup: the integral term alone may never exceed the output range
+            let limit_low = self.output_min / self.ki;
+            let limit_high = self.output_max / self.ki;
+            self.integral = self.integral.clamp(limit_low, limit_high);
+        }
+
+        let output = self.kp * error + self.ki * self.integral + self.kd * derivative;
+        output.clamp(self.output_min, self.output_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_clamped() {
+        let mut pid = PidController::new(1.0, 0.0, 0.0);
+        assert_eq!(pid.update(65.0, 20.0, 1.0), 1.0);
+        assert_eq!(pid.update(65.0, 80.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_integral_does_not_wind_up() {
+        let mut pid = PidController::new(0.0, 0.1, 0.0);
+
+        // Long saturated warm-up
+        for _ in 0..1000 {
+            pid.update(65.0, 20.0, 1.0);
+        }
+
+        // Once above the setpoint the output must drop off immediately
+        let output = pid.update(65.0, 70.0, 1.0);
+        assert!(output < 1.0, "integral wound up: output {}", output);
+    }
+}
@@ -0,0 +1,243 @@
+//! Ingredient reservoir levels
+//!
+//! How much is left in each reservoir is estimated from pump runtime and the
+//! pump calibration. The estimates are saved with the maintenance log and
+//! restored at power-on; a reservoir never estimated starts full. Level
+//! sensors, where fitted, correct the estimate or flag a reservoir as low.
+//! An order is refused before anything moves if it would dig into an
+//! ingredient's reserve.
+
+use crate::config::{Ingredient, PumpCalibration, ReservoirConfig, Reservoirs, Step};
+use crate::control::maintenance::ReservoirRecord;
+use crate::control::DispenseController;
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use anyhow::Result;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Estimated amount left in each reservoir, in the pump's calibrated unit
+pub struct ReservoirLevels {
+    remaining: HashMap<Ingredient, f32>,
+    /// Pump counter readings already taken off the estimates
+    counted_ms: HashMap<Ingredient, u64>,
+}
+
+impl ReservoirLevels {
+    /// Every reservoir full
+    pub fn full(reservoirs: &Reservoirs) -> Self {
+        let remaining = Ingredient::ALL.into_iter()
+            .map(|ingredient| (ingredient, reservoirs.get(ingredient).capacity))
+            .collect();
+
+        Self { remaining, counted_ms: HashMap::new() }
+    }
+
+    /// Take up saved estimates, capped at each reservoir's current capacity
+    pub fn restore(&mut self, saved: &ReservoirRecord, reservoirs: &Reservoirs) {
+        for ingredient in Ingredient::ALL {
+            if let Some(remaining) = saved.get(ingredient) {
+                let capacity = reservoirs.get(ingredient).capacity;
+                self.remaining.insert(ingredient, remaining.clamp(0.0, capacity));
+            }
+        }
+    }
+
+    /// Estimates to save
+    pub fn record(&self) -> ReservoirRecord {
+        let mut record = ReservoirRecord::default();
+        for ingredient in Ingredient::ALL {
+            record.set(ingredient, self.remaining(ingredient));
+        }
+        record
+    }
+
+    pub fn remaining(&self, ingredient: Ingredient) -> f32 {
+        self.remaining.get(&ingredient).copied().unwrap_or(0.0)
+    }
+
+    /// Amount that can be used before reaching the reserve
+    pub fn available(&self, ingredient: Ingredient, reservoir: &ReservoirConfig) -> f32 {
+        (self.remaining(ingredient) - reservoir.reserve).max(0.0)
+    }
+
+    pub fn refill(&mut self, ingredient: Ingredient, reservoir: &ReservoirConfig) {
+        self.remaining.insert(ingredient, reservoir.capacity);
+    }
+
+    /// Replace the estimate with a measured level (fraction full)
+    pub fn set_level(&mut self, ingredient: Ingredient, level: f32, reservoir: &ReservoirConfig) {
+        self.remaining.insert(ingredient, level.clamp(0.0, 1.0) * reservoir.capacity);
+    }
+
+    /// Take a pump's runtime since the last call off its reservoir, given its runtime counter
+    pub fn record_runtime(&mut self, ingredient: Ingredient, counter_ms: u64, calibration: &PumpCalibration) {
+        let counted = self.counted_ms.insert(ingredient, counter_ms).unwrap_or(0);
+        // A counter that went backwards was reset; all of it is new
+        let ran_ms = counter_ms.checked_sub(counted).unwrap_or(counter_ms);
+
+        let remaining = self.remaining.entry(ingredient).or_insert(0.0);
+        *remaining = (*remaining - calibration.amount(ran_ms)).max(0.0);
+    }
+
+    /// Treat a pump's runtime so far as not drawn from its reservoir, e.g. water pumped during a clean
+    pub fn skip_runtime(&mut self, ingredient: Ingredient, counter_ms: u64) {
+        self.counted_ms.insert(ingredient, counter_ms);
+    }
+}
+
+/// Amount of `ingredient` a program dispenses
+fn needed(program: &[Step], ingredient: Ingredient, calibration: &PumpCalibration) -> f32 {
+    program.iter()
+        .flat_map(Step::doses)
+        .filter(|dose| dose.ingredient == ingredient)
+        .map(|dose| dose.amount.unwrap_or_else(|| calibration.amount(dose.ms.unwrap_or(0))))
+        .sum()
+}
+
+impl DispenseController {
+    /// Estimated amount left in an ingredient's reservoir
+    #[cfg(test)]
+    pub fn reservoir_remaining(&self, ingredient: Ingredient) -> f32 {
+        self.levels.lock().unwrap().remaining(ingredient)
+    }
+
+    /// Mark a reservoir as full again
+    pub fn refill(&self, ingredient: Ingredient) {
+        info!("{} reservoir marked full", ingredient);
+        self.levels.lock().unwrap().refill(ingredient, self.config.reservoirs.get(ingredient));
+    }
+
+    /// Take pump runtime since the last call off the reservoir estimates
+    pub(super) fn record_reservoir_usage(&self) {
+        let mut levels = self.levels.lock().unwrap();
+
+        for ingredient in Ingredient::ALL {
+            let calibration = self.config.hardware.calibration.get(ingredient);
+            levels.record_runtime(ingredient, self.pump(ingredient).total_runtime_ms(), calibration);
+        }
+    }
+
+    /// Reservoirs down to their reserve, after reading any level sensors
+    pub async fn low_reservoirs(&self) -> Result<Vec<Ingredient>> {
+        let flagged = self.read_level_sensors().await?;
+        let levels = self.levels.lock().unwrap();
+
+        Ok(Ingredient::ALL.into_iter()
+            .filter(|ingredient| {
+                flagged.contains(ingredient)
+                    || levels.available(*ingredient, self.config.reservoirs.get(*ingredient)) <= 0.0
+            })
+            .collect())
+    }
+
+    /// Refuse a program that any reservoir cannot supply
+    pub(super) async fn check_reservoirs(&self, program: &[Step]) -> Result<()> {
+        let flagged = self.read_level_sensors().await?;
+        let levels = self.levels.lock().unwrap();
+
+        for ingredient in Ingredient::ALL {
+            let needed = needed(program, ingredient, self.config.hardware.calibration.get(ingredient));
+            if needed <= 0.0 {
+                continue;
+            }
+
+            let available = if flagged.contains(&ingredient) {
+                0.0
+            } else {
+                levels.available(ingredient, self.config.reservoirs.get(ingredient))
+            };
+
+            if needed > available {
+                warn!("{} reservoir too low: need {:.0}, {:.0} available", ingredient, needed, available);
+                return Err(HotChocolaBotError::ReservoirLow { ingredient, needed, available }.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update estimates from sensors that measure, returning those that report low
+    async fn read_level_sensors(&self) -> Result<Vec<Ingredient>> {
+        let mut sensors = self.level_sensors.lock().await;
+        let mut flagged = Vec::new();
+
+        for (ingredient, sensor) in sensors.iter_mut() {
+            let ingredient = *ingredient;
+            let device = Device::LevelSensor(ingredient);
+
+            if let Some(level) = sensor.read_level().await.device(device)? {
+                let reservoir = self.config.reservoirs.get(ingredient);
+                self.levels.lock().unwrap().set_level(ingredient, level, reservoir);
+            }
+
+            if sensor.is_low().await.device(device)? {
+                flagged.push(ingredient);
+            }
+        }
+
+        Ok(flagged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::orders::{OrderEvent, OrderQueue, OrderRequest};
+    use crate::control::tests::sim_config;
+    use crate::hardware::factory::ControllerHardware;
+    use crate::hardware::mock::{MockDisplay, MockLevelSensor};
+    use crate::safety::SafetyMonitor;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_order_refused_until_refilled() {
+        let mut config = sim_config();
+        config.reservoirs.milk.capacity = 200.0;
+        config.reservoirs.milk.reserve = 20.0;
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware { display: Box::new(display.clone()), ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        // 125ml of milk per standard cup: the second would dig into the reserve
+        let orders = OrderQueue::new();
+        let mut events = orders.subscribe();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+
+        let failed = std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| matches!(event, OrderEvent::Failed { id: 2, .. }));
+        assert!(failed);
+        assert_eq!(display.get_buffer(), "REFILL MILK\nCode E407");
+        assert!(controller.reservoir_remaining(Ingredient::Milk) < 75.0);
+
+        let orders = OrderQueue::new();
+        orders.refill(Ingredient::Milk);
+        orders.submit(OrderRequest::new("standard")).unwrap();
+        orders.close();
+        controller.run(&mut safety, &orders).await.unwrap();
+        assert_eq!(display.get_buffer(), "Complete!\nEnjoy!");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_level_sensor_overrides_estimate() {
+        let config = sim_config();
+        let sensor = MockLevelSensor::new(1.0);
+        let hardware = ControllerHardware {
+            level_sensors: vec![(Ingredient::Cocoa, Box::new(sensor.clone()))],
+            ..ControllerHardware::mock()
+        };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let program = config.recipes["standard"].program();
+
+        assert!(controller.check_reservoirs(&program).await.is_ok());
+
+        sensor.set_level(0.0);
+        let err = controller.check_reservoirs(&program).await.unwrap_err();
+        assert_eq!(HotChocolaBotError::code_of(&err), 407);
+        assert_eq!(controller.low_reservoirs().await.unwrap(), [Ingredient::Cocoa]);
+        assert_eq!(controller.reservoir_remaining(Ingredient::Cocoa), 0.0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Hardware self-test
+//!
+//! Exercises each device in turn so a technician can watch and listen for
+//! it working: a test pattern on the display, a short pulse on every pump
+//! and the stirrer, and a run of temperature readings. The heater is left
+//! alone; the thermal watchdog covers it whenever it runs.
+
+use crate::config::Ingredient;
+use crate::control::DispenseController;
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::hardware::glyphs::Glyph;
+use crate::safety::SafetyMonitor;
+use anyhow::Result;
+use tracing::{info, warn};
+
+/// Length of each self-test pump pulse in milliseconds
+const PULSE_MS: u64 = 300;
+
+/// Temperature readings taken by the self-test
+const READINGS: usize = 3;
+
+/// Outcome of testing one device
+#[derive(Debug, Clone)]
+pub struct SelfTestResult {
+    pub device: Device,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl SelfTestResult {
+    fn new(device: Device, passed: bool, detail: impl Into<String>) -> Self {
+        Self { device, passed, detail: detail.into() }
+    }
+}
+
+impl DispenseController {
+    /// Exercise every output and sensor, reporting how each responded
+    ///
+    /// Runs as an operation in the safety state machine, so pre-flight must
+    /// have passed. A device failing its test is reported, not returned as
+    /// an error; `Err` means the test itself could not run.
+    pub async fn self_test(&self, safety_monitor: &mut SafetyMonitor) -> Result<Vec<SelfTestResult>> {
+        safety_monitor.begin_operation()?;
+        let result = self.run_self_test(safety_monitor).await;
+        Self::finish_operation(result, safety_monitor)
+    }
+
+    async fn run_self_test(&self, safety_monitor: &SafetyMonitor) -> Result<Vec<SelfTestResult>> {
+        info!("Starting self-test");
+        let mut results = Vec::new();
+
+        let pattern: String = Glyph::ALL.iter().map(|glyph| glyph.symbol()).collect();
+        let shown = self.display.lock().await.show_message(&format!("Self test\n{}", pattern)).await;
+        results.push(match shown {
+            Ok(()) => SelfTestResult::new(Device::Display, true, "test pattern shown"),
+            Err(e) => SelfTestResult::new(Device::Display, false, format!("{:#}", e)),
+        });
+
+        for ingredient in Ingredient::ALL {
+            if safety_monitor.is_emergency_stop() {
+                return Err(HotChocolaBotError::EmergencyStop("self-test abandoned".to_string()).into());
+            }
+
+            results.push(match self.pulse_pump(ingredient, PULSE_MS).await {
+                Ok(pulse) if pulse.running_after => {
+                    SelfTestResult::new(Device::Pump(ingredient), false, "still running after the pulse")
+                }
+                Ok(pulse) => SelfTestResult::new(Device::Pump(ingredient), true, format!("ran {}ms", pulse.runtime_ms)),
+                Err(e) => SelfTestResult::new(Device::Pump(ingredient), false, format!("{:#}", e)),
+            });
+        }
+
+        // Test pulses draw real ingredient but are not dispenses
+        self.account_test_pulses();
+
+        if let Some(mixer) = &self.mixer {
+            results.push(match mixer.dispense(PULSE_MS).await {
+                Ok(()) if mixer.is_running() => SelfTestResult::new(Device::Mixer, false, "still running after the pulse"),
+                Ok(()) => SelfTestResult::new(Device::Mixer, true, format!("ran {}ms", PULSE_MS)),
+                Err(e) => SelfTestResult::new(Device::Mixer, false, format!("{:#}", e)),
+            });
+        }
+
+        match self.emergency_stop_pressed().await {
+            Some(false) => results.push(SelfTestResult::new(Device::EmergencyStop, true, "released")),
+            Some(true) => results.push(SelfTestResult::new(Device::EmergencyStop, false, "pressed - release it")),
+            None => {}
+        }
+
+        results.push(self.test_temperature_sensor().await);
+
+        for result in &results {
+            match result.passed {
+                true => info!("Self-test {}: {}", result.device, result.detail),
+                false => warn!("Self-test {} FAILED: {}", result.device, result.detail),
+            }
+        }
+
+        let failed = results.iter().filter(|result| !result.passed).count();
+        let summary = match failed {
+            0 => "Self test\nAll passed".to_string(),
+            n => format!("Self test\n{} failed", n),
+        };
+        self.display.lock().await.show_message(&summary).await.device(Device::Display)?;
+
+        Ok(results)
+    }
+
+    /// Take a few readings and require each to be within the safety limits
+    async fn test_temperature_sensor(&self) -> SelfTestResult {
+        let max = self.config.safety.max_temperature;
+        let mut readings = Vec::with_capacity(READINGS);
+
+        for _ in 0..READINGS {
+            match self.read_temperature().await {
+                Ok(temp) if (0.0..=max).contains(&temp) => readings.push(format!("{:.1}°C", temp)),
+                Ok(temp) => {
+                    return SelfTestResult::new(
+                        Device::TemperatureSensor,
+                        false,
+                        format!("implausible reading {:.1}°C", temp),
+                    );
+                }
+                Err(e) => return SelfTestResult::new(Device::TemperatureSensor, false, format!("{:#}", e)),
+            }
+        }
+
+        SelfTestResult::new(Device::TemperatureSensor, true, readings.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::tests::sim_config;
+    use crate::hardware::factory::ControllerHardware;
+    use crate::hardware::mock::{MockDisplay, MockTemperatureSensor};
+    use crate::hardware::TemperatureSensor;
+    use crate::safety::SafetyStates;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_self_test_exercises_every_device() {
+        let config = sim_config();
+        let display = MockDisplay::new();
+        let hardware = ControllerHardware { display: Box::new(display.clone()), ..ControllerHardware::mock() };
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        let results = controller.self_test(&mut safety).await.unwrap();
+
+        // Display, three pumps, the mock mixer, the button and the sensor
+        assert_eq!(results.len(), 7);
+        assert!(results.iter().all(|result| result.passed));
+        assert_eq!(display.get_buffer(), "Self test\nAll passed");
+        assert!(matches!(safety.state(), SafetyStates::Safe));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_self_test_reports_bad_sensor() {
+        let config = sim_config();
+        let controller = DispenseController::with_hardware(config.clone(), ControllerHardware::mock());
+        let mut safety = SafetyMonitor::new(&config.safety).unwrap();
+        assert!(safety.run_preflight_checks(&controller).await.unwrap());
+
+        // A sensor reading far above anything milk should reach
+        let sensor: Box<dyn TemperatureSensor> = Box::new(MockTemperatureSensor::new(150.0));
+        *controller.temp_sensor.lock().await = sensor;
+
+        let results = controller.self_test(&mut safety).await.unwrap();
+        let sensor = results.iter().find(|result| result.device == Device::TemperatureSensor).unwrap();
+        assert!(!sensor.passed);
+        assert!(sensor.detail.contains("implausible"));
+    }
+}
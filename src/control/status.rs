@@ -0,0 +1,137 @@
+//! Status LED driven from the safety state
+//!
+//! The LED is the only indicator a child standing at the bot can read at a
+//! glance, so it follows the safety state machine rather than being set by
+//! hand at scattered call sites.
+
+use crate::error::{Device, DeviceContext};
+use crate::hardware::StatusLed;
+use crate::safety::SafetyStates;
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, error};
+
+/// Status LED shared between the controller and the indicator task
+pub type SharedStatusLed = Arc<Mutex<Box<dyn StatusLed>>>;
+
+/// What the LED shows for a given state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LedPattern {
+    Off,
+    Solid,
+    Blink { on_ms: u64, off_ms: u64 },
+}
+
+impl LedPattern {
+    /// Pattern for a safety state
+    ///
+    /// Off until pre-flight passes, solid when ready, slow blink while
+    /// working, fast blink when something is wrong.
+    pub fn for_state(state: SafetyStates) -> Self {
+        match state {
+            SafetyStates::Uninitialized | SafetyStates::Initialized => LedPattern::Off,
+            SafetyStates::Safe => LedPattern::Solid,
+            SafetyStates::Operating => LedPattern::Blink { on_ms: 500, off_ms: 500 },
+            SafetyStates::Anomaly => LedPattern::Blink { on_ms: 250, off_ms: 250 },
+            SafetyStates::Unsafe => LedPattern::Blink { on_ms: 100, off_ms: 100 },
+        }
+    }
+
+    /// Show the pattern; blinking patterns run until cancelled
+    async fn show(self, led: &SharedStatusLed) -> Result<()> {
+        let result = match self {
+            LedPattern::Off => led.lock().await.off().await,
+            LedPattern::Solid => led.lock().await.on().await,
+            LedPattern::Blink { on_ms, off_ms } => loop {
+                if let Err(e) = led.lock().await.blink(on_ms, off_ms, 1).await {
+                    break Err(e);
+                }
+            },
+        };
+        result.device(Device::StatusLed)
+    }
+}
+
+/// Handle to the task keeping the LED in step with the safety state
+pub struct StatusLedHandle {
+    task: JoinHandle<()>,
+}
+
+impl StatusLedHandle {
+    /// Start following `states` on `led`
+    pub fn spawn(led: SharedStatusLed, mut states: watch::Receiver<SafetyStates>) -> Self {
+        let task = tokio::spawn(async move {
+            loop {
+                let pattern = LedPattern::for_state(*states.borrow_and_update());
+                debug!("Status LED: {:?}", pattern);
+
+                tokio::select! {
+                    result = pattern.show(&led) => {
+                        if let Err(e) = result {
+                            error!("Status LED failed: {:?}", e);
+                        }
+                        // Steady pattern applied; wait for the next state
+                        if states.changed().await.is_err() {
+                            return;
+                        }
+                    }
+                    changed = states.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { task }
+    }
+}
+
+impl Drop for StatusLedHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::mock::MockStatusLed;
+    use tokio::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_led_follows_safety_state() {
+        let led = MockStatusLed::new();
+        let shared: SharedStatusLed = Arc::new(Mutex::new(Box::new(led.clone())));
+        let (state_tx, state_rx) = watch::channel(SafetyStates::Initialized);
+        let _handle = StatusLedHandle::spawn(shared, state_rx);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!led.is_on());
+
+        state_tx.send_replace(SafetyStates::Safe);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(led.is_on());
+
+        // Blinking: on during the first half of each period, off during the second
+        state_tx.send_replace(SafetyStates::Unsafe);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(led.is_on());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!led.is_on());
+    }
+
+    #[test]
+    fn test_fault_states_blink_faster_than_operating() {
+        let period = |state| match LedPattern::for_state(state) {
+            LedPattern::Blink { on_ms, off_ms } => on_ms + off_ms,
+            other => panic!("{:?} does not blink", other),
+        };
+
+        assert!(period(SafetyStates::Unsafe) < period(SafetyStates::Anomaly));
+        assert!(period(SafetyStates::Anomaly) < period(SafetyStates::Operating));
+    }
+}
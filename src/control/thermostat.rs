@@ -0,0 +1,228 @@
+//! Closed-loop milk temperature control
+//!
+//! The thermostat reads the temperature sensor on a fixed interval and feeds
+//! the PID output to the heater, either until the target is reached
+//! (`heat_to`) or continuously in the background (`hold`).
+
+use crate::config::HeaterConfig;
+use crate::control::pid::PidController;
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::hardware::{Heater, TemperatureSensor};
+use anyhow::{Result, Context};
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Heater shared between the controller and background regulation
+pub type SharedHeater = Arc<Mutex<Box<dyn Heater>>>;
+
+/// Temperature sensor shared between the controller and background regulation
+pub type SharedSensor = Arc<Mutex<Box<dyn TemperatureSensor>>>;
+
+/// PID temperature regulator driving a heater from a temperature sensor
+pub struct Thermostat {
+    heater: SharedHeater,
+    sensor: SharedSensor,
+    config: HeaterConfig,
+}
+
+impl Thermostat {
+    /// Create new thermostat
+    pub fn new(heater: SharedHeater, sensor: SharedSensor, config: HeaterConfig) -> Self {
+        Self { heater, sensor, config }
+    }
+
+    fn pid(&self) -> PidController {
+        PidController::new(self.config.kp, self.config.ki, self.config.kd)
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.config.sample_interval_ms)
+    }
+
+    /// Heat until the temperature is within tolerance of `target`
+    ///
+    /// Returns the temperature reached, or a heat timeout after `limit`. The
+    /// heater is left under PID control at its last output; call `hold` or
+    /// `off` afterwards.
+    pub async fn heat_to(&self, target: f32, limit: Duration) -> Result<f32> {
+        info!("Heating to {:.1}°C", target);
+
+        let mut pid = self.pid();
+        let deadline = Instant::now() + limit;
+        let mut ticker = tokio::time::interval(self.interval());
+        let mut last_tick = Instant::now();
+
+        loop {
+            ticker.tick().await;
+            let now = Instant::now();
+            let dt = now.duration_since(last_tick).as_secs_f32();
+            last_tick = now;
+
+            let temp = regulate_step(&self.heater, &self.sensor, &mut pid, target, dt).await?;
+
+            if (temp - target).abs() <= self.config.tolerance {
+                info!("Target temperature reached: {:.1}°C", temp);
+                return Ok(temp);
+            }
+
+            if now >= deadline {
+                self.off().await?;
+                return Err(HotChocolaBotError::HeatTimeout {
+                    target,
+                    limit_secs: limit.as_secs(),
+                    last: temp,
+                }.into());
+            }
+        }
+    }
+
+    /// Keep regulating at `target` in the background until the hold is stopped
+    pub fn hold(&self, target: f32) -> TemperatureHold {
+        let heater = Arc::clone(&self.heater);
+        let sensor = Arc::clone(&self.sensor);
+        let mut pid = self.pid();
+        let interval = self.interval();
+        let (stop_tx, mut stop_rx) = oneshot::channel();
+
+        info!("Holding temperature at {:.1}°C", target);
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut last_tick = Instant::now();
+
+            loop {
+                tokio::select! {
+                    _ = &mut stop_rx => return Ok(()),
+                    _ = ticker.tick() => {
+                        let now = Instant::now();
+                        let dt = now.duration_since(last_tick).as_secs_f32();
+                        last_tick = now;
+                        regulate_step(&heater, &sensor, &mut pid, target, dt).await?;
+                    }
+                }
+            }
+        });
+
+        TemperatureHold {
+            heater: Arc::clone(&self.heater),
+            stop: Some(stop_tx),
+            task,
+        }
+    }
+
+    /// Switch the heater off
+    pub async fn off(&self) -> Result<()> {
+        self.heater.lock().await.off().await.device(Device::Heater)
+    }
+}
+
+/// Handle to a background temperature hold started by `Thermostat::hold`
+pub struct TemperatureHold {
+    heater: SharedHeater,
+    stop: Option<oneshot::Sender<()>>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl TemperatureHold {
+    /// Stop regulating and switch the heater off
+    ///
+    /// Returns any error the regulation loop hit while holding.
+    pub async fn stop(mut self) -> Result<()> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+
+        let outcome = (&mut self.task).await
+            .context("Temperature hold task panicked")
+            .device(Device::Heater);
+        self.heater.lock().await.off().await.device(Device::Heater)?;
+
+        outcome?
+    }
+}
+
+impl Drop for TemperatureHold {
+    fn drop(&mut self) {
+        // A hold that is dropped without `stop` must not keep heating
+        if self.stop.is_some() {
+            warn!("Temperature hold dropped without stop - switching heater off");
+            self.task.abort();
+
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let heater = Arc::clone(&self.heater);
+                runtime.spawn(async move { heater.lock().await.off().await });
+            }
+        }
+    }
+}
+
+/// One PID iteration: read sensor, compute output, apply to heater
+async fn regulate_step(
+    heater: &SharedHeater,
+    sensor: &SharedSensor,
+    pid: &mut PidController,
+    target: f32,
+    dt: f32,
+) -> Result<f32> {
+    let temp = sensor.lock().await.read_temperature().await
+        .context("Failed to read temperature")
+        .device(Device::TemperatureSensor)?;
+
+    let power = pid.update(target, temp, dt);
+    heater.lock().await.set_power(power).await.device(Device::Heater)?;
+
+    debug!(telemetry = "temperature", temp, target, power,
+           "Thermostat: {:.1}°C (target {:.1}°C) -> heater {:.0}%", temp, target, power * 100.0);
+    Ok(temp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::mock::{MockHeater, MockTemperatureSensor, ThermalModel};
+
+    const LIMIT: Duration = Duration::from_secs(600);
+
+    fn thermostat(model: &ThermalModel) -> Thermostat {
+        let heater: Box<dyn Heater> = Box::new(MockHeater::with_model(model.clone()));
+        let sensor: Box<dyn TemperatureSensor> = Box::new(MockTemperatureSensor::with_model(model.clone()));
+        Thermostat::new(Arc::new(Mutex::new(heater)), Arc::new(Mutex::new(sensor)), HeaterConfig::default())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heat_to_reaches_target() {
+        let model = ThermalModel::new(20.0);
+        let thermostat = thermostat(&model);
+
+        let temp = thermostat.heat_to(65.0, LIMIT).await.unwrap();
+        assert!((temp - 65.0).abs() <= 1.5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hold_keeps_temperature() {
+        let model = ThermalModel::new(20.0);
+        let thermostat = thermostat(&model);
+
+        thermostat.heat_to(65.0, LIMIT).await.unwrap();
+        let hold = thermostat.hold(65.0);
+        tokio::time::sleep(Duration::from_secs(120)).await;
+
+        let temp = model.temperature();
+        assert!((temp - 65.0).abs() <= 3.0, "drifted to {}", temp);
+
+        hold.stop().await.unwrap();
+        assert_eq!(model.power(), 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heat_to_times_out_with_weak_heater() {
+        let model = ThermalModel::new(20.0).with_rates(0.01, 0.01);
+        let thermostat = thermostat(&model);
+
+        assert!(thermostat.heat_to(65.0, LIMIT).await.is_err());
+        assert_eq!(model.power(), 0.0);
+    }
+}
@@ -0,0 +1,365 @@
+//! Typed failures with stable fault codes
+//!
+//! Modules still return `anyhow::Result`, but every failure starts life as a
+//! `HotChocolaBotError` so callers can tell faults apart by downcasting, and
+//! the LCD can show a numeric code students look up on the printed sheet
+//! (`education/activities/fault_codes.md`). Codes never change meaning:
+//! add new ones rather than renumbering.
+
+use crate::config::Ingredient;
+use crate::safety::thermal::ThermalFault;
+use crate::safety::OperationTimeout;
+use std::borrow::Cow;
+use std::fmt;
+use thiserror::Error;
+
+/// Code reported for failures that did not come from a typed error
+pub const UNCLASSIFIED_CODE: u16 = 999;
+
+/// Process exit status for failures that did not come from a typed error
+///
+/// Status 2 is left to the argument parser for usage errors.
+pub const UNCLASSIFIED_EXIT: u8 = 1;
+
+/// Physical device a hardware fault is attributed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Pump(Ingredient),
+    WaterPump,
+    Mixer,
+    TemperatureSensor,
+    Heater,
+    Display,
+    StatusLed,
+    Adc,
+    LevelSensor(Ingredient),
+    EmergencyStop,
+    /// Only fitted with the `rpi` backend
+    #[cfg_attr(not(feature = "rpi"), allow(dead_code))]
+    OrderButton,
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Device::Pump(ingredient) => write!(f, "{} pump", ingredient),
+            Device::WaterPump => f.write_str("water pump"),
+            Device::Mixer => f.write_str("mixer"),
+            Device::TemperatureSensor => f.write_str("temperature sensor"),
+            Device::Heater => f.write_str("heater"),
+            Device::Display => f.write_str("display"),
+            Device::StatusLed => f.write_str("status LED"),
+            Device::Adc => f.write_str("supply monitor ADC"),
+            Device::LevelSensor(ingredient) => write!(f, "{} level sensor", ingredient),
+            Device::EmergencyStop => f.write_str("emergency stop button"),
+            Device::OrderButton => f.write_str("order button"),
+        }
+    }
+}
+
+/// Every way HotChocolaBot can fail
+#[derive(Debug, Error)]
+pub enum HotChocolaBotError {
+    // 1xx: hardware
+    #[error("{device} fault: {message}")]
+    Hardware { device: Device, message: String },
+
+    // 2xx: safety
+    #[error("Emergency stop: {0}")]
+    EmergencyStop(String),
+
+    #[error("Thermal fault: {0}")]
+    Thermal(#[from] ThermalFault),
+
+    #[error("Temperature {temp:.1}°C outside safe range {min:.1}°C to {max:.1}°C")]
+    TemperatureOutOfRange { temp: f32, min: f32, max: f32 },
+
+    #[error("Not ready: {0}")]
+    NotReady(String),
+
+    #[error("Pre-flight checks failed: {0}")]
+    PreflightFailed(String),
+
+    #[error("System needs a manual reset: {0}")]
+    ManualResetRequired(String),
+
+    #[error("{ingredient} pump asked to run {requested_ms}ms, over the {max_ms}ms limit")]
+    PumpRuntimeExceeded { ingredient: Ingredient, requested_ms: u64, max_ms: u64 },
+
+    #[error("{ingredient} line last cleaned {hours}h ago; run a clean before serving")]
+    CleanOverdue { ingredient: Ingredient, hours: u64 },
+
+    // 3xx: configuration
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Cannot save records: {0}")]
+    Storage(String),
+
+    // 4xx: recipes and orders
+    #[error("Unknown recipe '{name}' (have: {})", .known.join(", "))]
+    UnknownRecipe { name: String, known: Vec<String> },
+
+    #[error("Recipe rejected: {0}")]
+    RecipeRejected(String),
+
+    #[error("Recipe needs a {0}, which is not fitted")]
+    MissingHardware(Device),
+
+    #[error("Cancelled by operator: {0}")]
+    OperatorDeclined(String),
+
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+
+    #[error("Not accepting orders")]
+    QueueClosed,
+
+    #[error("Not enough {ingredient}: recipe needs {needed:.0}, {available:.0} left before the reserve")]
+    ReservoirLow { ingredient: Ingredient, needed: f32, available: f32 },
+
+    #[error("Invalid operator input: {0}")]
+    InvalidInput(String),
+
+    // 5xx: timeouts
+    #[error(transparent)]
+    Timeout(#[from] OperationTimeout),
+
+    #[error("Heater did not reach {target:.1}°C within {limit_secs}s (last reading {last:.1}°C)")]
+    HeatTimeout { target: f32, limit_secs: u64, last: f32 },
+}
+
+impl HotChocolaBotError {
+    /// Hardware fault on `device`, keeping the underlying error's message
+    pub fn hardware(device: Device, error: impl fmt::Display) -> Self {
+        HotChocolaBotError::Hardware { device, message: format!("{:#}", error) }
+    }
+
+    /// Stable numeric code, shown on the LCD as `E<code>`
+    pub fn code(&self) -> u16 {
+        use HotChocolaBotError::*;
+
+        match self {
+            Hardware { device, .. } => match device {
+                Device::Pump(_) | Device::WaterPump => 101,
+                Device::TemperatureSensor => 102,
+                Device::Heater => 103,
+                Device::Display => 104,
+                Device::Adc => 105,
+                Device::StatusLed => 106,
+                Device::Mixer => 107,
+                Device::LevelSensor(_) => 108,
+                Device::EmergencyStop => 109,
+                Device::OrderButton => 110,
+            },
+            EmergencyStop(_) => 201,
+            Thermal(fault) => match fault {
+                ThermalFault::OverTemperature { .. } => 202,
+                ThermalFault::RunawayRate { .. } => 203,
+                ThermalFault::StuckSensor { .. } => 204,
+                ThermalFault::NotHeating { .. } => 205,
+                // Same remedy as any other sensor fault
+                ThermalFault::SensorFailure(_) => 102,
+            },
+            TemperatureOutOfRange { .. } => 206,
+            NotReady(_) => 207,
+            PreflightFailed(_) => 208,
+            ManualResetRequired(_) => 209,
+            PumpRuntimeExceeded { .. } => 210,
+            CleanOverdue { .. } => 211,
+            Config(_) => 301,
+            Storage(_) => 302,
+            UnknownRecipe { .. } => 401,
+            RecipeRejected(_) => 402,
+            MissingHardware(_) => 403,
+            OperatorDeclined(_) => 404,
+            InvalidOrder(_) => 405,
+            QueueClosed => 406,
+            ReservoirLow { .. } => 407,
+            InvalidInput(_) => 408,
+            Timeout(_) => 501,
+            HeatTimeout { .. } => 502,
+        }
+    }
+
+    /// Process exit status for this failure's class, so scripts can tell them apart
+    pub fn exit_code(&self) -> u8 {
+        match self.code() / 100 {
+            1 => 4, // hardware
+            2 => 5, // safety
+            3 => 3, // configuration
+            4 => 6, // recipes and orders
+            5 => 7, // timeouts
+            _ => UNCLASSIFIED_EXIT,
+        }
+    }
+
+    /// Headline for the LCD's first line (at most 16 characters)
+    pub fn label(&self) -> Cow<'static, str> {
+        use HotChocolaBotError::*;
+
+        let label = match self {
+            Hardware { device, .. } => match device {
+                Device::Pump(_) | Device::WaterPump => "PUMP FAULT",
+                Device::TemperatureSensor => "SENSOR FAULT",
+                Device::Heater => "HEATER FAULT",
+                Device::Display => "DISPLAY FAULT",
+                Device::Adc => "POWER FAULT",
+                Device::StatusLed => "LED FAULT",
+                Device::Mixer => "MIXER FAULT",
+                Device::LevelSensor(_) => "LEVEL FAULT",
+                Device::EmergencyStop => "ESTOP FAULT",
+                Device::OrderButton => "BUTTON FAULT",
+            },
+            EmergencyStop(_) => "EMERGENCY STOP",
+            Thermal(ThermalFault::SensorFailure(_)) => "SENSOR FAULT",
+            Thermal(_) => "THERMAL FAULT",
+            TemperatureOutOfRange { .. } => "BAD TEMPERATURE",
+            NotReady(_) => "NOT READY",
+            PreflightFailed(_) => "PREFLIGHT FAILED",
+            ManualResetRequired(_) => "RESET REQUIRED",
+            PumpRuntimeExceeded { .. } => "PUMP LIMIT",
+            CleanOverdue { .. } => "CLEAN NEEDED",
+            Config(_) => "CONFIG ERROR",
+            Storage(_) => "STORAGE ERROR",
+            UnknownRecipe { .. } => "UNKNOWN RECIPE",
+            RecipeRejected(_) => "RECIPE REJECTED",
+            MissingHardware(_) => "NEEDS HARDWARE",
+            OperatorDeclined(_) => "CANCELLED",
+            InvalidOrder(_) => "BAD ORDER",
+            QueueClosed => "CLOSED",
+            InvalidInput(_) => "BAD INPUT",
+            Timeout(_) => "TIMEOUT",
+            HeatTimeout { .. } => "HEAT TIMEOUT",
+            // Name the reservoir so the operator knows what to refill
+            ReservoirLow { ingredient, .. } => {
+                return Cow::Owned(format!("REFILL {}", ingredient.name().to_uppercase()));
+            }
+        };
+        Cow::Borrowed(label)
+    }
+
+    /// First typed error in an error's chain, looking through added context
+    pub fn find(error: &anyhow::Error) -> Option<&HotChocolaBotError> {
+        error.chain().find_map(|cause| cause.downcast_ref::<HotChocolaBotError>())
+    }
+
+    /// Fault code for any error, `UNCLASSIFIED_CODE` if it is untyped
+    pub fn code_of(error: &anyhow::Error) -> u16 {
+        Self::find(error).map_or(UNCLASSIFIED_CODE, HotChocolaBotError::code)
+    }
+
+    /// Process exit status for any error, `UNCLASSIFIED_EXIT` if it is untyped
+    pub fn exit_code_of(error: &anyhow::Error) -> u8 {
+        Self::find(error).map_or(UNCLASSIFIED_EXIT, HotChocolaBotError::exit_code)
+    }
+
+    /// Two-line LCD text for any error
+    pub fn screen_for(error: &anyhow::Error) -> String {
+        let label = Self::find(error).map_or(Cow::Borrowed("FAULT"), HotChocolaBotError::label);
+        format!("{}\nCode E{:03}", label, Self::code_of(error))
+    }
+}
+
+/// Attribute a failing hardware call to its device
+pub trait DeviceContext<T> {
+    fn device(self, device: Device) -> anyhow::Result<T>;
+}
+
+impl<T> DeviceContext<T> for anyhow::Result<T> {
+    fn device(self, device: Device) -> anyhow::Result<T> {
+        self.map_err(|error| {
+            // Keep a more specific classification made further down
+            if HotChocolaBotError::find(&error).is_some() {
+                error
+            } else {
+                HotChocolaBotError::hardware(device, error).into()
+            }
+        })
+    }
+}
+
+/// Classify a failure that has no fault code yet
+pub trait FaultContext<T> {
+    fn fault(self, classify: impl FnOnce(String) -> HotChocolaBotError) -> anyhow::Result<T>;
+}
+
+impl<T> FaultContext<T> for anyhow::Result<T> {
+    fn fault(self, classify: impl FnOnce(String) -> HotChocolaBotError) -> anyhow::Result<T> {
+        self.map_err(|error| {
+            // Keep a more specific classification made further down
+            if HotChocolaBotError::find(&error).is_some() {
+                error
+            } else {
+                classify(format!("{:#}", error)).into()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_code_found_through_context() {
+        let error = anyhow::Error::from(HotChocolaBotError::EmergencyStop("button".to_string()))
+            .context("Dispense failed");
+
+        assert_eq!(HotChocolaBotError::code_of(&error), 201);
+        assert_eq!(HotChocolaBotError::screen_for(&error), "EMERGENCY STOP\nCode E201");
+        assert_eq!(HotChocolaBotError::code_of(&anyhow::anyhow!("untyped")), UNCLASSIFIED_CODE);
+    }
+
+    #[test]
+    fn test_device_context_classifies_hardware_errors() {
+        let failed: anyhow::Result<()> = Err(anyhow::anyhow!("I2C NACK")).context("Failed to read TMP102");
+        let error = failed.device(Device::TemperatureSensor).unwrap_err();
+
+        assert_eq!(HotChocolaBotError::code_of(&error), 102);
+        assert!(error.to_string().contains("I2C NACK"));
+
+        // An already-typed error keeps its code
+        let typed: anyhow::Result<()> = Err(HotChocolaBotError::QueueClosed.into());
+        assert_eq!(HotChocolaBotError::code_of(&typed.device(Device::Display).unwrap_err()), 406);
+    }
+
+    #[test]
+    fn test_fault_context_classifies_untyped_errors() {
+        let failed: anyhow::Result<()> = Err(anyhow::anyhow!("disk full")).context("Failed to write log");
+        let error = failed.fault(HotChocolaBotError::Storage).unwrap_err();
+
+        assert_eq!(HotChocolaBotError::code_of(&error), 302);
+        assert!(error.to_string().contains("disk full"));
+
+        let typed: anyhow::Result<()> = Err(HotChocolaBotError::QueueClosed.into());
+        assert_eq!(HotChocolaBotError::code_of(&typed.fault(HotChocolaBotError::Storage).unwrap_err()), 406);
+    }
+
+    #[test]
+    fn test_exit_code_follows_fault_class() {
+        let preflight = anyhow::Error::from(HotChocolaBotError::PreflightFailed("pumps".to_string()))
+            .context("Cannot start");
+
+        assert_eq!(HotChocolaBotError::exit_code_of(&preflight), 5);
+        assert_eq!(HotChocolaBotError::Config(String::new()).exit_code(), 3);
+        assert_eq!(HotChocolaBotError::hardware(Device::Heater, "x").exit_code(), 4);
+        assert_eq!(HotChocolaBotError::QueueClosed.exit_code(), 6);
+        assert_eq!(HotChocolaBotError::exit_code_of(&anyhow::anyhow!("untyped")), UNCLASSIFIED_EXIT);
+    }
+
+    #[test]
+    fn test_labels_fit_the_lcd() {
+        let samples = [
+            HotChocolaBotError::TemperatureOutOfRange { temp: 0.0, min: 0.0, max: 0.0 },
+            HotChocolaBotError::PreflightFailed(String::new()),
+            HotChocolaBotError::RecipeRejected(String::new()),
+            HotChocolaBotError::hardware(Device::TemperatureSensor, "x"),
+            HotChocolaBotError::hardware(Device::OrderButton, "x"),
+            HotChocolaBotError::ReservoirLow { ingredient: Ingredient::Cocoa, needed: 0.0, available: 0.0 },
+        ];
+
+        assert!(samples.iter().all(|error| error.label().len() <= 16));
+    }
+}
@@ -0,0 +1,82 @@
+//! ADC implementation using I2C (ADS1115)
+
+use crate::error::{Device, HotChocolaBotError};
+use crate::hardware::Adc;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use std::sync::Mutex;
+use tracing::info;
+
+use rppal::i2c::I2c;
+
+/// ADS1115 conversion register
+const REG_CONVERSION: u8 = 0x00;
+
+/// ADS1115 config register
+const REG_CONFIG: u8 = 0x01;
+
+/// Full-scale range for the ±4.096V gain setting
+const FULL_SCALE_VOLTS: f32 = 4.096;
+
+/// I2C 16-bit ADC (ADS1115) used to monitor the supply rail through a divider
+///
+/// rppal's bus is not `Sync`, so it sits behind a lock like `GpioPump`'s pin.
+pub struct Ads1115Adc {
+    i2c: Mutex<I2c>,
+}
+
+impl Ads1115Adc {
+    /// Create new ADS1115 ADC
+    pub fn new(address: u8) -> Result<Self> {
+        let mut i2c = I2c::new().context("Failed to initialize I2C")?;
+        i2c.set_slave_address(address as u16)
+            .context(format!("Failed to set I2C address 0x{:02X}", address))?;
+
+        info!("Initialized ADC at I2C address 0x{:02X}", address);
+
+        Ok(Self { i2c: Mutex::new(i2c) })
+    }
+
+    /// The bus, which `&mut self` already holds exclusively
+    fn bus(&mut self) -> &mut I2c {
+        self.i2c.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl Adc for Ads1115Adc {
+    async fn read_voltage(&mut self, channel: u8) -> Result<f32> {
+        if channel > 3 {
+            return Err(HotChocolaBotError::hardware(Device::Adc, format!("ADS1115 has no channel {}", channel)).into());
+        }
+
+        // Single-shot, single-ended AINx vs GND, ±4.096V, 128 SPS, comparator off
+        let config: u16 = 0x8000 | (0x4000 | (channel as u16) << 12) | 0x0200 | 0x0100 | 0x0080 | 0x0003;
+        self.bus().write(&[REG_CONFIG, (config >> 8) as u8, config as u8])
+            .context("Failed to start ADC conversion")?;
+
+        // One conversion at 128 SPS takes ~7.8ms
+        tokio::time::sleep(tokio::time::Duration::from_millis(9)).await;
+
+        let mut buf = [0u8; 2];
+        self.bus().write_read(&[REG_CONVERSION], &mut buf)
+            .context("Failed to read ADC conversion")?;
+
+        let raw = i16::from_be_bytes(buf);
+        Ok(raw as f32 * FULL_SCALE_VOLTS / 32768.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "requires an ADS1115 on the I2C bus"]
+    async fn test_adc_reading() {
+        let mut adc = Ads1115Adc::new(0x49).unwrap();
+        let volts = adc.read_voltage(0).await.unwrap();
+
+        assert!((0.0..=4.096).contains(&volts), "ADC reading out of range");
+    }
+}
@@ -0,0 +1,58 @@
+//! Order button implementation using a GPIO interrupt
+
+use crate::hardware::estop::{Debouncer, DEBOUNCE};
+use crate::hardware::{ButtonCallback, OrderButton};
+use anyhow::{Result, Context};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{debug, error, info};
+
+use rppal::gpio::{Gpio, InputPin, Level, Trigger};
+
+/// Normally-open push button wired between a GPIO pin and ground
+///
+/// Wired like the emergency stop: internal pull-up, falling-edge interrupt
+/// and software debouncing.
+pub struct GpioButton {
+    // Dropping the pin would cancel the interrupt
+    _pin: InputPin,
+    callbacks: Arc<Mutex<Vec<ButtonCallback>>>,
+}
+
+impl GpioButton {
+    /// Create new button input
+    pub fn new(pin_number: u8) -> Result<Self> {
+        let callbacks: Arc<Mutex<Vec<ButtonCallback>>> = Arc::new(Mutex::new(Vec::new()));
+
+        let mut pin = Gpio::new()
+            .context("Failed to initialize GPIO")?
+            .get(pin_number)
+            .context(format!("Failed to get GPIO pin {}", pin_number))?
+            .into_input_pullup();
+
+        let interrupt_callbacks = Arc::clone(&callbacks);
+        let mut debouncer = Debouncer::new(DEBOUNCE);
+        pin.set_async_interrupt(Trigger::FallingEdge, move |level| {
+            if level != Level::Low || !debouncer.accept(Instant::now()) {
+                debug!("Ignoring button bounce on GPIO pin {}", pin_number);
+                return;
+            }
+
+            match interrupt_callbacks.lock() {
+                Ok(callbacks) => callbacks.iter().for_each(|callback| callback()),
+                Err(e) => error!("Button callbacks poisoned: {}", e),
+            }
+        })
+        .context(format!("Failed to register interrupt on GPIO pin {}", pin_number))?;
+
+        info!("Initialized order button on GPIO pin {}", pin_number);
+
+        Ok(Self { _pin: pin, callbacks })
+    }
+}
+
+impl OrderButton for GpioButton {
+    fn on_press(&mut self, callback: ButtonCallback) {
+        self.callbacks.lock().unwrap().push(callback);
+    }
+}
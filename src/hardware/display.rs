@@ -1,127 +1,243 @@
-//! LCD display implementation using I2C
-
+//! HD44780 character LCD behind a PCF8574 I2C backpack
+//!
+//! The backpack drives the LCD in 4-bit mode: each byte goes out as two
+//! nibbles on P4-P7, latched by pulsing the enable line, with register
+//! select, read/write and the backlight on P0, P1 and P3. Timings follow
+//! the HD44780 datasheet (initialisation by instruction, figure 24).
+
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::hardware::glyphs::{Glyph, FULL_BLOCK};
+use crate::hardware::i2c::I2cBus;
 use crate::hardware::Display;
-use anyhow::{Result, Context};
+use anyhow::Result;
 use async_trait::async_trait;
-use tracing::info;
 
-#[cfg(target_os = "linux")]
+#[cfg(feature = "rpi")]
+use anyhow::Context;
+#[cfg(feature = "rpi")]
 use rppal::i2c::I2c;
+#[cfg(feature = "rpi")]
+use std::sync::Mutex;
+#[cfg(feature = "rpi")]
+use tracing::info;
 
-/// I2C LCD display (e.g., 16x2 or 20x4 with PCF8574 backpack)
-pub struct I2cLcdDisplay {
-    #[cfg(target_os = "linux")]
-    i2c: I2c,
-
-    #[cfg(not(target_os = "linux"))]
-    address: u8,
+// PCF8574 pins
+const RS: u8 = 0x01;
+const ENABLE: u8 = 0x04;
+const BACKLIGHT: u8 = 0x08;
+
+// HD44780 instructions
+const CLEAR: u8 = 0x01;
+const ENTRY_MODE_INCREMENT: u8 = 0x06;
+const DISPLAY_OFF: u8 = 0x08;
+const DISPLAY_ON: u8 = 0x0C;
+const FUNCTION_SET_4BIT: u8 = 0x20;
+const TWO_LINES: u8 = 0x08;
+const SET_CGRAM_ADDR: u8 = 0x40;
+const SET_DDRAM_ADDR: u8 = 0x80;
+const CGRAM_SLOTS: usize = 8;
+
+// Datasheet timings, in microseconds, with some margin
+const POWER_ON_US: u64 = 50_000;
+const FIRST_WAKE_US: u64 = 4_500;
+const WAKE_US: u64 = 150;
+const CLEAR_US: u64 = 2_000;
+const COMMAND_US: u64 = 50;
+
+/// Character the LCD's ROM (A00) shows for `c`, `?` if it has none
+fn rom_code(c: char) -> u8 {
+    match c {
+        ' '..='}' => c as u8,
+        '°' => 0xDF,
+        FULL_BLOCK => 0xFF,
+        _ => b'?',
+    }
+}
 
+/// I2C LCD display (e.g., 16x2 or 20x4 with PCF8574 backpack)
+pub struct I2cLcdDisplay<B: I2cBus> {
+    bus: B,
     rows: u8,
     cols: u8,
     cursor_row: u8,
     cursor_col: u8,
+    backlight: bool,
+    /// Glyphs in CGRAM, by slot
+    glyphs: Vec<Glyph>,
 }
 
-impl I2cLcdDisplay {
+#[cfg(feature = "rpi")]
+impl I2cLcdDisplay<Mutex<I2c>> {
     /// Create new I2C LCD display
     pub fn new(address: u8, rows: u8, cols: u8) -> Result<Self> {
-        #[cfg(target_os = "linux")]
         let mut i2c = I2c::new().context("Failed to initialize I2C")?;
-
-        #[cfg(target_os = "linux")]
         i2c.set_slave_address(address as u16)
             .context(format!("Failed to set I2C address 0x{:02X}", address))?;
 
-        info!("Initialized {}x{} LCD at I2C address 0x{:02X}", rows, cols, address);
+        let mut display = Self::with_bus(Mutex::new(i2c), rows, cols)?;
+        display.upload_glyphs(&Glyph::ALL)?;
+        info!("Initialized {}x{} LCD at I2C address 0x{:02X}", cols, rows, address);
 
-        let mut display = Self {
-            #[cfg(target_os = "linux")]
-            i2c,
-            #[cfg(not(target_os = "linux"))]
-            address,
+        Ok(display)
+    }
+}
 
+impl<B: I2cBus> I2cLcdDisplay<B> {
+    /// Initialise an LCD of `rows` x `cols` characters on `bus`
+    pub fn with_bus(bus: B, rows: u8, cols: u8) -> Result<Self> {
+        let mut display = Self {
+            bus,
             rows,
             cols,
             cursor_row: 0,
             cursor_col: 0,
+            backlight: true,
+            glyphs: Vec::new(),
         };
 
-        // Initialize display
-        display.initialize().await?;
+        display.initialize()?;
 
         Ok(display)
     }
 
-    /// Initialize LCD display
-    async fn initialize(&mut self) -> Result<()> {
-        #[cfg(target_os = "linux")]
-        {
-            // LCD initialization sequence for HD44780
-            // This is simplified - real implementation would need proper timing
-            self.send_command(0x33)?; // Initialize
-            self.send_command(0x32)?; // Set to 4-bit mode
-            self.send_command(0x28)?; // 2 line, 5x8 matrix
-            self.send_command(0x0C)?; // Display on, cursor off
-            self.send_command(0x06)?; // Increment cursor
-            self.send_command(0x01)?; // Clear display
-        }
-
-        #[cfg(not(target_os = "linux"))]
-        info!("[MOCK] LCD display initialized");
+    /// Bring the controller into 4-bit mode from any state
+    ///
+    /// After power-on the LCD may be in 8-bit mode or halfway through a
+    /// 4-bit byte, so three "8-bit mode" nibbles resynchronise it before the
+    /// switch to 4-bit.
+    fn initialize(&mut self) -> Result<()> {
+        self.bus.delay_us(POWER_ON_US);
+
+        self.write_nibble(0x30, 0)?;
+        self.bus.delay_us(FIRST_WAKE_US);
+        self.write_nibble(0x30, 0)?;
+        self.bus.delay_us(WAKE_US);
+        self.write_nibble(0x30, 0)?;
+        self.bus.delay_us(WAKE_US);
+        self.write_nibble(0x20, 0)?;
+        self.bus.delay_us(WAKE_US);
+
+        let lines = if self.rows > 1 { TWO_LINES } else { 0 };
+        self.send_command(FUNCTION_SET_4BIT | lines)?;
+        self.send_command(DISPLAY_OFF)?;
+        self.send_command(CLEAR)?;
+        self.send_command(ENTRY_MODE_INCREMENT)?;
+        self.send_command(DISPLAY_ON)?;
 
         Ok(())
     }
 
-    /// Send command to LCD
-    #[cfg(target_os = "linux")]
-    fn send_command(&mut self, cmd: u8) -> Result<()> {
-        // Simplified command sending - real implementation needs proper timing
-        let high_nibble = (cmd & 0xF0) | 0x0C; // En=1, Rs=0, Rw=0
-        let low_nibble = ((cmd << 4) & 0xF0) | 0x0C;
+    /// Latch the high nibble of `bits` into the LCD
+    fn write_nibble(&mut self, bits: u8, rs: u8) -> Result<()> {
+        let byte = (bits & 0xF0) | rs | if self.backlight { BACKLIGHT } else { 0 };
+
+        // Data is read on the falling edge of enable
+        self.bus.write(&[byte | ENABLE, byte]).device(Device::Display)
+    }
 
-        self.i2c.write(&[high_nibble, high_nibble & !0x04])?;
-        self.i2c.write(&[low_nibble, low_nibble & !0x04])?;
+    fn send(&mut self, byte: u8, rs: u8) -> Result<()> {
+        self.write_nibble(byte, rs)?;
+        self.write_nibble(byte << 4, rs)
+    }
+
+    /// Send an instruction and wait for it to complete
+    fn send_command(&mut self, cmd: u8) -> Result<()> {
+        self.send(cmd, 0)?;
+        self.bus.delay_us(if cmd == CLEAR { CLEAR_US } else { COMMAND_US });
 
         Ok(())
     }
 
-    /// Send data to LCD
-    #[cfg(target_os = "linux")]
+    /// Send a character to display memory at the cursor
     fn send_data(&mut self, data: u8) -> Result<()> {
-        let high_nibble = (data & 0xF0) | 0x0D; // En=1, Rs=1, Rw=0
-        let low_nibble = ((data << 4) & 0xF0) | 0x0D;
-
-        self.i2c.write(&[high_nibble, high_nibble & !0x04])?;
-        self.i2c.write(&[low_nibble, low_nibble & !0x04])?;
+        self.send(data, RS)?;
+        self.bus.delay_us(COMMAND_US);
 
         Ok(())
     }
+
+    /// Write glyph bitmaps into CGRAM, replacing any uploaded before
+    fn upload_glyphs(&mut self, glyphs: &[Glyph]) -> Result<()> {
+        if glyphs.len() > CGRAM_SLOTS {
+            return Err(HotChocolaBotError::hardware(
+                Device::Display,
+                format!("{} glyphs do not fit in {} CGRAM slots", glyphs.len(), CGRAM_SLOTS),
+            ).into());
+        }
+
+        self.send_command(SET_CGRAM_ADDR)?;
+        for glyph in glyphs {
+            for row in glyph.bitmap() {
+                self.send_data(row)?;
+            }
+        }
+        self.glyphs = glyphs.to_vec();
+
+        // Writes go to CGRAM until a DDRAM address is set again
+        let address = self.ddram_address(self.cursor_row, self.cursor_col);
+        self.send_command(SET_DDRAM_ADDR | address)
+    }
+
+    /// Code to send for `c`: its CGRAM slot if uploaded, else from ROM
+    fn char_code(&self, c: char) -> u8 {
+        match self.glyphs.iter().position(|glyph| glyph.symbol() == c) {
+            Some(slot) => slot as u8,
+            None => rom_code(c),
+        }
+    }
+
+    /// Display memory address of a position
+    ///
+    /// Rows 2 and 3 continue rows 0 and 1 in memory, which is why text
+    /// overflowing a row must be moved explicitly rather than left to wrap.
+    fn ddram_address(&self, row: u8, col: u8) -> u8 {
+        let row_start = match row {
+            0 => 0x00,
+            1 => 0x40,
+            2 => self.cols,
+            _ => 0x40 + self.cols,
+        };
+        row_start + col
+    }
+
+    /// Move to the start of the next row; false if there is none
+    async fn next_row(&mut self) -> Result<bool> {
+        if self.cursor_row + 1 >= self.rows {
+            return Ok(false);
+        }
+        self.set_cursor(self.cursor_row + 1, 0).await?;
+        Ok(true)
+    }
 }
 
 #[async_trait]
-impl Display for I2cLcdDisplay {
+impl<B: I2cBus> Display for I2cLcdDisplay<B> {
+    /// Write text at the cursor
+    ///
+    /// `\n` starts the next row and long rows wrap; anything past the last
+    /// row is dropped.
     async fn write(&mut self, text: &str) -> Result<()> {
-        #[cfg(target_os = "linux")]
-        for byte in text.bytes() {
-            if self.cursor_col >= self.cols {
+        for c in text.chars() {
+            if c == '\n' {
+                if !self.next_row().await? {
+                    break;
+                }
+                continue;
+            }
+
+            if self.cursor_col >= self.cols && !self.next_row().await? {
                 break;
             }
-            self.send_data(byte)?;
+
+            self.send_data(self.char_code(c))?;
             self.cursor_col += 1;
         }
 
-        #[cfg(not(target_os = "linux"))]
-        info!("[MOCK] LCD write: {}", text);
-
         Ok(())
     }
 
     async fn clear(&mut self) -> Result<()> {
-        #[cfg(target_os = "linux")]
-        self.send_command(0x01)?;
-
-        #[cfg(not(target_os = "linux"))]
-        info!("[MOCK] LCD cleared");
+        self.send_command(CLEAR)?;
 
         self.cursor_row = 0;
         self.cursor_col = 0;
@@ -130,37 +246,151 @@ async fn clear(&mut self) -> Result<()> {
 
     async fn set_cursor(&mut self, row: u8, col: u8) -> Result<()> {
         if row >= self.rows || col >= self.cols {
-            anyhow::bail!("Cursor position out of bounds: ({}, {})", row, col);
+            return Err(HotChocolaBotError::hardware(
+                Device::Display,
+                format!("cursor position out of bounds: ({}, {})", row, col),
+            ).into());
         }
 
-        // Row offsets for HD44780
-        let row_offsets = [0x00, 0x40, 0x14, 0x54];
-        let offset = row_offsets[row as usize] + col;
-
-        #[cfg(target_os = "linux")]
-        self.send_command(0x80 | offset)?;
-
-        #[cfg(not(target_os = "linux"))]
-        info!("[MOCK] LCD cursor set to ({}, {})", row, col);
+        self.send_command(SET_DDRAM_ADDR | self.ddram_address(row, col))?;
 
         self.cursor_row = row;
         self.cursor_col = col;
         Ok(())
     }
+
+    async fn set_backlight(&mut self, on: bool) -> Result<()> {
+        self.backlight = on;
+        // The backpack only updates its pins on a write; send one with enable low
+        let byte = if on { BACKLIGHT } else { 0 };
+        self.bus.write(&[byte]).device(Device::Display)
+    }
+
+    async fn load_glyphs(&mut self, glyphs: &[Glyph]) -> Result<()> {
+        self.upload_glyphs(glyphs)
+    }
+
+    fn columns(&self) -> u8 {
+        self.cols
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hardware::mock::{I2cOp, MockI2cBus};
+
+    /// What the LCD received: (register select, byte) per latched byte
+    fn decode(ops: &[I2cOp]) -> Vec<(bool, u8)> {
+        let nibbles: Vec<u8> = ops.iter()
+            .filter_map(|op| match op {
+                I2cOp::Write(bytes) if bytes.len() == 2 && bytes[0] & ENABLE != 0 => Some(bytes[1]),
+                _ => None,
+            })
+            .collect();
+
+        nibbles.chunks(2)
+            .map(|pair| (pair[0] & RS != 0, (pair[0] & 0xF0) | (pair[1] >> 4)))
+            .collect()
+    }
+
+    fn text(received: &[(bool, u8)]) -> String {
+        received.iter()
+            .map(|&(rs, byte)| if rs { (byte as char).to_string() } else { format!("[{:02X}]", byte) })
+            .collect()
+    }
+
+    fn lcd(rows: u8, cols: u8) -> (I2cLcdDisplay<MockI2cBus>, MockI2cBus) {
+        let bus = MockI2cBus::new();
+        let display = I2cLcdDisplay::with_bus(bus.clone(), rows, cols).unwrap();
+        bus.clear();
+        (display, bus)
+    }
+
+    #[test]
+    fn test_init_sequence_and_timing() {
+        let bus = MockI2cBus::new();
+        I2cLcdDisplay::with_bus(bus.clone(), 2, 16).unwrap();
+        let ops = bus.ops();
+
+        // Power-on wait, then three wake-up nibbles and the switch to 4-bit
+        let wake: Vec<_> = ops.iter().take(9).cloned().collect();
+        assert_eq!(wake, [
+            I2cOp::Delay(POWER_ON_US),
+            I2cOp::Write(vec![0x3C, 0x38]),
+            I2cOp::Delay(FIRST_WAKE_US),
+            I2cOp::Write(vec![0x3C, 0x38]),
+            I2cOp::Delay(WAKE_US),
+            I2cOp::Write(vec![0x3C, 0x38]),
+            I2cOp::Delay(WAKE_US),
+            I2cOp::Write(vec![0x2C, 0x28]),
+            I2cOp::Delay(WAKE_US),
+        ]);
+
+        assert_eq!(decode(&ops[9..]), [(false, 0x28), (false, 0x08), (false, 0x01), (false, 0x06), (false, 0x0C)]);
+        assert!(ops.contains(&I2cOp::Delay(CLEAR_US)));
+    }
+
+    #[tokio::test]
+    async fn test_newline_moves_to_next_row() {
+        let (mut display, bus) = lcd(2, 16);
+
+        display.show_message("HotChocolaBot\nReady!").await.unwrap();
+
+        assert_eq!(text(&decode(&bus.ops())), "[01]HotChocolaBot[C0]Ready!");
+    }
+
+    #[tokio::test]
+    async fn test_wraps_and_truncates() {
+        let (mut display, bus) = lcd(4, 20);
+        display.write("Hot chocolate is on its way").await.unwrap();
+        display.write("\nrow 3\nrow 4\nrow 5").await.unwrap();
+        assert_eq!(text(&decode(&bus.ops())), "Hot chocolate is on [C0]its way[94]row 3[D4]row 4");
+
+        let (mut display, bus) = lcd(2, 16);
+        display.write("0123456789abcdefXYZ\nlost").await.unwrap();
+        assert_eq!(text(&decode(&bus.ops())), "0123456789abcdef[C0]XYZ");
+    }
+
+    #[tokio::test]
+    async fn test_backlight_bit_follows_setting() {
+        let (mut display, bus) = lcd(2, 16);
+
+        display.set_backlight(false).await.unwrap();
+        display.write("A").await.unwrap();
+
+        assert!(bus.written().iter().all(|byte| byte & BACKLIGHT == 0));
+        assert_eq!(decode(&bus.ops()), [(true, b'A')]);
+    }
+
+    #[tokio::test]
+    async fn test_glyphs_uploaded_and_written_by_slot() {
+        let (mut display, bus) = lcd(2, 16);
+
+        display.load_glyphs(&Glyph::ALL).await.unwrap();
+        let received = decode(&bus.ops());
+        assert_eq!(received[0], (false, SET_CGRAM_ADDR));
+        assert_eq!(received[1..9], Glyph::Degree.bitmap().map(|row| (true, row)));
+        assert_eq!(received.last(), Some(&(false, SET_DDRAM_ADDR)));
+
+        bus.clear();
+        display.show_progress("Milk", 0.5).await.unwrap();
+        let shown = decode(&bus.ops());
+        assert_eq!(text(&shown), "[01]Milk[C0]\u{ff}\u{ff}\u{ff}\u{ff}\u{ff}\u{6}       50%");
+    }
 
+    #[cfg(feature = "rpi")]
     #[tokio::test]
+    #[ignore = "requires an I2C LCD"]
     async fn test_display_creation() {
         let display = I2cLcdDisplay::new(0x27, 2, 16).unwrap();
         assert_eq!(display.rows, 2);
         assert_eq!(display.cols, 16);
     }
 
+    #[cfg(feature = "rpi")]
     #[tokio::test]
+    #[ignore = "requires an I2C LCD"]
     async fn test_display_write() {
         let mut display = I2cLcdDisplay::new(0x27, 2, 16).unwrap();
         assert!(display.write("Hello").await.is_ok());
@@ -0,0 +1,127 @@
+//! Emergency stop button implementation using a GPIO interrupt
+
+use crate::hardware::{EmergencyStop, EmergencyStopCallback};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
+
+use rppal::gpio::{Gpio, InputPin, Level, Trigger};
+
+/// Edges closer together than this are contact bounce, not new presses
+pub(crate) const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Rejects edges that follow an accepted edge within the debounce window
+pub(crate) struct Debouncer {
+    window: Duration,
+    last_accepted: Option<Instant>,
+}
+
+impl Debouncer {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self { window, last_accepted: None }
+    }
+
+    /// Whether an edge at `now` counts as a new press
+    pub(crate) fn accept(&mut self, now: Instant) -> bool {
+        let bounce = self.last_accepted
+            .is_some_and(|last| now.duration_since(last) < self.window);
+
+        if !bounce {
+            self.last_accepted = Some(now);
+        }
+        !bounce
+    }
+}
+
+/// Normally-open emergency stop button wired between a GPIO pin and ground
+///
+/// Presses are delivered by an edge interrupt, so callbacks fire even while
+/// the controller is blocked waiting on a pump. The pin uses the internal
+/// pull-up and contact bounce is filtered in software.
+pub struct GpioEmergencyStop {
+    pin: InputPin,
+    callbacks: Arc<Mutex<Vec<EmergencyStopCallback>>>,
+}
+
+impl GpioEmergencyStop {
+    /// Create new emergency stop input
+    pub fn new(pin_number: u8) -> Result<Self> {
+        let callbacks: Arc<Mutex<Vec<EmergencyStopCallback>>> = Arc::new(Mutex::new(Vec::new()));
+
+        let mut pin = Gpio::new()
+            .context("Failed to initialize GPIO")?
+            .get(pin_number)
+            .context(format!("Failed to get GPIO pin {}", pin_number))?
+            .into_input_pullup();
+
+        // Pressing the button pulls the line low
+        let interrupt_callbacks = Arc::clone(&callbacks);
+        let mut debouncer = Debouncer::new(DEBOUNCE);
+        pin.set_async_interrupt(Trigger::FallingEdge, move |level| {
+            if level == Level::Low && debouncer.accept(Instant::now()) {
+                Self::fire(&interrupt_callbacks);
+            } else {
+                debug!("Ignoring emergency stop bounce");
+            }
+        })
+        .context(format!("Failed to register interrupt on GPIO pin {}", pin_number))?;
+
+        info!("Initialized emergency stop on GPIO pin {}", pin_number);
+
+        Ok(Self { pin, callbacks })
+    }
+
+    /// Invoke every registered callback (runs on the interrupt thread)
+    fn fire(callbacks: &Mutex<Vec<EmergencyStopCallback>>) {
+        error!("Emergency stop button pressed");
+
+        match callbacks.lock() {
+            Ok(callbacks) => callbacks.iter().for_each(|callback| callback()),
+            Err(e) => error!("Emergency stop callbacks poisoned: {}", e),
+        }
+    }
+}
+
+#[async_trait]
+impl EmergencyStop for GpioEmergencyStop {
+    async fn is_pressed(&self) -> bool {
+        self.pin.is_low()
+    }
+
+    fn on_press(&mut self, callback: EmergencyStopCallback) {
+        self.callbacks.lock().unwrap().push(callback);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
+    async fn test_emergency_stop_released_and_callbacks_fire() {
+        let mut estop = GpioEmergencyStop::new(23).unwrap();
+        assert!(!estop.is_pressed().await);
+
+        let fired = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&fired);
+        estop.on_press(Box::new(move || flag.store(true, Ordering::SeqCst)));
+
+        GpioEmergencyStop::fire(&estop.callbacks);
+        assert!(fired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_debouncer_filters_contact_bounce() {
+        let mut debouncer = Debouncer::new(DEBOUNCE);
+        let start = Instant::now();
+
+        assert!(debouncer.accept(start));
+        assert!(!debouncer.accept(start + Duration::from_millis(2)));
+        assert!(!debouncer.accept(start + Duration::from_millis(30)));
+        assert!(debouncer.accept(start + Duration::from_millis(200)));
+    }
+}
@@ -0,0 +1,157 @@
+//! Builds the device set for the configured backend
+//!
+//! The backend is chosen at runtime from `HardwareConfig::backend`; each
+//! backend's drivers are only compiled in with the matching cargo feature
+//! (`sim` for mocks, `rpi` for GPIO/I2C).
+
+use crate::config::{Backend, BotConfig, Ingredient};
+use crate::hardware::{Adc, Display, EmergencyStop, Heater, LevelSensor, Pump, StatusLed, TemperatureSensor};
+use anyhow::Result;
+#[cfg(any(feature = "sim", feature = "rpi", test))]
+use tracing::info;
+
+/// The set of devices a controller drives
+pub struct ControllerHardware {
+    pub cocoa_pump: Box<dyn Pump>,
+    pub milk_pump: Box<dyn Pump>,
+    pub sugar_pump: Box<dyn Pump>,
+    pub temp_sensor: Box<dyn TemperatureSensor>,
+    pub heater: Box<dyn Heater>,
+    /// Stirrer motor; switched like a pump, so it shares the `Pump` interface
+    pub mixer: Option<Box<dyn Pump>>,
+    /// Flushes the lines during a clean; without one each ingredient pump flushes itself
+    pub water_pump: Option<Box<dyn Pump>>,
+    pub display: Box<dyn Display>,
+    pub emergency_stop: Option<Box<dyn EmergencyStop>>,
+    pub status_led: Box<dyn StatusLed>,
+    pub adc: Box<dyn Adc>,
+    /// Reservoirs without a sensor rely on the dispensed-amount estimate alone
+    pub level_sensors: Vec<(Ingredient, Box<dyn LevelSensor>)>,
+}
+
+impl ControllerHardware {
+    /// Build the devices for `config.hardware.backend`
+    pub fn build(config: &BotConfig) -> Result<Self> {
+        match config.hardware.backend {
+            Backend::Mock => Self::sim(),
+            Backend::Rpi => Self::rpi(config),
+        }
+    }
+
+    #[cfg(any(feature = "sim", test))]
+    fn sim() -> Result<Self> {
+        info!("Initializing MOCK hardware...");
+        Ok(Self::mock())
+    }
+
+    #[cfg(not(any(feature = "sim", test)))]
+    fn sim() -> Result<Self> {
+        Err(crate::error::HotChocolaBotError::Config("backend = \"mock\" requires building with the `sim` feature".to_string()).into())
+    }
+
+    #[cfg(feature = "rpi")]
+    fn rpi(config: &BotConfig) -> Result<Self> {
+        use crate::hardware::adc::Ads1115Adc;
+        use crate::config::DisplayKind;
+        use crate::hardware::display::I2cLcdDisplay;
+        use crate::hardware::estop::GpioEmergencyStop;
+        use crate::hardware::heater::GpioHeater;
+        use crate::hardware::led::GpioStatusLed;
+        use crate::hardware::level::GpioFloatSwitch;
+        use crate::hardware::pump::GpioPump;
+        use crate::hardware::sensor::I2cTemperatureSensor;
+        use crate::hardware::ssd1306::Ssd1306Display;
+        use crate::error::{Device, DeviceContext};
+
+        info!("Initializing Raspberry Pi hardware...");
+        let hw = &config.hardware;
+
+        let mut level_sensors: Vec<(Ingredient, Box<dyn LevelSensor>)> = Vec::new();
+        for ingredient in Ingredient::ALL {
+            if let Some(pin) = config.reservoirs.get(ingredient).level_pin {
+                level_sensors.push((ingredient, Box::new(GpioFloatSwitch::new(pin).device(Device::LevelSensor(ingredient))?)));
+            }
+        }
+
+        Ok(Self {
+            cocoa_pump: Box::new(GpioPump::new(hw.cocoa_pump_pin, "Cocoa").device(Device::Pump(Ingredient::Cocoa))?),
+            milk_pump: Box::new(GpioPump::new(hw.milk_pump_pin, "Milk").device(Device::Pump(Ingredient::Milk))?),
+            sugar_pump: Box::new(GpioPump::new(hw.sugar_pump_pin, "Sugar").device(Device::Pump(Ingredient::Sugar))?),
+            temp_sensor: Box::new(I2cTemperatureSensor::new(hw.temp_sensor_addr).device(Device::TemperatureSensor)?),
+            heater: Box::new(GpioHeater::new(hw.heater_pin, "Milk", config.heater.pwm_window_ms).device(Device::Heater)?),
+            mixer: match hw.mixer_pin {
+                Some(pin) => Some(Box::new(GpioPump::new(pin, "Mixer").device(Device::Mixer)?)),
+                None => None,
+            },
+            water_pump: match hw.water_pump_pin {
+                Some(pin) => Some(Box::new(GpioPump::new(pin, "Water").device(Device::WaterPump)?)),
+                None => None,
+            },
+            display: match hw.display {
+                DisplayKind::Lcd => Box::new(I2cLcdDisplay::new(hw.lcd_addr, hw.lcd_rows, hw.lcd_cols).device(Device::Display)?),
+                DisplayKind::Ssd1306 => Box::new(Ssd1306Display::new(hw.oled_addr).device(Device::Display)?),
+                DisplayKind::Terminal => return Err(crate::error::HotChocolaBotError::Config("display = \"terminal\" requires backend = \"mock\"".to_string()).into()),
+            },
+            emergency_stop: Some(Box::new(GpioEmergencyStop::new(hw.emergency_stop_pin).device(Device::EmergencyStop)?)),
+            status_led: Box::new(GpioStatusLed::new(hw.status_led_pin).device(Device::StatusLed)?),
+            adc: Box::new(Ads1115Adc::new(hw.adc_addr).device(Device::Adc)?),
+            level_sensors,
+        })
+    }
+
+    #[cfg(not(feature = "rpi"))]
+    fn rpi(_config: &BotConfig) -> Result<Self> {
+        Err(crate::error::HotChocolaBotError::Config("backend = \"rpi\" requires building with the `rpi` feature".to_string()).into())
+    }
+
+    /// Mock devices for testing and development
+    #[cfg(any(feature = "sim", test))]
+    pub fn mock() -> Self {
+        use crate::hardware::mock::{
+            MockAdc, MockDisplay, MockEmergencyStop, MockHeater, MockPump, MockStatusLed,
+            MockTemperatureSensor, ThermalModel,
+        };
+
+        // Sensor and heater share a thermal model so the heating loop behaves realistically
+        let model = ThermalModel::new(20.0);
+
+        Self {
+            cocoa_pump: Box::new(MockPump::new("Cocoa")),
+            milk_pump: Box::new(MockPump::new("Milk")),
+            sugar_pump: Box::new(MockPump::new("Sugar")),
+            temp_sensor: Box::new(MockTemperatureSensor::with_model(model.clone())),
+            heater: Box::new(MockHeater::with_model(model)),
+            mixer: Some(Box::new(MockPump::new("Mixer"))),
+            water_pump: None,
+            display: Box::new(MockDisplay::new()),
+            emergency_stop: Some(Box::new(MockEmergencyStop::new())),
+            status_led: Box::new(MockStatusLed::new()),
+            // 12V supply through the default 4:1 divider
+            adc: Box::new(MockAdc::new(3.0)),
+            level_sensors: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_backend_builds() {
+        let mut config = BotConfig::default();
+        config.hardware.backend = Backend::Mock;
+
+        assert!(ControllerHardware::build(&config).is_ok());
+    }
+
+    #[cfg(not(feature = "rpi"))]
+    #[test]
+    fn test_rpi_backend_needs_feature() {
+        let mut config = BotConfig::default();
+        config.hardware.backend = Backend::Rpi;
+
+        let err = ControllerHardware::build(&config).err().unwrap();
+        assert!(err.to_string().contains("`rpi` feature"));
+    }
+}
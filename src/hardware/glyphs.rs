@@ -0,0 +1,111 @@
+//! Custom LCD characters and the progress bar built from them
+//!
+//! Text written to a display uses each glyph's Unicode symbol, so it reads
+//! naturally in logs and on `MockDisplay`. Character LCDs upload the
+//! bitmaps into their eight CGRAM slots and swap the symbols for slot codes.
+
+/// Custom character shown on the LCD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Degree,
+    Cup,
+    Warning,
+    Thermometer,
+    /// Progress bar cell with 1 to 4 of its 5 pixel columns filled
+    Bar1,
+    Bar2,
+    Bar3,
+    Bar4,
+}
+
+/// A completely filled bar cell; character LCDs have one in ROM
+pub const FULL_BLOCK: char = '█';
+
+impl Glyph {
+    /// Every glyph, filling the HD44780's eight CGRAM slots
+    pub const ALL: [Glyph; 8] = [
+        Glyph::Degree,
+        Glyph::Cup,
+        Glyph::Warning,
+        Glyph::Thermometer,
+        Glyph::Bar1,
+        Glyph::Bar2,
+        Glyph::Bar3,
+        Glyph::Bar4,
+    ];
+
+    /// Partial bar cells, by filled columns minus one
+    const BARS: [Glyph; 4] = [Glyph::Bar1, Glyph::Bar2, Glyph::Bar3, Glyph::Bar4];
+
+    /// Character standing for the glyph in display text
+    pub fn symbol(self) -> char {
+        match self {
+            Glyph::Degree => '°',
+            Glyph::Cup => '☕',
+            Glyph::Warning => '⚠',
+            Glyph::Thermometer => '🌡',
+            Glyph::Bar1 => '▏',
+            Glyph::Bar2 => '▎',
+            Glyph::Bar3 => '▍',
+            Glyph::Bar4 => '▌',
+        }
+    }
+
+    /// 5x8 pixel rows, top first, for CGRAM
+    #[cfg(any(feature = "rpi", test))]
+    pub fn bitmap(self) -> [u8; 8] {
+        match self {
+            Glyph::Degree => [0x0C, 0x12, 0x12, 0x0C, 0x00, 0x00, 0x00, 0x00],
+            Glyph::Cup => [0x04, 0x08, 0x04, 0x1E, 0x13, 0x13, 0x1E, 0x0C],
+            Glyph::Warning => [0x04, 0x0E, 0x0A, 0x1B, 0x1B, 0x1F, 0x1B, 0x1F],
+            Glyph::Thermometer => [0x04, 0x0A, 0x0A, 0x0A, 0x0E, 0x1F, 0x1F, 0x0E],
+            Glyph::Bar1 => [0x10; 8],
+            Glyph::Bar2 => [0x18; 8],
+            Glyph::Bar3 => [0x1C; 8],
+            Glyph::Bar4 => [0x1E; 8],
+        }
+    }
+}
+
+/// Bar of `width` cells filled to `fraction`, with 5 steps per cell
+pub fn progress_bar(fraction: f32, width: usize) -> String {
+    let steps = (fraction.clamp(0.0, 1.0) * (width * 5) as f32).round() as usize;
+    let (full, partial) = (steps / 5, steps % 5);
+
+    let mut bar: String = std::iter::repeat_n(FULL_BLOCK, full).collect();
+    if partial > 0 {
+        bar.push(Glyph::BARS[partial - 1].symbol());
+    }
+    while bar.chars().count() < width {
+        bar.push(' ');
+    }
+    bar
+}
+
+/// Progress bar followed by the percentage, `cols` characters wide
+pub fn progress_line(fraction: f32, cols: usize) -> String {
+    let percent = (fraction.clamp(0.0, 1.0) * 100.0).round() as u32;
+    format!("{} {:>3}%", progress_bar(fraction, cols.saturating_sub(5)), percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_bar_fills_in_fifths() {
+        assert_eq!(progress_bar(0.0, 4), "    ");
+        assert_eq!(progress_bar(0.25, 4), "█   ");
+        assert_eq!(progress_bar(0.3, 4), "█▏  ");
+        assert_eq!(progress_bar(1.5, 4), "████");
+        assert_eq!(progress_line(0.5, 16), "█████▍       50%");
+    }
+
+    #[test]
+    fn test_glyphs_fit_cgram() {
+        for (i, glyph) in Glyph::ALL.into_iter().enumerate() {
+            assert!(!Glyph::ALL[..i].iter().any(|other| other.symbol() == glyph.symbol()));
+            assert!(glyph.bitmap().iter().all(|row| row & !0x1F == 0), "{:?} wider than 5 pixels", glyph);
+        }
+    }
+}
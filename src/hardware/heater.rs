@@ -0,0 +1,140 @@
+//! Heater implementation using a GPIO-driven solid state relay (SSR)
+
+use crate::hardware::Heater;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use rppal::gpio::{Gpio, OutputPin};
+
+/// SSR-switched heater using time-proportional control
+///
+/// An SSR can only be fully on or off, so fractional power is produced by
+/// switching it on for `power * window` of every control window.
+pub struct GpioHeater {
+    output: Arc<Mutex<SsrOutput>>,
+    name: String,
+    power: Arc<Mutex<f32>>,
+    window_ms: u64,
+    driver: Option<JoinHandle<()>>,
+}
+
+/// The physical relay output, shared with the switching task
+struct SsrOutput {
+    pin: OutputPin,
+}
+
+impl SsrOutput {
+    fn set(&mut self, on: bool) {
+        if on {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+    }
+}
+
+impl GpioHeater {
+    /// Create new SSR heater controller
+    pub fn new(pin_number: u8, name: &str, window_ms: u64) -> Result<Self> {
+        let pin = Gpio::new()
+            .context("Failed to initialize GPIO")?
+            .get(pin_number)
+            .context(format!("Failed to get GPIO pin {}", pin_number))?
+            .into_output_low();
+
+        info!("Initialized {} heater on GPIO pin {} ({}ms window)", name, pin_number, window_ms);
+
+        Ok(Self {
+            output: Arc::new(Mutex::new(SsrOutput { pin })),
+            name: name.to_string(),
+            power: Arc::new(Mutex::new(0.0)),
+            window_ms: window_ms.max(100),
+            driver: None,
+        })
+    }
+
+    /// Start the time-proportioning task if it is not already running
+    fn ensure_driver(&mut self) {
+        if self.driver.is_some() {
+            return;
+        }
+
+        let output = Arc::clone(&self.output);
+        let power = Arc::clone(&self.power);
+        let window_ms = self.window_ms;
+
+        self.driver = Some(tokio::spawn(async move {
+            loop {
+                let power = *power.lock().unwrap();
+                let on_ms = (power * window_ms as f32) as u64;
+
+                if on_ms > 0 {
+                    output.lock().unwrap().set(true);
+                    sleep(Duration::from_millis(on_ms)).await;
+                }
+
+                if on_ms < window_ms {
+                    output.lock().unwrap().set(false);
+                    sleep(Duration::from_millis(window_ms - on_ms)).await;
+                }
+            }
+        }));
+    }
+}
+
+#[async_trait]
+impl Heater for GpioHeater {
+    async fn set_power(&mut self, power: f32) -> Result<()> {
+        let power = power.clamp(0.0, 1.0);
+        *self.power.lock().unwrap() = power;
+
+        if power > 0.0 {
+            self.ensure_driver();
+        } else {
+            // Switch off now rather than waiting for the end of the window
+            if let Some(driver) = self.driver.take() {
+                driver.abort();
+            }
+            self.output.lock().unwrap().set(false);
+        }
+
+        debug!("{} heater power set to {:.0}%", self.name, power * 100.0);
+        Ok(())
+    }
+
+    fn power(&self) -> f32 {
+        *self.power.lock().unwrap()
+    }
+}
+
+impl Drop for GpioHeater {
+    fn drop(&mut self) {
+        if let Some(driver) = self.driver.take() {
+            driver.abort();
+        }
+        self.output.lock().unwrap().set(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
+    async fn test_heater_power_clamped() {
+        let mut heater = GpioHeater::new(25, "test", 1000).unwrap();
+
+        heater.set_power(1.5).await.unwrap();
+        assert_eq!(heater.power(), 1.0);
+
+        heater.off().await.unwrap();
+        assert_eq!(heater.power(), 0.0);
+        assert!(heater.driver.is_none());
+    }
+}
@@ -0,0 +1,28 @@
+//! I2C bus abstraction for byte-level device drivers
+//!
+//! Drivers that talk to a device over I2C write through `I2cBus` instead of
+//! rppal directly, so `MockI2cBus` can record the exact traffic in tests.
+
+use anyhow::Result;
+use std::time::Duration;
+
+/// Write side of an I2C bus with a device address already selected
+pub trait I2cBus: Send + Sync {
+    /// Write bytes to the device in one transaction
+    fn write(&mut self, bytes: &[u8]) -> Result<()>;
+
+    /// Wait at least `us` microseconds, e.g. for a command to complete
+    fn delay_us(&mut self, us: u64) {
+        std::thread::sleep(Duration::from_micros(us));
+    }
+}
+
+/// rppal's bus is not `Sync`, so drivers hold it behind a lock
+#[cfg(feature = "rpi")]
+impl I2cBus for std::sync::Mutex<rppal::i2c::I2c> {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        let bus = self.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        bus.write(bytes)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Status LED implementation using GPIO control
+
+use crate::hardware::StatusLed;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use rppal::gpio::{Gpio, OutputPin};
+
+/// Status LED driven directly from a GPIO pin (through a series resistor)
+pub struct GpioStatusLed {
+    pin: OutputPin,
+    is_on: bool,
+}
+
+impl GpioStatusLed {
+    /// Create new status LED, initially off
+    pub fn new(pin_number: u8) -> Result<Self> {
+        let pin = Gpio::new()
+            .context("Failed to initialize GPIO")?
+            .get(pin_number)
+            .context(format!("Failed to get GPIO pin {}", pin_number))?
+            .into_output_low();
+
+        info!("Initialized status LED on GPIO pin {}", pin_number);
+
+        Ok(Self { pin, is_on: false })
+    }
+
+    /// Check if LED is currently lit
+    #[cfg(test)]
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+
+    fn set(&mut self, on: bool) {
+        if on {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+
+        self.is_on = on;
+    }
+}
+
+#[async_trait]
+impl StatusLed for GpioStatusLed {
+    async fn on(&mut self) -> Result<()> {
+        self.set(true);
+        Ok(())
+    }
+
+    async fn off(&mut self) -> Result<()> {
+        self.set(false);
+        Ok(())
+    }
+
+    async fn blink(&mut self, on_ms: u64, off_ms: u64, count: u32) -> Result<()> {
+        debug!("Status LED blinking {}ms on, {}ms off, {} times", on_ms, off_ms, count);
+
+        for _ in 0..count {
+            self.set(true);
+            sleep(Duration::from_millis(on_ms)).await;
+            self.set(false);
+            sleep(Duration::from_millis(off_ms)).await;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for GpioStatusLed {
+    fn drop(&mut self) {
+        self.set(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
+    async fn test_led_on_off() {
+        let mut led = GpioStatusLed::new(24).unwrap();
+        assert!(!led.is_on());
+
+        led.on().await.unwrap();
+        assert!(led.is_on());
+
+        led.blink(10, 10, 2).await.unwrap();
+        assert!(!led.is_on());
+    }
+}
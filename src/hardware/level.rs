@@ -0,0 +1,43 @@
+//! Reservoir float switch implementation using a GPIO input
+
+use crate::hardware::LevelSensor;
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use tracing::info;
+
+use rppal::gpio::{Gpio, InputPin};
+
+/// Float switch mounted at a reservoir's low mark, wired between a GPIO pin and ground
+///
+/// The float closes the switch while it is floating, so the pulled-up pin
+/// reads high once the ingredient drops below the mark. A switch only knows
+/// above or below, not how much is left.
+pub struct GpioFloatSwitch {
+    pin: InputPin,
+}
+
+impl GpioFloatSwitch {
+    /// Create new float switch input
+    pub fn new(pin_number: u8) -> Result<Self> {
+        let pin = Gpio::new()
+            .context("Failed to initialize GPIO")?
+            .get(pin_number)
+            .context(format!("Failed to get GPIO pin {}", pin_number))?
+            .into_input_pullup();
+
+        info!("Initialized float switch on GPIO pin {}", pin_number);
+
+        Ok(Self { pin })
+    }
+}
+
+#[async_trait]
+impl LevelSensor for GpioFloatSwitch {
+    async fn read_level(&mut self) -> Result<Option<f32>> {
+        Ok(None)
+    }
+
+    async fn is_low(&mut self) -> Result<bool> {
+        Ok(self.pin.is_high())
+    }
+}
@@ -1,10 +1,15 @@
 //! Mock hardware implementations for testing without physical devices
 
-use crate::hardware::{Pump, TemperatureSensor, Display, EmergencyStop, StatusLed};
+#[cfg(test)]
+use crate::hardware::i2c::I2cBus;
+use crate::hardware::{Pump, TemperatureSensor, Heater, Display, EmergencyStop, EmergencyStopCallback, StatusLed, Adc};
+#[cfg(test)]
+use crate::hardware::{ButtonCallback, LevelSensor, OrderButton};
+use crate::error::{Device, HotChocolaBotError};
 use anyhow::Result;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use tokio::time::Instant;
 use tracing::info;
 
 /// Mock pump for testing
@@ -18,6 +23,8 @@ struct MockPumpState {
     is_running: bool,
     total_runtime_ms: u64,
     last_start: Option<Instant>,
+    hung: bool,
+    failing: bool,
 }
 
 impl MockPump {
@@ -28,40 +35,77 @@ pub fn new(name: &str) -> Self {
                 is_running: false,
                 total_runtime_ms: 0,
                 last_start: None,
+                hung: false,
+                failing: false,
             })),
         }
     }
-}
 
-#[async_trait]
-impl Pump for MockPump {
-    async fn dispense(&mut self, duration_ms: u64) -> Result<()> {
-        let mut state = self.state.lock().unwrap();
+    /// Make future dispenses never return, like a driver stuck on the bus
+    #[cfg(test)]
+    pub fn set_hung(&self, hung: bool) {
+        self.state.lock().unwrap().hung = hung;
+    }
 
-        info!("[MOCK] {} pump dispensing for {}ms", self.name, duration_ms);
+    /// Make future dispenses fail, like a driver that lost its GPIO line
+    #[cfg(test)]
+    pub fn set_failing(&self, failing: bool) {
+        self.state.lock().unwrap().failing = failing;
+    }
 
-        state.is_running = true;
-        state.last_start = Some(Instant::now());
+    /// Mark the pump stopped and account its runtime
+    fn halt(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.is_running = false;
 
-        drop(state); // Release lock during sleep
+        if let Some(start) = state.last_start.take() {
+            let ran_ms = start.elapsed().as_millis() as u64;
+            state.total_runtime_ms += ran_ms;
+            info!(telemetry = "pump_stop", pump = %self.name, ran_ms,
+                  "[MOCK] {} pump stopped after {}ms", self.name, ran_ms);
+        }
+    }
+}
 
-        tokio::time::sleep(tokio::time::Duration::from_millis(duration_ms)).await;
+/// Marks the mock pump stopped and accounts runtime when dropped
+struct MockPumpHalt<'a>(&'a MockPump);
 
-        let mut state = self.state.lock().unwrap();
-        state.is_running = false;
+impl Drop for MockPumpHalt<'_> {
+    fn drop(&mut self) {
+        self.0.halt();
+    }
+}
 
-        if let Some(start) = state.last_start {
-            let elapsed = start.elapsed().as_millis() as u64;
-            state.total_runtime_ms += elapsed;
+#[async_trait]
+impl Pump for MockPump {
+    async fn dispense(&self, duration_ms: u64) -> Result<()> {
+        info!(telemetry = "pump_start", pump = %self.name, duration_ms,
+              "[MOCK] {} pump dispensing for {}ms", self.name, duration_ms);
+
+        let hung = {
+            let mut state = self.state.lock().unwrap();
+            if state.failing {
+                anyhow::bail!("{} pump driver not responding", self.name);
+            }
+            state.is_running = true;
+            state.last_start = Some(Instant::now());
+            state.hung
+        }; // Release lock during sleep
+
+        // Like the real pump, stop if the dispense is cancelled mid-run
+        let guard = MockPumpHalt(self);
+
+        if hung {
+            std::future::pending::<()>().await;
         }
+        tokio::time::sleep(tokio::time::Duration::from_millis(duration_ms)).await;
 
+        drop(guard);
         Ok(())
     }
 
-    async fn stop(&mut self) -> Result<()> {
-        let mut state = self.state.lock().unwrap();
-        info!("[MOCK] {} pump stopped", self.name);
-        state.is_running = false;
+    async fn stop(&self) -> Result<()> {
+        self.halt();
         Ok(())
     }
 
@@ -73,35 +117,143 @@ fn total_runtime_ms(&self) -> u64 {
         self.state.lock().unwrap().total_runtime_ms
     }
 
-    fn reset_counter(&mut self) {
+    fn reset_counter(&self) {
         let mut state = self.state.lock().unwrap();
         info!("[MOCK] {} pump counter reset", self.name);
         state.total_runtime_ms = 0;
     }
 }
 
+/// First-order thermal model of the milk reservoir
+///
+/// Temperature follows `dT/dt = heating_rate * power - loss_rate * (T - ambient)`,
+/// advanced lazily using tokio time so tests can run it with paused time.
+#[derive(Clone)]
+pub struct ThermalModel {
+    state: Arc<Mutex<ThermalState>>,
+}
+
+struct ThermalState {
+    temperature: f32,
+    ambient: f32,
+    power: f32,
+    heating_rate: f32,
+    loss_rate: f32,
+    last_update: Instant,
+}
+
+impl ThermalModel {
+    /// Create a model starting at (and losing heat towards) the given ambient temperature
+    pub fn new(ambient: f32) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ThermalState {
+                temperature: ambient,
+                ambient,
+                power: 0.0,
+                heating_rate: 2.0,
+                loss_rate: 0.01,
+                last_update: Instant::now(),
+            })),
+        }
+    }
+
+    /// Override heating rate (°C/s at full power) and loss rate (1/s)
+    #[cfg(test)]
+    pub fn with_rates(self, heating_rate: f32, loss_rate: f32) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.heating_rate = heating_rate;
+            state.loss_rate = loss_rate;
+        }
+        self
+    }
+
+    /// Current simulated temperature
+    pub fn temperature(&self) -> f32 {
+        let mut state = self.state.lock().unwrap();
+        Self::advance(&mut state);
+        state.temperature
+    }
+
+    /// Force the simulated temperature (for testing)
+    #[cfg(test)]
+    pub fn set_temperature(&self, temp: f32) {
+        let mut state = self.state.lock().unwrap();
+        Self::advance(&mut state);
+        state.temperature = temp;
+    }
+
+    /// Current heater power applied to the model
+    #[cfg(test)]
+    pub fn power(&self) -> f32 {
+        self.state.lock().unwrap().power
+    }
+
+    fn set_power(&self, power: f32) {
+        let mut state = self.state.lock().unwrap();
+        Self::advance(&mut state);
+        state.power = power;
+    }
+
+    /// Integrate the model up to now in small steps for stability
+    fn advance(state: &mut ThermalState) {
+        const STEP_SECS: f32 = 0.1;
+
+        let now = Instant::now();
+        let mut remaining = now.duration_since(state.last_update).as_secs_f32();
+        state.last_update = now;
+
+        while remaining > 0.0 {
+            let dt = remaining.min(STEP_SECS);
+            let heating = state.heating_rate * state.power;
+            let loss = state.loss_rate * (state.temperature - state.ambient);
+            state.temperature += (heating - loss) * dt;
+            remaining -= dt;
+        }
+    }
+}
+
 /// Mock temperature sensor
 pub struct MockTemperatureSensor {
     temperature: Arc<Mutex<f32>>,
+    model: Option<ThermalModel>,
 }
 
 impl MockTemperatureSensor {
+    #[cfg(test)]
     pub fn new(initial_temp: f32) -> Self {
         Self {
             temperature: Arc::new(Mutex::new(initial_temp)),
+            model: None,
+        }
+    }
+
+    /// Create a sensor that reads from a thermal model
+    pub fn with_model(model: ThermalModel) -> Self {
+        let initial_temp = model.temperature();
+        Self {
+            temperature: Arc::new(Mutex::new(initial_temp)),
+            model: Some(model),
         }
     }
 
     /// Set the mock temperature (for testing)
+    #[cfg(test)]
     pub fn set_temperature(&mut self, temp: f32) {
-        *self.temperature.lock().unwrap() = temp;
+        match &self.model {
+            Some(model) => model.set_temperature(temp),
+            None => *self.temperature.lock().unwrap() = temp,
+        }
     }
 }
 
 #[async_trait]
 impl TemperatureSensor for MockTemperatureSensor {
     async fn read_temperature(&mut self) -> Result<f32> {
-        let temp = *self.temperature.lock().unwrap();
+        let temp = match &self.model {
+            Some(model) => model.temperature(),
+            None => *self.temperature.lock().unwrap(),
+        };
         info!("[MOCK] Temperature reading: {:.1}°C", temp);
         Ok(temp)
     }
@@ -111,69 +263,373 @@ async fn is_healthy(&self) -> bool {
     }
 }
 
+/// Mock heater, optionally driving a thermal model
+#[derive(Default)]
+pub struct MockHeater {
+    power: Arc<Mutex<f32>>,
+    model: Option<ThermalModel>,
+}
+
+impl MockHeater {
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self {
+            power: Arc::new(Mutex::new(0.0)),
+            model: None,
+        }
+    }
+
+    /// Create a heater that heats a thermal model
+    pub fn with_model(model: ThermalModel) -> Self {
+        Self {
+            power: Arc::new(Mutex::new(0.0)),
+            model: Some(model),
+        }
+    }
+}
+
+#[async_trait]
+impl Heater for MockHeater {
+    async fn set_power(&mut self, power: f32) -> Result<()> {
+        let power = power.clamp(0.0, 1.0);
+        *self.power.lock().unwrap() = power;
+
+        if let Some(model) = &self.model {
+            model.set_power(power);
+        }
+
+        info!("[MOCK] Heater power {:.0}%", power * 100.0);
+        Ok(())
+    }
+
+    fn power(&self) -> f32 {
+        *self.power.lock().unwrap()
+    }
+}
+
+/// DDRAM of an HD44780: two 40-character lines, shown through a window
+///
+/// Rows 2 and 3 of a 4-row display are the right-hand halves of lines 0
+/// and 1, so they start `cols` characters in.
+struct CharLcd {
+    rows: u8,
+    cols: u8,
+    ddram: [[char; 40]; 2],
+    /// Address counter as (line, offset)
+    address: (usize, usize),
+    cursor_row: u8,
+    cursor_col: u8,
+}
+
+impl CharLcd {
+    fn new(rows: u8, cols: u8) -> Self {
+        Self {
+            rows,
+            cols,
+            ddram: [[' '; 40]; 2],
+            address: (0, 0),
+            cursor_row: 0,
+            cursor_col: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.ddram = [[' '; 40]; 2];
+        self.address = (0, 0);
+        self.cursor_row = 0;
+        self.cursor_col = 0;
+    }
+
+    fn set_cursor(&mut self, row: u8, col: u8) -> Result<()> {
+        if row >= self.rows || col >= self.cols {
+            return Err(HotChocolaBotError::hardware(
+                Device::Display,
+                format!("cursor position out of bounds: ({}, {})", row, col),
+            ).into());
+        }
+
+        self.address = ((row % 2) as usize, (row / 2 * self.cols + col) as usize);
+        self.cursor_row = row;
+        self.cursor_col = col;
+        Ok(())
+    }
+
+    /// Store a character and advance, running from the end of one line into the other
+    fn put(&mut self, c: char) {
+        let (line, offset) = self.address;
+        self.ddram[line][offset] = c;
+        self.address = if offset + 1 < 40 { (line, offset + 1) } else { (1 - line, 0) };
+    }
+
+    /// Write text the way `I2cLcdDisplay` does: `\n` and long rows move to
+    /// the next row, and anything past the last row is dropped
+    fn write(&mut self, text: &str) -> Result<()> {
+        for c in text.chars() {
+            if c == '\n' {
+                if !self.next_row()? {
+                    break;
+                }
+                continue;
+            }
+
+            if self.cursor_col >= self.cols && !self.next_row()? {
+                break;
+            }
+
+            self.put(c);
+            self.cursor_col += 1;
+        }
+
+        Ok(())
+    }
+
+    fn next_row(&mut self) -> Result<bool> {
+        if self.cursor_row + 1 >= self.rows {
+            return Ok(false);
+        }
+        self.set_cursor(self.cursor_row + 1, 0)?;
+        Ok(true)
+    }
+
+    /// Visible rows, each `cols` characters wide
+    fn lines(&self) -> Vec<String> {
+        (0..self.rows)
+            .map(|row| {
+                let start = (row / 2 * self.cols) as usize;
+                self.ddram[(row % 2) as usize][start..start + self.cols as usize].iter().collect()
+            })
+            .collect()
+    }
+}
+
+/// Screen contents as text: rows without trailing spaces, blank rows at the end dropped
+#[cfg(test)]
+fn screen_text(lines: &[String]) -> String {
+    let rows: Vec<&str> = lines.iter().map(|line| line.trim_end()).collect();
+    let shown = rows.iter().rposition(|row| !row.is_empty()).map_or(0, |last| last + 1);
+    rows[..shown].join("\n")
+}
+
+/// What the display showed from a moment in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub at: Instant,
+    /// Visible rows, each the full display width
+    pub lines: Vec<String>,
+}
+
+impl Frame {
+    /// The frame as `get_buffer` would have returned it
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        screen_text(&self.lines)
+    }
+}
+
+struct MockDisplayState {
+    lcd: CharLcd,
+    frames: Vec<Frame>,
+}
+
+impl MockDisplayState {
+    /// Add the current screen to the history, unless it is unchanged
+    fn capture(&mut self) {
+        let lines = self.lcd.lines();
+        if self.frames.last().map(|frame| &frame.lines) != Some(&lines) {
+            self.frames.push(Frame { at: Instant::now(), lines });
+        }
+    }
+}
+
 /// Mock LCD display
+///
+/// Models the character grid of an HD44780 LCD and keeps every distinct
+/// screen it showed, so tests can assert what the physical display would
+/// have looked like. Glyph symbols are stored as their Unicode characters.
+#[derive(Clone)]
 pub struct MockDisplay {
-    buffer: Arc<Mutex<String>>,
+    state: Arc<Mutex<MockDisplayState>>,
 }
 
 impl MockDisplay {
+    /// A 16x2 display
     pub fn new() -> Self {
+        Self::with_size(2, 16)
+    }
+
+    pub fn with_size(rows: u8, cols: u8) -> Self {
+        let state = MockDisplayState { lcd: CharLcd::new(rows, cols), frames: Vec::new() };
         Self {
-            buffer: Arc::new(Mutex::new(String::new())),
+            state: Arc::new(Mutex::new(state)),
         }
     }
 
     /// Get current display buffer (for testing)
+    ///
+    /// Rows are joined with `\n`, trailing spaces and blank rows left off.
+    #[cfg(test)]
     pub fn get_buffer(&self) -> String {
-        self.buffer.lock().unwrap().clone()
+        screen_text(&self.snapshot())
+    }
+
+    /// Visible rows as the LCD shows them, each the full display width
+    #[cfg(any(feature = "tui", test))]
+    pub fn snapshot(&self) -> Vec<String> {
+        self.state.lock().unwrap().lcd.lines()
+    }
+
+    /// Every distinct screen shown so far, oldest first
+    #[cfg(test)]
+    pub fn frames(&self) -> Vec<Frame> {
+        self.state.lock().unwrap().frames.clone()
+    }
+
+    /// Text of every distinct screen shown so far, oldest first
+    #[cfg(test)]
+    pub fn screens(&self) -> Vec<String> {
+        self.frames().iter().map(Frame::text).collect()
+    }
+}
+
+impl Default for MockDisplay {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[async_trait]
 impl Display for MockDisplay {
     async fn write(&mut self, text: &str) -> Result<()> {
-        let mut buffer = self.buffer.lock().unwrap();
-        buffer.push_str(text);
+        let mut state = self.state.lock().unwrap();
+        state.lcd.write(text)?;
+        state.capture();
         info!("[MOCK] Display write: {}", text);
         Ok(())
     }
 
     async fn clear(&mut self) -> Result<()> {
-        let mut buffer = self.buffer.lock().unwrap();
-        buffer.clear();
+        let mut state = self.state.lock().unwrap();
+        state.lcd.clear();
+        state.capture();
         info!("[MOCK] Display cleared");
         Ok(())
     }
 
     async fn set_cursor(&mut self, row: u8, col: u8) -> Result<()> {
+        self.state.lock().unwrap().lcd.set_cursor(row, col)?;
         info!("[MOCK] Display cursor set to ({}, {})", row, col);
         Ok(())
     }
+
+    /// Clear and write as one frame, as a person watching would see it
+    async fn show_message(&mut self, message: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.lcd.clear();
+        state.lcd.write(message)?;
+        state.capture();
+        info!(telemetry = "display", text = message, "[MOCK] Display: {}", message.replace('\n', " | "));
+        Ok(())
+    }
+
+    fn columns(&self) -> u8 {
+        self.state.lock().unwrap().lcd.cols
+    }
+}
+
+/// One transaction on a `MockI2cBus`
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum I2cOp {
+    Write(Vec<u8>),
+    Delay(u64),
+}
+
+/// I2C bus that records traffic instead of sending it, for driver tests
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockI2cBus {
+    ops: Arc<Mutex<Vec<I2cOp>>>,
+}
+
+#[cfg(test)]
+impl MockI2cBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes and delays so far, in order
+    pub fn ops(&self) -> Vec<I2cOp> {
+        self.ops.lock().unwrap().clone()
+    }
+
+    /// Every byte written so far, in order
+    pub fn written(&self) -> Vec<u8> {
+        self.ops.lock().unwrap().iter()
+            .filter_map(|op| match op {
+                I2cOp::Write(bytes) => Some(bytes.as_slice()),
+                I2cOp::Delay(_) => None,
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Forget the traffic so far
+    pub fn clear(&self) {
+        self.ops.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+impl I2cBus for MockI2cBus {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.ops.lock().unwrap().push(I2cOp::Write(bytes.to_vec()));
+        Ok(())
+    }
+
+    fn delay_us(&mut self, us: u64) {
+        self.ops.lock().unwrap().push(I2cOp::Delay(us));
+    }
 }
 
 /// Mock emergency stop button
+#[derive(Clone)]
 pub struct MockEmergencyStop {
     pressed: Arc<Mutex<bool>>,
+    callbacks: Arc<Mutex<Vec<EmergencyStopCallback>>>,
 }
 
 impl MockEmergencyStop {
     pub fn new() -> Self {
         Self {
             pressed: Arc::new(Mutex::new(false)),
+            callbacks: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
     /// Simulate button press (for testing)
+    #[cfg(any(feature = "tui", test))]
     pub fn press(&mut self) {
         *self.pressed.lock().unwrap() = true;
         info!("[MOCK] Emergency stop pressed!");
+
+        for callback in self.callbacks.lock().unwrap().iter() {
+            callback();
+        }
     }
 
     /// Release button (for testing)
+    #[cfg(any(feature = "tui", test))]
     pub fn release(&mut self) {
         *self.pressed.lock().unwrap() = false;
         info!("[MOCK] Emergency stop released");
     }
+
+    /// Whether the button is held down right now
+    #[cfg(all(test, feature = "tui"))]
+    pub fn is_pressed_now(&self) -> bool {
+        *self.pressed.lock().unwrap()
+    }
 }
 
 #[async_trait]
@@ -182,15 +638,102 @@ async fn is_pressed(&self) -> bool {
         *self.pressed.lock().unwrap()
     }
 
-    fn on_press<F>(&mut self, _callback: F)
-    where
-        F: Fn() + Send + 'static,
-    {
+    fn on_press(&mut self, callback: EmergencyStopCallback) {
         info!("[MOCK] Emergency stop callback registered");
+        self.callbacks.lock().unwrap().push(callback);
+    }
+}
+
+/// Mock order button
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockButton {
+    callbacks: Arc<Mutex<Vec<ButtonCallback>>>,
+}
+
+#[cfg(test)]
+impl MockButton {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simulate a press
+    pub fn press(&self) {
+        info!("[MOCK] Order button pressed");
+
+        for callback in self.callbacks.lock().unwrap().iter() {
+            callback();
+        }
+    }
+}
+
+#[cfg(test)]
+impl OrderButton for MockButton {
+    fn on_press(&mut self, callback: ButtonCallback) {
+        self.callbacks.lock().unwrap().push(callback);
+    }
+}
+
+/// Mock ADC reading the same voltage on every channel
+#[derive(Clone)]
+pub struct MockAdc {
+    voltages: Arc<Mutex<[f32; 4]>>,
+}
+
+impl MockAdc {
+    /// Create an ADC reading `voltage` on every channel
+    pub fn new(voltage: f32) -> Self {
+        Self {
+            voltages: Arc::new(Mutex::new([voltage; 4])),
+        }
+    }
+}
+
+#[async_trait]
+impl Adc for MockAdc {
+    async fn read_voltage(&mut self, channel: u8) -> Result<f32> {
+        let voltage = *self.voltages.lock().unwrap()
+            .get(channel as usize)
+            .ok_or_else(|| HotChocolaBotError::hardware(Device::Adc, format!("mock ADC has no channel {}", channel)))?;
+        info!("[MOCK] ADC channel {} reading: {:.2}V", channel, voltage);
+        Ok(voltage)
+    }
+}
+
+/// Mock reservoir level sensor (an ultrasonic ranger that measures the level)
+#[cfg(test)]
+#[derive(Clone)]
+pub struct MockLevelSensor {
+    level: Arc<Mutex<f32>>,
+}
+
+#[cfg(test)]
+impl MockLevelSensor {
+    /// Create a sensor reading `level` (fraction full)
+    pub fn new(level: f32) -> Self {
+        Self {
+            level: Arc::new(Mutex::new(level)),
+        }
+    }
+
+    /// Set the level the sensor reports (for testing)
+    pub fn set_level(&self, level: f32) {
+        *self.level.lock().unwrap() = level;
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl LevelSensor for MockLevelSensor {
+    async fn read_level(&mut self) -> Result<Option<f32>> {
+        let level = *self.level.lock().unwrap();
+        info!("[MOCK] Reservoir level: {:.0}%", level * 100.0);
+        Ok(Some(level))
     }
 }
 
 /// Mock status LED
+#[derive(Clone)]
 pub struct MockStatusLed {
     state: Arc<Mutex<bool>>,
 }
@@ -203,6 +746,7 @@ pub fn new() -> Self {
     }
 
     /// Check if LED is on (for testing)
+    #[cfg(test)]
     pub fn is_on(&self) -> bool {
         *self.state.lock().unwrap()
     }
@@ -242,7 +786,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_mock_pump() {
-        let mut pump = MockPump::new("test");
+        let pump = MockPump::new("test");
         assert!(!pump.is_running());
 
         pump.dispense(100).await.unwrap();
@@ -262,6 +806,23 @@ async fn test_mock_temperature_sensor() {
         assert_eq!(temp, 30.0);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_thermal_model_heats_and_cools() {
+        let model = ThermalModel::new(20.0);
+        let mut heater = MockHeater::with_model(model.clone());
+        let mut sensor = MockTemperatureSensor::with_model(model.clone());
+
+        heater.set_power(1.0).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        let heated = sensor.read_temperature().await.unwrap();
+        assert!(heated > 35.0, "expected heating, got {}", heated);
+
+        heater.off().await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        let cooled = sensor.read_temperature().await.unwrap();
+        assert!(cooled < heated && cooled > 20.0);
+    }
+
     #[tokio::test]
     async fn test_mock_display() {
         let mut display = MockDisplay::new();
@@ -272,6 +833,38 @@ async fn test_mock_display() {
         assert_eq!(display.get_buffer(), "");
     }
 
+    #[tokio::test]
+    async fn test_mock_display_grid_matches_lcd() {
+        let mut display = MockDisplay::with_size(4, 20);
+        display.show_message("Hot chocolate is on its way\nnext").await.unwrap();
+        display.set_cursor(3, 18).await.unwrap();
+        display.write("OK!").await.unwrap();
+
+        assert_eq!(display.snapshot(), [
+            "Hot chocolate is on ",
+            "its way             ",
+            "next                ",
+            "                  OK",
+        ]);
+        assert!(display.set_cursor(4, 0).await.is_err());
+
+        display.clear().await.unwrap();
+        assert_eq!(display.snapshot()[0], " ".repeat(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mock_display_records_frames() {
+        let mut display = MockDisplay::new();
+        display.show_message("Heating").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        display.show_message("Adding milk").await.unwrap();
+        display.show_message("Adding milk").await.unwrap();
+
+        let frames = display.frames();
+        assert_eq!(display.screens(), ["Heating", "Adding milk"]);
+        assert_eq!(frames[1].at - frames[0].at, std::time::Duration::from_secs(2));
+    }
+
     #[tokio::test]
     async fn test_mock_emergency_stop() {
         let mut estop = MockEmergencyStop::new();
@@ -283,4 +876,16 @@ async fn test_mock_emergency_stop() {
         estop.release();
         assert!(!estop.is_pressed().await);
     }
+
+    #[tokio::test]
+    async fn test_mock_emergency_stop_callback() {
+        let mut estop = MockEmergencyStop::new();
+        let fired = Arc::new(Mutex::new(0));
+
+        let counter = Arc::clone(&fired);
+        estop.on_press(Box::new(move || *counter.lock().unwrap() += 1));
+
+        estop.press();
+        assert_eq!(*fired.lock().unwrap(), 1);
+    }
 }
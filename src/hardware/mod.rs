@@ -3,22 +3,55 @@
 //! Provides trait-based interfaces for hardware components to enable
 //! testing without physical hardware and future platform portability.
 
+pub mod factory;
+
+#[cfg(feature = "rpi")]
 pub mod pump;
+#[cfg(feature = "rpi")]
 pub mod sensor;
+#[cfg(any(feature = "rpi", test))]
 pub mod display;
+#[cfg(any(feature = "rpi", test))]
+pub mod font;
+pub mod glyphs;
+#[cfg(any(feature = "rpi", test))]
+pub mod i2c;
+#[cfg(any(feature = "rpi", test))]
+pub mod ssd1306;
+#[cfg(feature = "rpi")]
+pub mod heater;
+#[cfg(feature = "rpi")]
+pub mod estop;
+#[cfg(feature = "rpi")]
+pub mod button;
+#[cfg(feature = "rpi")]
+pub mod led;
+#[cfg(feature = "rpi")]
+pub mod adc;
+#[cfg(feature = "rpi")]
+pub mod level;
+
+#[cfg(any(feature = "sim", test))]
 pub mod mock;
+#[cfg(feature = "tui")]
+pub mod terminal;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use crate::hardware::glyphs::{progress_line, Glyph};
+use tracing::debug;
 
 /// Trait for controllable pump devices
+///
+/// Methods take `&self` so a pump can be stopped or queried while a
+/// dispense is running; implementations synchronise internally.
 #[async_trait]
 pub trait Pump: Send + Sync {
     /// Activate pump for specified duration in milliseconds
-    async fn dispense(&mut self, duration_ms: u64) -> Result<()>;
+    async fn dispense(&self, duration_ms: u64) -> Result<()>;
 
     /// Stop pump immediately
-    async fn stop(&mut self) -> Result<()>;
+    async fn stop(&self) -> Result<()>;
 
     /// Check if pump is currently running
     fn is_running(&self) -> bool;
@@ -27,7 +60,8 @@ pub trait Pump: Send + Sync {
     fn total_runtime_ms(&self) -> u64;
 
     /// Reset runtime counter
-    fn reset_counter(&mut self);
+    #[allow(dead_code)]
+    fn reset_counter(&self);
 }
 
 /// Trait for temperature sensors
@@ -40,6 +74,21 @@ pub trait TemperatureSensor: Send + Sync {
     async fn is_healthy(&self) -> bool;
 }
 
+/// Trait for milk heater elements (e.g. SSR-switched heating pad)
+#[async_trait]
+pub trait Heater: Send + Sync {
+    /// Set heater output as a fraction of full power (0.0 - 1.0)
+    async fn set_power(&mut self, power: f32) -> Result<()>;
+
+    /// Turn heater off immediately
+    async fn off(&mut self) -> Result<()> {
+        self.set_power(0.0).await
+    }
+
+    /// Get current output power (0.0 - 1.0)
+    fn power(&self) -> f32;
+}
+
 /// Trait for LCD display
 #[async_trait]
 pub trait Display: Send + Sync {
@@ -50,15 +99,44 @@ pub trait Display: Send + Sync {
     async fn clear(&mut self) -> Result<()>;
 
     /// Set cursor position (row, column)
+    #[cfg_attr(not(any(feature = "rpi", test)), allow(dead_code))]
     async fn set_cursor(&mut self, row: u8, col: u8) -> Result<()>;
 
+    /// Switch the backlight on or off, on displays that have one
+    #[cfg_attr(not(test), allow(dead_code))]
+    async fn set_backlight(&mut self, _on: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Upload custom characters so their symbols can be written
+    ///
+    /// Displays that can already draw every symbol need nothing.
+    async fn load_glyphs(&mut self, _glyphs: &[Glyph]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Characters per row
+    fn columns(&self) -> u8 {
+        16
+    }
+
+    /// Show a label above a progress bar and percentage
+    async fn show_progress(&mut self, label: &str, fraction: f32) -> Result<()> {
+        let line = progress_line(fraction, self.columns() as usize);
+        self.show_message(&format!("{}\n{}", label, line)).await
+    }
+
     /// Display message with automatic formatting
     async fn show_message(&mut self, message: &str) -> Result<()> {
+        debug!(telemetry = "display", text = message, "Display: {}", message.replace('\n', " | "));
         self.clear().await?;
         self.write(message).await
     }
 }
 
+/// Callback invoked when the emergency stop is pressed
+pub type EmergencyStopCallback = Box<dyn Fn() + Send + Sync + 'static>;
+
 /// Trait for emergency stop button
 #[async_trait]
 pub trait EmergencyStop: Send + Sync {
@@ -66,9 +144,38 @@ pub trait EmergencyStop: Send + Sync {
     async fn is_pressed(&self) -> bool;
 
     /// Register callback for emergency stop events
-    fn on_press<F>(&mut self, callback: F)
-    where
-        F: Fn() + Send + 'static;
+    fn on_press(&mut self, callback: EmergencyStopCallback);
+}
+
+/// Callback invoked when an order button is pressed
+#[cfg(any(feature = "rpi", test))]
+pub type ButtonCallback = Box<dyn Fn() + Send + Sync + 'static>;
+
+/// Trait for the push buttons customers order with
+#[cfg(any(feature = "rpi", test))]
+pub trait OrderButton: Send {
+    /// Register callback for presses
+    fn on_press(&mut self, callback: ButtonCallback);
+}
+
+/// Trait for analog-to-digital converters (supply voltage monitoring)
+#[async_trait]
+pub trait Adc: Send + Sync {
+    /// Read the voltage on a channel, in volts at the ADC input
+    async fn read_voltage(&mut self, channel: u8) -> Result<f32>;
+}
+
+/// Trait for ingredient reservoir level sensors (float switches, ultrasonic rangers)
+#[async_trait]
+pub trait LevelSensor: Send + Sync {
+    /// Fraction of the reservoir still full (0.0 - 1.0), or `None` if the
+    /// sensor cannot measure it (e.g. a float switch)
+    async fn read_level(&mut self) -> Result<Option<f32>>;
+
+    /// Whether the reservoir is down to its low mark
+    async fn is_low(&mut self) -> Result<bool> {
+        Ok(self.read_level().await?.is_some_and(|level| level <= 0.0))
+    }
 }
 
 /// Hardware abstraction for status LED
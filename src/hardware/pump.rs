@@ -3,21 +3,25 @@
 use crate::hardware::Pump;
 use anyhow::{Result, Context};
 use async_trait::async_trait;
+use std::sync::{Mutex, MutexGuard};
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use tracing::{info, warn};
 
-#[cfg(target_os = "linux")]
 use rppal::gpio::{Gpio, OutputPin};
 
 /// GPIO-controlled peristaltic pump
+///
+/// All state sits behind locks held only briefly, so the pump can be stopped
+/// or queried while a dispense is in progress.
 pub struct GpioPump {
-    #[cfg(target_os = "linux")]
-    pin: OutputPin,
-    #[cfg(not(target_os = "linux"))]
-    pin_number: u8,
-
+    pin: Mutex<OutputPin>,
     name: String,
+    state: Mutex<PumpState>,
+}
+
+/// Run state and runtime accounting
+struct PumpState {
     is_running: bool,
     total_runtime_ms: u64,
     last_start: Option<Instant>,
@@ -26,7 +30,6 @@ pub struct GpioPump {
 impl GpioPump {
     /// Create new GPIO pump controller
     pub fn new(pin_number: u8, name: &str) -> Result<Self> {
-        #[cfg(target_os = "linux")]
         let pin = Gpio::new()
             .context("Failed to initialize GPIO")?
             .get(pin_number)
@@ -36,94 +39,113 @@ pub fn new(pin_number: u8, name: &str) -> Result<Self> {
         info!("Initialized {} pump on GPIO pin {}", name, pin_number);
 
         Ok(Self {
-            #[cfg(target_os = "linux")]
-            pin,
-            #[cfg(not(target_os = "linux"))]
-            pin_number,
-
+            pin: Mutex::new(pin),
             name: name.to_string(),
-            is_running: false,
-            total_runtime_ms: 0,
-            last_start: None,
+            state: Mutex::new(PumpState {
+                is_running: false,
+                total_runtime_ms: 0,
+                last_start: None,
+            }),
         })
     }
 
-    /// Internal helper to activate GPIO pin
-    fn activate(&mut self) -> Result<()> {
-        #[cfg(target_os = "linux")]
-        self.pin.set_high();
-
-        #[cfg(not(target_os = "linux"))]
-        info!("[MOCK] Setting pin {} HIGH for {} pump", self.pin_number, self.name);
+    fn state(&self) -> MutexGuard<'_, PumpState> {
+        // A panic mid-update leaves the counters usable; never refuse to stop
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
 
+    /// Internal helper to activate GPIO pin
+    fn activate(&self) -> Result<()> {
+        self.pin.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).set_high();
         Ok(())
     }
 
     /// Internal helper to deactivate GPIO pin
-    fn deactivate(&mut self) -> Result<()> {
-        #[cfg(target_os = "linux")]
-        self.pin.set_low();
-
-        #[cfg(not(target_os = "linux"))]
-        info!("[MOCK] Setting pin {} LOW for {} pump", self.pin_number, self.name);
-
+    fn deactivate(&self) -> Result<()> {
+        self.pin.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).set_low();
         Ok(())
     }
-}
 
-#[async_trait]
-impl Pump for GpioPump {
-    async fn dispense(&mut self, duration_ms: u64) -> Result<()> {
-        if self.is_running {
-            warn!("{} pump already running", self.name);
+    /// Switch the pump off and update the runtime counter
+    fn halt(&self) -> Result<()> {
+        let mut state = self.state();
+        if !state.is_running {
             return Ok(());
         }
 
-        info!("{} pump dispensing for {}ms", self.name, duration_ms);
-
-        self.is_running = true;
-        self.last_start = Some(Instant::now());
-        self.activate()?;
+        self.deactivate()?;
 
-        // Run pump for specified duration
-        sleep(Duration::from_millis(duration_ms)).await;
+        // Update runtime counter
+        if let Some(start) = state.last_start {
+            let elapsed = start.elapsed().as_millis() as u64;
+            state.total_runtime_ms += elapsed;
+            info!(telemetry = "pump_stop", pump = %self.name, ran_ms = elapsed,
+                  "{} pump stopped after {}ms (total: {}ms)", self.name, elapsed, state.total_runtime_ms);
+        }
 
-        self.stop().await?;
+        state.is_running = false;
+        state.last_start = None;
         Ok(())
     }
+}
 
-    async fn stop(&mut self) -> Result<()> {
-        if !self.is_running {
-            return Ok(());
+/// Halts the pump when dropped, so a cancelled dispense never leaves it running
+struct HaltOnDrop<'a>(&'a GpioPump);
+
+impl Drop for HaltOnDrop<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.halt() {
+            warn!("Failed to halt {} pump: {:?}", self.0.name, e);
         }
+    }
+}
 
-        self.deactivate()?;
+#[async_trait]
+impl Pump for GpioPump {
+    async fn dispense(&self, duration_ms: u64) -> Result<()> {
+        {
+            let mut state = self.state();
+            if state.is_running {
+                warn!("{} pump already running", self.name);
+                return Ok(());
+            }
+
+            info!(telemetry = "pump_start", pump = %self.name, duration_ms,
+                  "{} pump dispensing for {}ms", self.name, duration_ms);
+
+            state.is_running = true;
+            state.last_start = Some(Instant::now());
+            self.activate()?;
+        } // Release lock so the pump can be stopped or queried while running
+
+        // If this future is dropped mid-run (e.g. a safety abort), the guard
+        // switches the pump off instead of leaving the pin high
+        let guard = HaltOnDrop(self);
 
-        // Update runtime counter
-        if let Some(start) = self.last_start {
-            let elapsed = start.elapsed().as_millis() as u64;
-            self.total_runtime_ms += elapsed;
-            info!("{} pump stopped after {}ms (total: {}ms)",
-                  self.name, elapsed, self.total_runtime_ms);
-        }
+        // Run pump for specified duration
+        sleep(Duration::from_millis(duration_ms)).await;
 
-        self.is_running = false;
-        self.last_start = None;
+        drop(guard);
         Ok(())
     }
 
+    async fn stop(&self) -> Result<()> {
+        self.halt()
+    }
+
     fn is_running(&self) -> bool {
-        self.is_running
+        self.state().is_running
     }
 
     fn total_runtime_ms(&self) -> u64 {
-        self.total_runtime_ms
+        self.state().total_runtime_ms
     }
 
-    fn reset_counter(&mut self) {
+    fn reset_counter(&self) {
+        let mut state = self.state();
         info!("{} pump runtime counter reset (was {}ms)",
-              self.name, self.total_runtime_ms);
-        self.total_runtime_ms = 0;
+              self.name, state.total_runtime_ms);
+        state.total_runtime_ms = 0;
     }
 }
 
@@ -132,8 +154,9 @@ mod tests {
     use super::*;
 
     #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
     async fn test_pump_dispense() {
-        let mut pump = GpioPump::new(17, "test").unwrap();
+        let pump = GpioPump::new(17, "test").unwrap();
         assert!(!pump.is_running());
 
         pump.dispense(100).await.unwrap();
@@ -143,8 +166,9 @@ async fn test_pump_dispense() {
     }
 
     #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
     async fn test_pump_counter_reset() {
-        let mut pump = GpioPump::new(17, "test").unwrap();
+        let pump = GpioPump::new(17, "test").unwrap();
 
         pump.dispense(50).await.unwrap();
         assert!(pump.total_runtime_ms() > 0);
@@ -152,4 +176,33 @@ async fn test_pump_counter_reset() {
         pump.reset_counter();
         assert_eq!(pump.total_runtime_ms(), 0);
     }
+
+    #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
+    async fn test_cancelled_dispense_halts_pump() {
+        let pump = GpioPump::new(17, "test").unwrap();
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(20), pump.dispense(10_000)).await;
+        assert!(cancelled.is_err());
+
+        assert!(!pump.is_running());
+        assert!(pump.total_runtime_ms() < 10_000);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Raspberry Pi GPIO"]
+    async fn test_stop_while_dispensing() {
+        let pump = GpioPump::new(17, "test").unwrap();
+
+        let stopper = async {
+            sleep(Duration::from_millis(20)).await;
+            assert!(pump.is_running());
+            pump.stop().await.unwrap();
+            assert!(!pump.is_running());
+        };
+        let (result, _) = tokio::join!(pump.dispense(100), stopper);
+
+        result.unwrap();
+        assert!(pump.total_runtime_ms() < 100);
+    }
 }
@@ -1,21 +1,19 @@
 //! Temperature sensor implementation using I2C
 
+use crate::error::{Device, HotChocolaBotError};
 use crate::hardware::TemperatureSensor;
 use anyhow::{Result, Context};
 use async_trait::async_trait;
+use std::sync::Mutex;
 use tracing::{info, warn};
 
-#[cfg(target_os = "linux")]
 use rppal::i2c::I2c;
 
 /// I2C temperature sensor (e.g., TMP102, DS18B20)
+///
+/// rppal's bus is not `Sync`, so it sits behind a lock like `GpioPump`'s pin.
 pub struct I2cTemperatureSensor {
-    #[cfg(target_os = "linux")]
-    i2c: I2c,
-
-    #[cfg(not(target_os = "linux"))]
-    address: u8,
-
+    i2c: Mutex<I2c>,
     last_reading: Option<f32>,
     consecutive_failures: u32,
 }
@@ -23,32 +21,24 @@ pub struct I2cTemperatureSensor {
 impl I2cTemperatureSensor {
     /// Create new I2C temperature sensor
     pub fn new(address: u8) -> Result<Self> {
-        #[cfg(target_os = "linux")]
         let mut i2c = I2c::new().context("Failed to initialize I2C")?;
-
-        #[cfg(target_os = "linux")]
         i2c.set_slave_address(address as u16)
             .context(format!("Failed to set I2C address 0x{:02X}", address))?;
 
         info!("Initialized temperature sensor at I2C address 0x{:02X}", address);
 
         Ok(Self {
-            #[cfg(target_os = "linux")]
-            i2c,
-            #[cfg(not(target_os = "linux"))]
-            address,
-
+            i2c: Mutex::new(i2c),
             last_reading: None,
             consecutive_failures: 0,
         })
     }
 
     /// Read raw temperature from sensor
-    #[cfg(target_os = "linux")]
     async fn read_raw(&mut self) -> Result<f32> {
         // TMP102 register layout: 2-byte temperature reading
         let mut buf = [0u8; 2];
-        self.i2c.read(&mut buf)
+        self.i2c.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).read(&mut buf)
             .context("Failed to read from temperature sensor")?;
 
         // Convert to temperature (12-bit resolution, 0.0625°C per LSB)
@@ -57,15 +47,6 @@ async fn read_raw(&mut self) -> Result<f32> {
 
         Ok(temp)
     }
-
-    #[cfg(not(target_os = "linux"))]
-    async fn read_raw(&mut self) -> Result<f32> {
-        // Mock reading: simulate temperature around 20-25°C
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let temp = rng.gen_range(20.0..25.0);
-        Ok(temp)
-    }
 }
 
 #[async_trait]
@@ -83,8 +64,10 @@ async fn read_temperature(&mut self) -> Result<f32> {
                       self.consecutive_failures, e);
 
                 if self.consecutive_failures > 5 {
-                    Err(anyhow::anyhow!("Temperature sensor failed {} times",
-                                       self.consecutive_failures))
+                    Err(HotChocolaBotError::hardware(
+                        Device::TemperatureSensor,
+                        format!("failed {} times in a row", self.consecutive_failures),
+                    ).into())
                 } else if let Some(last) = self.last_reading {
                     warn!("Using last known temperature: {}°C", last);
                     Ok(last)
@@ -105,6 +88,7 @@ mod tests {
     use super::*;
 
     #[tokio::test]
+    #[ignore = "requires a TMP102 on the I2C bus"]
     async fn test_sensor_reading() {
         let mut sensor = I2cTemperatureSensor::new(0x48).unwrap();
         let temp = sensor.read_temperature().await.unwrap();
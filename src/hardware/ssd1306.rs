@@ -0,0 +1,381 @@
+//! SSD1306 128x64 OLED over I2C
+//!
+//! The panel is driven from a framebuffer in memory: text and graphics are
+//! drawn into it and the whole buffer is sent after each change. Memory is
+//! laid out in eight pages of 8-pixel-high columns, least significant bit
+//! at the top, which is also how the 5x7 font is stored.
+
+use crate::error::{Device, DeviceContext, HotChocolaBotError};
+use crate::hardware::font::{self, CELL_HEIGHT, CELL_WIDTH};
+use crate::hardware::i2c::I2cBus;
+use crate::hardware::Display;
+use anyhow::Result;
+use async_trait::async_trait;
+use tracing::debug;
+
+#[cfg(feature = "rpi")]
+use anyhow::Context;
+#[cfg(feature = "rpi")]
+use rppal::i2c::I2c;
+#[cfg(feature = "rpi")]
+use std::sync::Mutex;
+#[cfg(feature = "rpi")]
+use tracing::info;
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+const PAGES: usize = HEIGHT / 8;
+
+/// Text grid in 6x8 character cells
+const TEXT_ROWS: u8 = (HEIGHT / CELL_HEIGHT) as u8;
+const TEXT_COLS: u8 = (WIDTH / CELL_WIDTH) as u8;
+
+// Control bytes starting each I2C write
+const COMMAND: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+/// Data bytes per I2C write, within what small I2C controllers buffer
+const DATA_CHUNK: usize = 16;
+
+/// Power-on configuration for a 128x64 panel with the internal charge pump
+const INIT: &[&[u8]] = &[
+    &[0xAE],       // display off
+    &[0xD5, 0x80], // clock divide
+    &[0xA8, 0x3F], // multiplex: 64 rows
+    &[0xD3, 0x00], // no display offset
+    &[0x40],       // start line 0
+    &[0x8D, 0x14], // charge pump on
+    &[0x20, 0x00], // horizontal addressing
+    &[0xA1],       // column 127 at the left edge
+    &[0xC8],       // scan from the bottom page up
+    &[0xDA, 0x12], // alternate COM pins
+    &[0x81, 0xCF], // contrast
+    &[0xD9, 0xF1], // precharge
+    &[0xDB, 0x40], // VCOMH deselect level
+    &[0xA4],       // show RAM contents
+    &[0xA6],       // not inverted
+    &[0xAF],       // display on
+];
+
+/// SSD1306 OLED display showing text in a 21x8 grid, plus graphics
+pub struct Ssd1306Display<B: I2cBus> {
+    bus: B,
+    /// Page-major pixels: byte `page * WIDTH + x` holds rows `page * 8..page * 8 + 8`
+    buffer: [u8; WIDTH * PAGES],
+    cursor_row: u8,
+    cursor_col: u8,
+}
+
+#[cfg(feature = "rpi")]
+impl Ssd1306Display<Mutex<I2c>> {
+    /// Create new OLED display at the given I2C address
+    pub fn new(address: u8) -> Result<Self> {
+        let mut i2c = I2c::new().context("Failed to initialize I2C")?;
+        i2c.set_slave_address(address as u16)
+            .context(format!("Failed to set I2C address 0x{:02X}", address))?;
+
+        let display = Self::with_bus(Mutex::new(i2c))?;
+        info!("Initialized {}x{} OLED at I2C address 0x{:02X}", WIDTH, HEIGHT, address);
+
+        Ok(display)
+    }
+}
+
+impl<B: I2cBus> Ssd1306Display<B> {
+    /// Initialise the panel on `bus` and blank it
+    pub fn with_bus(bus: B) -> Result<Self> {
+        let mut display = Self {
+            bus,
+            buffer: [0; WIDTH * PAGES],
+            cursor_row: 0,
+            cursor_col: 0,
+        };
+
+        for command in INIT {
+            display.send_command(command)?;
+        }
+        display.flush()?;
+
+        Ok(display)
+    }
+
+    fn send_command(&mut self, command: &[u8]) -> Result<()> {
+        let mut bytes = vec![COMMAND];
+        bytes.extend_from_slice(command);
+        self.bus.write(&bytes).device(Device::Display)
+    }
+
+    /// Send the whole framebuffer to the panel
+    pub fn flush(&mut self) -> Result<()> {
+        self.send_command(&[0x21, 0, (WIDTH - 1) as u8])?; // column range
+        self.send_command(&[0x22, 0, (PAGES - 1) as u8])?; // page range
+
+        for chunk in self.buffer.chunks(DATA_CHUNK) {
+            let mut bytes = Vec::with_capacity(chunk.len() + 1);
+            bytes.push(DATA);
+            bytes.extend_from_slice(chunk);
+            self.bus.write(&bytes).device(Device::Display)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clear the framebuffer and home the cursor, without sending anything
+    fn blank(&mut self) {
+        self.buffer = [0; WIDTH * PAGES];
+        self.cursor_row = 0;
+        self.cursor_col = 0;
+    }
+
+    fn draw_char(&mut self, c: char) {
+        let start = self.cursor_row as usize * WIDTH + self.cursor_col as usize * CELL_WIDTH;
+        let cell = &mut self.buffer[start..start + CELL_WIDTH];
+        cell[..5].copy_from_slice(&font::columns(c));
+        cell[5] = 0;
+    }
+
+    /// Draw text at the cursor: `\n` and long rows move to the next row,
+    /// and anything past the last row is dropped
+    fn write_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                if !self.next_row() {
+                    break;
+                }
+                continue;
+            }
+
+            if self.cursor_col >= TEXT_COLS && !self.next_row() {
+                break;
+            }
+
+            self.draw_char(c);
+            self.cursor_col += 1;
+        }
+    }
+
+    fn next_row(&mut self) -> bool {
+        if self.cursor_row + 1 >= TEXT_ROWS {
+            return false;
+        }
+        self.cursor_row += 1;
+        self.cursor_col = 0;
+        true
+    }
+}
+
+/// Pixel graphics; nothing on the bot plots a graph yet
+#[cfg_attr(not(test), allow(dead_code))]
+impl<B: I2cBus> Ssd1306Display<B> {
+    /// Light or clear a pixel; positions off the panel are ignored
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+
+        let byte = &mut self.buffer[y / 8 * WIDTH + x];
+        if on {
+            *byte |= 1 << (y % 8);
+        } else {
+            *byte &= !(1 << (y % 8));
+        }
+    }
+
+    #[cfg(test)]
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        x < WIDTH && y < HEIGHT && self.buffer[y / 8 * WIDTH + x] & (1 << (y % 8)) != 0
+    }
+
+    /// Straight line between two points, inclusive
+    pub fn draw_line(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+        let (x1, y1) = (to.0 as i32, to.1 as i32);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut error = dx + dy;
+
+        // Bresenham: step whichever axis keeps the line closest to true
+        loop {
+            self.set_pixel(x as usize, y as usize, true);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Outline of a rectangle with its top-left corner at (x, y)
+    pub fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        self.draw_line((x, y), (right, y));
+        self.draw_line((x, bottom), (right, bottom));
+        self.draw_line((x, y), (x, bottom));
+        self.draw_line((right, y), (right, bottom));
+    }
+
+    /// Plot readings as a line graph inside a frame from row `top` to the bottom
+    ///
+    /// The newest readings are kept if there are more than the graph is wide.
+    /// Readings are scaled so `range.0` sits on the bottom edge and
+    /// `range.1` on the top edge.
+    pub fn draw_graph(&mut self, readings: &[f32], range: (f32, f32), top: usize) {
+        let height = HEIGHT.saturating_sub(top);
+        if height < 3 {
+            return;
+        }
+        self.draw_rect(0, top, WIDTH, height);
+
+        // Inside the frame
+        let (inner_width, inner_height) = (WIDTH - 2, height - 2);
+        let readings = &readings[readings.len().saturating_sub(inner_width)..];
+        let span = (range.1 - range.0).max(f32::EPSILON);
+        let point = |i: usize, reading: f32| {
+            let level = ((reading - range.0) / span).clamp(0.0, 1.0);
+            let y = top + inner_height - (level * (inner_height - 1) as f32).round() as usize;
+            (1 + i, y)
+        };
+
+        for (i, pair) in readings.windows(2).enumerate() {
+            self.draw_line(point(i, pair[0]), point(i + 1, pair[1]));
+        }
+        if let [reading] = readings {
+            let (x, y) = point(0, *reading);
+            self.set_pixel(x, y, true);
+        }
+    }
+
+    /// Show a label above a graph of readings
+    pub async fn show_graph(&mut self, label: &str, readings: &[f32], range: (f32, f32)) -> Result<()> {
+        self.blank();
+        self.write_text(label);
+        self.draw_graph(readings, range, CELL_HEIGHT + 2);
+        self.flush()
+    }
+}
+
+#[async_trait]
+impl<B: I2cBus> Display for Ssd1306Display<B> {
+    async fn write(&mut self, text: &str) -> Result<()> {
+        self.write_text(text);
+        self.flush()
+    }
+
+    async fn clear(&mut self) -> Result<()> {
+        self.blank();
+        self.flush()
+    }
+
+    async fn set_cursor(&mut self, row: u8, col: u8) -> Result<()> {
+        if row >= TEXT_ROWS || col >= TEXT_COLS {
+            return Err(HotChocolaBotError::hardware(
+                Device::Display,
+                format!("cursor position out of bounds: ({}, {})", row, col),
+            ).into());
+        }
+
+        self.cursor_row = row;
+        self.cursor_col = col;
+        Ok(())
+    }
+
+    /// Clear and write with a single transfer to the panel
+    async fn show_message(&mut self, message: &str) -> Result<()> {
+        debug!(telemetry = "display", text = message, "OLED: {}", message.replace('\n', " | "));
+        self.blank();
+        self.write_text(message);
+        self.flush()
+    }
+
+    async fn set_backlight(&mut self, on: bool) -> Result<()> {
+        // An OLED has no backlight; switch the whole panel instead
+        self.send_command(&[if on { 0xAF } else { 0xAE }])
+    }
+
+    fn columns(&self) -> u8 {
+        TEXT_COLS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::mock::{I2cOp, MockI2cBus};
+
+    /// Commands and the last complete framebuffer sent over the bus
+    fn decode(bus: &MockI2cBus) -> (Vec<Vec<u8>>, Vec<u8>) {
+        let mut commands = Vec::new();
+        let mut data = Vec::new();
+
+        for op in bus.ops() {
+            if let I2cOp::Write(bytes) = op {
+                match bytes[0] {
+                    COMMAND => commands.push(bytes[1..].to_vec()),
+                    _ => data.extend_from_slice(&bytes[1..]),
+                }
+            }
+        }
+
+        let frame = data[data.len() - WIDTH * PAGES..].to_vec();
+        (commands, frame)
+    }
+
+    #[test]
+    fn test_init_sequence_blanks_panel() {
+        let bus = MockI2cBus::new();
+        Ssd1306Display::with_bus(bus.clone()).unwrap();
+
+        let (commands, frame) = decode(&bus);
+        assert_eq!(commands[..INIT.len()], *INIT);
+        assert_eq!(commands[INIT.len()..], [vec![0x21, 0, 127], vec![0x22, 0, 7]]);
+        assert!(frame.iter().all(|&byte| byte == 0));
+        assert!(bus.ops().iter().all(|op| matches!(op, I2cOp::Write(bytes) if bytes.len() <= DATA_CHUNK + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_text_drawn_with_font() {
+        let bus = MockI2cBus::new();
+        let mut display = Ssd1306Display::with_bus(bus.clone()).unwrap();
+        bus.clear();
+
+        display.show_message("Hi\nA").await.unwrap();
+
+        let (_, frame) = decode(&bus);
+        assert_eq!(frame[..5], font::columns('H'));
+        assert_eq!(frame[6..11], font::columns('i'));
+        assert_eq!(frame[WIDTH..WIDTH + 5], font::columns('A'));
+        assert_eq!(display.columns(), 21);
+        assert!(display.set_cursor(8, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_graph_rises_with_readings() {
+        let mut display = Ssd1306Display::with_bus(MockI2cBus::new()).unwrap();
+
+        display.show_graph("Milk", &[20.0, 40.0, 60.0], (20.0, 60.0)).await.unwrap();
+
+        // Frame from row 10; inside it the first reading is on the bottom and the last on top
+        assert!(display.pixel(0, 10) && display.pixel(127, 63));
+        assert!(display.pixel(1, 62));
+        assert!(display.pixel(3, 11));
+        assert!(!display.pixel(3, 62));
+    }
+
+    #[test]
+    fn test_line_covers_both_ends() {
+        let mut display = Ssd1306Display::with_bus(MockI2cBus::new()).unwrap();
+        display.draw_line((10, 5), (2, 1));
+
+        assert!(display.pixel(10, 5) && display.pixel(2, 1) && display.pixel(6, 3));
+    }
+}
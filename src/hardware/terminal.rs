@@ -0,0 +1,365 @@
+//! Terminal dashboard standing in for the rig's front panel
+//!
+//! With `display = "terminal"` the simulated bot draws its LCD in the
+//! terminal next to the pumps, temperature and safety state, and takes
+//! orders and emergency stops from the keyboard, so a workshop can demo it
+//! on a projector with no hardware attached. Log lines go to a pane at the
+//! bottom instead of being interleaved with the drawing.
+
+use crate::config::{BotConfig, Ingredient};
+use crate::control::orders::{OrderQueue, OrderRequest};
+use crate::control::DispenseController;
+use crate::hardware::factory::ControllerHardware;
+use crate::hardware::mock::{MockDisplay, MockEmergencyStop};
+use crate::safety::SafetyStates;
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::{interval, Duration};
+use tracing::warn;
+
+/// How often the dashboard is redrawn and the keyboard polled
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Log lines kept for the log pane
+const LOG_LINES: usize = 200;
+
+/// Log lines captured for the dashboard's log pane
+#[derive(Clone, Default)]
+pub struct LogPane {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogPane {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writer for `tracing_subscriber::fmt().with_writer(...)`
+    pub fn writer(&self) -> LogPaneWriter {
+        LogPaneWriter { pane: self.clone(), partial: Vec::new() }
+    }
+
+    /// The newest `count` lines, oldest first
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect()
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// Splits formatted log output into lines for a `LogPane`
+pub struct LogPaneWriter {
+    pane: LogPane,
+    partial: Vec<u8>,
+}
+
+impl Write for LogPaneWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            self.pane.push(String::from_utf8_lossy(&line[..end]).into_owned());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogPaneWriter {
+    fn drop(&mut self) {
+        if !self.partial.is_empty() {
+            self.pane.push(String::from_utf8_lossy(&self.partial).into_owned());
+        }
+    }
+}
+
+/// Everything the dashboard shows, gathered once per refresh
+pub struct DashboardView {
+    pub lcd: Vec<String>,
+    pub safety: SafetyStates,
+    pub temperature: Option<f32>,
+    pub heater_power: f32,
+    pub pumps: Vec<(Ingredient, bool)>,
+    pub emergency_stop_pressed: bool,
+    pub waiting: usize,
+    pub recipes: Vec<String>,
+    pub log: Vec<String>,
+}
+
+/// Terminal front panel for a simulated rig
+pub struct Dashboard {
+    display: MockDisplay,
+    emergency_stop: MockEmergencyStop,
+    recipes: Vec<String>,
+    log: LogPane,
+}
+
+impl Dashboard {
+    /// Put the dashboard's LCD and emergency stop into simulated hardware
+    pub fn attach(hardware: &mut ControllerHardware, config: &BotConfig, log: LogPane) -> Self {
+        let display = MockDisplay::with_size(config.hardware.lcd_rows, config.hardware.lcd_cols);
+        let emergency_stop = MockEmergencyStop::new();
+        hardware.display = Box::new(display.clone());
+        hardware.emergency_stop = Some(Box::new(emergency_stop.clone()));
+
+        Self {
+            display,
+            emergency_stop,
+            recipes: config.recipes.names().map(str::to_string).collect(),
+            log,
+        }
+    }
+
+    /// Draw and take keys until the operator quits, then close the order queue
+    pub async fn run(
+        &mut self,
+        controller: &DispenseController,
+        safety: watch::Receiver<SafetyStates>,
+        orders: &OrderQueue,
+    ) -> Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.run_on(&mut terminal, controller, safety, orders).await;
+        ratatui::restore();
+
+        orders.close();
+        result
+    }
+
+    async fn run_on(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        controller: &DispenseController,
+        safety: watch::Receiver<SafetyStates>,
+        orders: &OrderQueue,
+    ) -> Result<()> {
+        let mut ticks = interval(REFRESH);
+
+        loop {
+            ticks.tick().await;
+
+            let view = self.view(controller, &safety, orders).await;
+            terminal.draw(|frame| draw(frame, &view))?;
+
+            while event::poll(Duration::ZERO)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key, orders) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Act on a key press; false to quit
+    fn handle_key(&mut self, key: KeyEvent, orders: &OrderQueue) -> bool {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('e') | KeyCode::Char(' ') => self.emergency_stop.press(),
+            KeyCode::Char('r') => self.emergency_stop.release(),
+            KeyCode::Char('y') => self.answer(orders, true),
+            KeyCode::Char('n') => self.answer(orders, false),
+            KeyCode::Char(digit @ '1'..='9') => {
+                let index = digit as usize - '1' as usize;
+                if let Some(recipe) = self.recipes.get(index) {
+                    if let Err(e) = orders.submit(OrderRequest::new(recipe.as_str())) {
+                        warn!("Order not taken: {:#}", e);
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn answer(&self, orders: &OrderQueue, confirmed: bool) {
+        if let Err(e) = orders.answer(confirmed) {
+            warn!("{:#}", e);
+        }
+    }
+
+    async fn view(
+        &self,
+        controller: &DispenseController,
+        safety: &watch::Receiver<SafetyStates>,
+        orders: &OrderQueue,
+    ) -> DashboardView {
+        DashboardView {
+            lcd: self.display.snapshot(),
+            safety: *safety.borrow(),
+            temperature: controller.read_temperature().await.ok(),
+            heater_power: controller.heater_power().await,
+            pumps: Ingredient::ALL.into_iter()
+                .map(|ingredient| (ingredient, controller.pump_running(ingredient)))
+                .collect(),
+            emergency_stop_pressed: controller.emergency_stop_pressed().await.unwrap_or(false),
+            waiting: orders.waiting(),
+            recipes: self.recipes.clone(),
+            log: self.log.tail(LOG_LINES),
+        }
+    }
+}
+
+fn safety_style(state: SafetyStates) -> Style {
+    let color = match state {
+        SafetyStates::Safe => Color::Green,
+        SafetyStates::Operating => Color::Yellow,
+        SafetyStates::Anomaly => Color::Magenta,
+        SafetyStates::Unsafe => Color::Red,
+        SafetyStates::Uninitialized | SafetyStates::Initialized => Color::Gray,
+    };
+    Style::default().fg(color).add_modifier(Modifier::BOLD)
+}
+
+/// Draw one frame of the dashboard
+pub fn draw(frame: &mut Frame, view: &DashboardView) {
+    let lcd_width = view.lcd.first().map_or(16, |row| row.chars().count()) as u16 + 2;
+    let lcd_height = view.lcd.len() as u16 + 2;
+
+    let [top, keys, log] = Layout::vertical([
+        Constraint::Length(lcd_height.max(6)),
+        Constraint::Length(3),
+        Constraint::Min(3),
+    ])
+    .areas(frame.area());
+    let [lcd_area, status_area] = Layout::horizontal([Constraint::Length(lcd_width), Constraint::Min(20)])
+        .areas(top);
+
+    // The LCD, drawn the way the physical panel looks
+    let lcd_style = Style::default().fg(Color::Black).bg(Color::LightGreen);
+    let lcd = Paragraph::new(view.lcd.iter().map(|row| Line::from(row.as_str())).collect::<Vec<_>>())
+        .style(lcd_style)
+        .block(Block::default().borders(Borders::ALL).title("LCD"));
+    frame.render_widget(lcd, lcd_area);
+
+    let temperature = match view.temperature {
+        Some(temp) => format!("{:.1}°C", temp),
+        None => "sensor fault".to_string(),
+    };
+    let mut pumps = vec![Span::raw("Pumps: ")];
+    for (ingredient, running) in &view.pumps {
+        let (mark, style) = match running {
+            true => ("●", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            false => ("○", Style::default().fg(Color::DarkGray)),
+        };
+        pumps.push(Span::styled(format!("{} {}  ", mark, ingredient), style));
+    }
+    let emergency_stop = match view.emergency_stop_pressed {
+        true => Span::styled("PRESSED", Style::default().fg(Color::White).bg(Color::Red)),
+        false => Span::raw("released"),
+    };
+
+    let status = Paragraph::new(vec![
+        Line::from(vec![Span::raw("Safety: "), Span::styled(format!("{:?}", view.safety), safety_style(view.safety))]),
+        Line::from(format!("Milk: {}  heater {:.0}%", temperature, view.heater_power * 100.0)),
+        Line::from(pumps),
+        Line::from(vec![Span::raw("E-stop: "), emergency_stop]),
+        Line::from(format!("Orders waiting: {}", view.waiting)),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("HotChocolaBot"));
+    frame.render_widget(status, status_area);
+
+    let mut shortcuts: Vec<String> = view.recipes.iter().take(9).enumerate()
+        .map(|(i, recipe)| format!("{} {}", i + 1, recipe))
+        .collect();
+    shortcuts.extend(["y/n answer", "e stop", "r release", "q quit"].map(str::to_string));
+    let keys_widget = Paragraph::new(shortcuts.join("  "))
+        .block(Block::default().borders(Borders::ALL).title("Keys"));
+    frame.render_widget(keys_widget, keys);
+
+    let shown = log.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = view.log.iter()
+        .skip(view.log.len().saturating_sub(shown))
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Log")), log);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn view() -> DashboardView {
+        DashboardView {
+            lcd: vec!["Adding milk...  ".to_string(), "#1 (0 waiting)  ".to_string()],
+            safety: SafetyStates::Operating,
+            temperature: Some(64.5),
+            heater_power: 0.4,
+            pumps: vec![(Ingredient::Cocoa, false), (Ingredient::Milk, true), (Ingredient::Sugar, false)],
+            emergency_stop_pressed: false,
+            waiting: 0,
+            recipes: vec!["rich".to_string(), "standard".to_string()],
+            log: vec!["INFO Dispensing milk for 6250ms".to_string()],
+        }
+    }
+
+    fn render(view: &DashboardView) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|frame| draw(frame, view)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_dashboard_shows_lcd_and_state() {
+        let screen = render(&view());
+
+        assert!(screen.contains("│Adding milk...  │"));
+        assert!(screen.contains("Safety: Operating"));
+        assert!(screen.contains("Milk: 64.5°C  heater 40%"));
+        assert!(screen.contains("○ cocoa  ● milk  ○ sugar"));
+        assert!(screen.contains("1 rich  2 standard"));
+        assert!(screen.contains("Dispensing milk for 6250ms"));
+    }
+
+    #[test]
+    fn test_keys_order_and_stop() {
+        let mut hardware = ControllerHardware::mock();
+        let mut dashboard = Dashboard::attach(&mut hardware, &crate::control::tests::sim_config(), LogPane::new());
+        let orders = OrderQueue::new();
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        assert!(dashboard.handle_key(press(KeyCode::Char('2')), &orders));
+        assert_eq!(orders.waiting(), 1);
+
+        assert!(dashboard.handle_key(press(KeyCode::Char('e')), &orders));
+        assert!(dashboard.emergency_stop.is_pressed_now());
+
+        assert!(!dashboard.handle_key(press(KeyCode::Char('q')), &orders));
+    }
+
+    #[test]
+    fn test_log_pane_splits_lines() {
+        let pane = LogPane::new();
+        let mut writer = pane.writer();
+        write!(writer, "first\nsec").unwrap();
+        writeln!(writer, "ond").unwrap();
+
+        assert_eq!(pane.tail(5), ["first", "second"]);
+    }
+}
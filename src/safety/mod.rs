@@ -3,22 +3,55 @@
 //! Implements phase-separated safety checks and state machine-based
 //! operation validation to prevent unsafe conditions.
 
-use anyhow::{Result, Context};
+pub mod thermal;
+
+use anyhow::Result;
 use smlang::statemachine;
-use tracing::{warn, error, info};
-use crate::config::SafetyConfig;
+use tokio::sync::watch;
+use tokio::time::Duration;
+use tracing::{debug, warn, error, info};
+use crate::config::{Ingredient, SafetyConfig};
+use crate::error::HotChocolaBotError;
 use crate::control::DispenseController;
+use crate::control::thermostat::{SharedHeater, SharedSensor};
+use crate::safety::thermal::{ThermalFault, ThermalWatchdog, ThermalWatchdogHandle};
 
 /// Safety monitor implementing CNO principles
 pub struct SafetyMonitor {
     config: SafetyConfig,
-    state_machine: SafetyStateMachine,
+    state_machine: SafetyStateMachine<SafetyContext>,
     emergency_stop_triggered: bool,
     consecutive_failures: u32,
+    last_preflight: Vec<SafetyCheckResult>,
+    state_tx: watch::Sender<SafetyStates>,
+}
+
+/// An operation overran `SafetyConfig::operation_timeout` and was aborted
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Operation did not finish within {timeout_secs}s (stuck at: {step})")]
+pub struct OperationTimeout {
+    pub timeout_secs: u64,
+    /// Step the operation was on when the deadline passed
+    pub step: String,
 }
 
+/// Number of readings taken by the temperature sensor check
+const SENSOR_CHECK_SAMPLES: usize = 5;
+
+/// Delay between temperature sensor check readings
+const SENSOR_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Range of readings a working sensor can produce in a room-temperature rig
+const PLAUSIBLE_TEMPERATURE: std::ops::RangeInclusive<f32> = -10.0..=100.0;
+
+/// Largest spread between consecutive check readings of a settled sensor (°C)
+const MAX_SENSOR_SPREAD: f32 = 2.0;
+
 // Define safety state machine using smlang
 statemachine! {
+    name: Safety,
+    derive_states: [Debug, Clone, Copy],
+    derive_events: [Debug, Clone, Copy],
     transitions: {
         *Uninitialized + Initialize / initialize_checks = Initialized,
         Initialized + PassPreflight / run_preflight = Safe,
@@ -28,6 +61,8 @@ pub struct SafetyMonitor {
         Operating + CompleteOperation / finalize_operation = Safe,
         Operating + DetectAnomaly / handle_anomaly = Anomaly,
         Operating + EmergencyStop = Unsafe,
+        Initialized + EmergencyStop = Unsafe,
+        Anomaly + EmergencyStop = Unsafe,
         Anomaly + Recover / attempt_recovery = Safe,
         Anomaly + FailRecovery = Unsafe,
         Unsafe + Reset / perform_reset = Initialized,
@@ -35,146 +70,334 @@ pub struct SafetyMonitor {
 }
 
 /// Safety check results
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SafetyCheckResult {
+    pub check: &'static str,
     pub passed: bool,
     pub message: String,
     pub severity: SafetySeverity,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SafetySeverity {
     Info,
     Warning,
     Critical,
 }
 
+impl SafetyCheckResult {
+    fn pass(check: &'static str, message: impl Into<String>) -> Self {
+        Self { check, passed: true, message: message.into(), severity: SafetySeverity::Info }
+    }
+
+    /// Passed, but worth the operator's attention
+    fn warning(check: &'static str, message: impl Into<String>) -> Self {
+        Self { check, passed: true, message: message.into(), severity: SafetySeverity::Warning }
+    }
+
+    fn fail(check: &'static str, severity: SafetySeverity, message: impl Into<String>) -> Self {
+        Self { check, passed: false, message: message.into(), severity }
+    }
+}
+
 impl SafetyMonitor {
     /// Create new safety monitor
     pub fn new(config: &SafetyConfig) -> Result<Self> {
+        let mut state_machine = SafetyStateMachine::new(SafetyContext);
+        state_machine.process_event(SafetyEvents::Initialize)
+            .map_err(|e| HotChocolaBotError::NotReady(format!("safety state machine failed to initialize: {:?}", e)))?;
+        let (state_tx, _) = watch::channel(SafetyStates::Initialized);
+
         Ok(Self {
             config: config.clone(),
-            state_machine: SafetyStateMachine::new(),
+            state_machine,
             emergency_stop_triggered: false,
             consecutive_failures: 0,
+            last_preflight: Vec::new(),
+            state_tx,
         })
     }
 
+    /// Current state of the safety state machine
+    pub fn state(&self) -> SafetyStates {
+        *self.state_machine.state()
+    }
+
+    /// Follow state changes (e.g. to drive the status LED)
+    pub fn subscribe(&self) -> watch::Receiver<SafetyStates> {
+        self.state_tx.subscribe()
+    }
+
+    /// Drive the state machine, refusing the operation if the event is illegal
+    fn transition(&mut self, event: SafetyEvents) -> Result<SafetyStates> {
+        let from = self.state();
+
+        match self.state_machine.process_event(event) {
+            Ok(to) => {
+                let to = *to;
+                if self.config.verbose_logging {
+                    info!(telemetry = "transition", from = ?from, event = ?event, to = ?to,
+                          "Safety state: {:?} --{:?}--> {:?}", from, event, to);
+                } else {
+                    debug!(telemetry = "transition", from = ?from, event = ?event, to = ?to,
+                           "Safety state: {:?} --{:?}--> {:?}", from, event, to);
+                }
+                self.state_tx.send_replace(to);
+                Ok(to)
+            }
+            Err(e) => {
+                warn!("Refused {:?} in state {:?}: {:?}", event, from, e);
+                Err(HotChocolaBotError::NotReady(format!("{:?} is not permitted in safety state {:?}", event, from)).into())
+            }
+        }
+    }
+
     /// Run pre-flight safety checks
+    ///
+    /// Only permitted in the `Initialized` state; moves the machine to `Safe`
+    /// if the checks pass and to `Unsafe` otherwise.
     pub async fn run_preflight_checks(&mut self, controller: &DispenseController) -> Result<bool> {
+        if !matches!(self.state(), SafetyStates::Initialized) {
+            return Err(HotChocolaBotError::NotReady(format!(
+                "pre-flight checks require state Initialized (currently {:?})",
+                self.state()
+            )).into());
+        }
+
         info!("Running pre-flight safety checks...");
 
-        let checks = vec![
-            self.check_temperature_sensors(controller).await,
-            self.check_pump_connectivity(controller).await,
-            self.check_emergency_stop(controller).await,
-            self.check_power_supply(controller).await,
+        let checks = [
+            ("temperature_sensor", self.check_temperature_sensors(controller).await),
+            ("pumps", self.check_pump_connectivity(controller).await),
+            ("emergency_stop", self.check_emergency_stop(controller).await),
+            ("power_supply", self.check_power_supply(controller).await),
+            ("reservoirs", self.check_reservoirs(controller).await),
+            ("maintenance", Ok(Self::check_maintenance(controller))),
         ];
 
         let mut all_passed = true;
         let mut has_critical = false;
+        self.last_preflight.clear();
 
-        for check in checks {
-            match check {
-                Ok(result) => {
-                    if !result.passed {
-                        all_passed = false;
-                        match result.severity {
-                            SafetySeverity::Info => info!("Check: {}", result.message),
-                            SafetySeverity::Warning => warn!("Check: {}", result.message),
-                            SafetySeverity::Critical => {
-                                error!("CRITICAL: {}", result.message);
-                                has_critical = true;
-                            }
-                        }
-                    } else {
-                        info!("✓ {}", result.message);
+        for (name, check) in checks {
+            // A check that could not even run counts as a critical failure
+            let result = check.unwrap_or_else(|e| {
+                SafetyCheckResult::fail(name, SafetySeverity::Critical, format!("Check could not run: {:#}", e))
+            });
+
+            let (check, passed, severity) = (result.check, result.passed, result.severity);
+            if !result.passed {
+                all_passed = false;
+                match result.severity {
+                    SafetySeverity::Info => info!(telemetry = "safety_check", check, passed, severity = ?severity,
+                                                  "Check {}: {}", result.check, result.message),
+                    SafetySeverity::Warning => warn!(telemetry = "safety_check", check, passed, severity = ?severity,
+                                                     "Check {}: {}", result.check, result.message),
+                    SafetySeverity::Critical => {
+                        error!(telemetry = "safety_check", check, passed, severity = ?severity,
+                               "CRITICAL: {}: {}", result.check, result.message);
+                        has_critical = true;
                     }
                 }
-                Err(e) => {
-                    error!("Check failed with error: {:?}", e);
-                    all_passed = false;
-                    has_critical = true;
-                }
+            } else if result.severity == SafetySeverity::Warning {
+                warn!(telemetry = "safety_check", check, passed, severity = ?severity,
+                      "Check {}: {}", result.check, result.message);
+            } else {
+                info!(telemetry = "safety_check", check, passed, severity = ?severity, "✓ {}", result.message);
             }
+
+            self.last_preflight.push(result);
         }
 
         if all_passed {
             info!("All pre-flight checks passed");
+            self.transition(SafetyEvents::PassPreflight)?;
             Ok(true)
         } else if has_critical {
             error!("Critical safety checks failed - system unsafe");
+            self.transition(SafetyEvents::FailPreflight)?;
             Ok(false)
         } else {
             warn!("Some checks failed but system may operate with caution");
+            self.transition(SafetyEvents::PassPreflight)?;
             Ok(true)
         }
     }
 
+    /// Results of the most recent pre-flight run
+    pub fn last_preflight(&self) -> &[SafetyCheckResult] {
+        &self.last_preflight
+    }
+
+    /// The checks that failed in the most recent pre-flight run, as a typed error
+    pub fn preflight_error(&self) -> anyhow::Error {
+        let failed: Vec<_> = self.last_preflight.iter()
+            .filter(|result| !result.passed)
+            .map(|result| result.check)
+            .collect();
+        HotChocolaBotError::PreflightFailed(failed.join(", ")).into()
+    }
+
     /// Check temperature sensors are functioning
-    async fn check_temperature_sensors(&self, _controller: &DispenseController) -> Result<SafetyCheckResult> {
-        // In real implementation, would read from actual sensor
-        // For now, simulate the check
-        Ok(SafetyCheckResult {
-            passed: true,
-            message: "Temperature sensors operational".to_string(),
-            severity: SafetySeverity::Info,
-        })
+    ///
+    /// Takes several readings and requires each to succeed, be physically
+    /// plausible and agree with its neighbours.
+    async fn check_temperature_sensors(&self, controller: &DispenseController) -> Result<SafetyCheckResult> {
+        const CHECK: &str = "temperature_sensor";
+
+        if !controller.temperature_sensor_healthy().await {
+            return Ok(SafetyCheckResult::fail(CHECK, SafetySeverity::Critical, "Temperature sensor reports unhealthy"));
+        }
+
+        let mut readings = Vec::with_capacity(SENSOR_CHECK_SAMPLES);
+        for i in 0..SENSOR_CHECK_SAMPLES {
+            if i > 0 {
+                tokio::time::sleep(SENSOR_CHECK_INTERVAL).await;
+            }
+
+            match controller.read_temperature().await {
+                Ok(temp) => readings.push(temp),
+                Err(e) => {
+                    return Ok(SafetyCheckResult::fail(
+                        CHECK,
+                        SafetySeverity::Critical,
+                        format!("Temperature read {} of {} failed: {:#}", i + 1, SENSOR_CHECK_SAMPLES, e),
+                    ));
+                }
+            }
+        }
+
+        if let Some(&bad) = readings.iter().find(|t| !PLAUSIBLE_TEMPERATURE.contains(*t)) {
+            return Ok(SafetyCheckResult::fail(
+                CHECK,
+                SafetySeverity::Critical,
+                format!("Implausible temperature reading {:.1}°C", bad),
+            ));
+        }
+
+        let spread = readings.windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max);
+        if spread > MAX_SENSOR_SPREAD {
+            return Ok(SafetyCheckResult::fail(
+                CHECK,
+                SafetySeverity::Critical,
+                format!("Temperature readings unstable (jumped {:.1}°C between samples)", spread),
+            ));
+        }
+
+        let mean = readings.iter().sum::<f32>() / readings.len() as f32;
+        Ok(SafetyCheckResult::pass(CHECK, format!("Temperature sensor operational ({:.1}°C)", mean)))
     }
 
     /// Check pump connectivity
-    async fn check_pump_connectivity(&self, _controller: &DispenseController) -> Result<SafetyCheckResult> {
-        // Simulate checking GPIO connectivity
-        Ok(SafetyCheckResult {
-            passed: true,
-            message: "All pumps connected and responsive".to_string(),
-            severity: SafetySeverity::Info,
-        })
+    ///
+    /// Pulses each pump briefly; a pump must account the runtime and report
+    /// stopped again afterwards.
+    async fn check_pump_connectivity(&self, controller: &DispenseController) -> Result<SafetyCheckResult> {
+        const CHECK: &str = "pumps";
+        let pulse_ms = self.config.preflight_pump_pulse_ms;
+
+        if pulse_ms == 0 {
+            return Ok(SafetyCheckResult::warning(CHECK, "Pump test pulse disabled in config"));
+        }
+
+        for ingredient in Ingredient::ALL {
+            let pulse = match controller.pulse_pump(ingredient, pulse_ms).await {
+                Ok(pulse) => pulse,
+                Err(e) => return Ok(SafetyCheckResult::fail(CHECK, SafetySeverity::Critical, format!("{:#}", e))),
+            };
+
+            if pulse.running_after {
+                return Ok(SafetyCheckResult::fail(
+                    CHECK,
+                    SafetySeverity::Critical,
+                    format!("{} pump still running after test pulse", ingredient),
+                ));
+            }
+
+            if pulse.runtime_ms == 0 {
+                return Ok(SafetyCheckResult::fail(
+                    CHECK,
+                    SafetySeverity::Critical,
+                    format!("{} pump did not respond to a {}ms test pulse", ingredient, pulse_ms),
+                ));
+            }
+        }
+
+        Ok(SafetyCheckResult::pass(CHECK, "All pumps connected and responsive"))
     }
 
     /// Check emergency stop button
-    async fn check_emergency_stop(&self, _controller: &DispenseController) -> Result<SafetyCheckResult> {
+    async fn check_emergency_stop(&self, controller: &DispenseController) -> Result<SafetyCheckResult> {
+        const CHECK: &str = "emergency_stop";
+
         if !self.config.emergency_stop_enabled {
-            return Ok(SafetyCheckResult {
-                passed: true,
-                message: "Emergency stop disabled in config".to_string(),
-                severity: SafetySeverity::Warning,
-            });
+            return Ok(SafetyCheckResult::warning(CHECK, "Emergency stop disabled in config"));
         }
 
-        Ok(SafetyCheckResult {
-            passed: true,
-            message: "Emergency stop button operational".to_string(),
-            severity: SafetySeverity::Info,
-        })
+        match controller.emergency_stop_pressed().await {
+            Some(false) => Ok(SafetyCheckResult::pass(CHECK, "Emergency stop button released")),
+            Some(true) => Ok(SafetyCheckResult::fail(
+                CHECK,
+                SafetySeverity::Critical,
+                "Emergency stop is pressed - release it before operating",
+            )),
+            None => Ok(SafetyCheckResult::fail(CHECK, SafetySeverity::Critical, "No emergency stop input available")),
+        }
     }
 
     /// Check power supply stability
-    async fn check_power_supply(&self, _controller: &DispenseController) -> Result<SafetyCheckResult> {
-        // In real implementation, would check voltage levels
-        Ok(SafetyCheckResult {
-            passed: true,
-            message: "Power supply stable".to_string(),
-            severity: SafetySeverity::Info,
-        })
+    async fn check_power_supply(&self, controller: &DispenseController) -> Result<SafetyCheckResult> {
+        const CHECK: &str = "power_supply";
+        let (min, max) = (self.config.min_supply_voltage, self.config.max_supply_voltage);
+
+        let volts = match controller.supply_voltage().await {
+            Ok(volts) => volts,
+            Err(e) => return Ok(SafetyCheckResult::fail(CHECK, SafetySeverity::Critical, format!("{:#}", e))),
+        };
+
+        if volts < min || volts > max {
+            return Ok(SafetyCheckResult::fail(
+                CHECK,
+                SafetySeverity::Critical,
+                format!("Supply voltage {:.2}V outside {:.1}-{:.1}V", volts, min, max),
+            ));
+        }
+
+        Ok(SafetyCheckResult::pass(CHECK, format!("Power supply stable ({:.2}V)", volts)))
+    }
+
+    /// Warn about reservoirs that need refilling before the first order
+    async fn check_reservoirs(&self, controller: &DispenseController) -> Result<SafetyCheckResult> {
+        const CHECK: &str = "reservoirs";
+
+        let low: Vec<_> = controller.low_reservoirs().await?.iter().map(Ingredient::name).collect();
+        if low.is_empty() {
+            Ok(SafetyCheckResult::pass(CHECK, "Reservoirs above reserve"))
+        } else {
+            Ok(SafetyCheckResult::warning(CHECK, format!("Refill needed: {}", low.join(", "))))
+        }
+    }
+
+    /// Warn about pumps due for maintenance; never fails the pre-flight
+    fn check_maintenance(controller: &DispenseController) -> SafetyCheckResult {
+        const CHECK: &str = "maintenance";
+
+        let due: Vec<_> = controller.maintenance_due().iter().map(ToString::to_string).collect();
+        if due.is_empty() {
+            SafetyCheckResult::pass(CHECK, "No maintenance due")
+        } else {
+            SafetyCheckResult::warning(CHECK, format!("Maintenance due: {}", due.join("; ")))
+        }
     }
 
     /// Validate temperature is within safe range
     pub fn validate_temperature(&self, temp: f32) -> Result<()> {
-        if temp < self.config.min_temperature {
-            anyhow::bail!(
-                "Temperature too low: {}°C (min: {}°C)",
-                temp,
-                self.config.min_temperature
-            );
-        }
+        let (min, max) = (self.config.min_temperature, self.config.max_temperature);
 
-        if temp > self.config.max_temperature {
-            anyhow::bail!(
-                "Temperature too high: {}°C (max: {}°C)",
-                temp,
-                self.config.max_temperature
-            );
+        if !(min..=max).contains(&temp) {
+            return Err(HotChocolaBotError::TemperatureOutOfRange { temp, min, max }.into());
         }
 
         Ok(())
@@ -189,15 +412,80 @@ pub fn is_emergency_stop(&self) -> bool {
     pub fn trigger_emergency_stop(&mut self, reason: &str) {
         error!("EMERGENCY STOP TRIGGERED: {}", reason);
         self.emergency_stop_triggered = true;
+
+        if let Err(e) = self.state_machine.process_event(SafetyEvents::EmergencyStop) {
+            // Already Unsafe (or not yet initialised) - the latch above still holds
+            warn!("EmergencyStop transition not taken: {:?}", e);
+        }
+        self.state_tx.send_replace(self.state());
     }
 
-    /// Reset emergency stop after manual intervention
+    /// Start the thermal watchdog over the given sensor and heater
+    pub fn spawn_thermal_watchdog(&self, sensor: SharedSensor, heater: SharedHeater) -> ThermalWatchdogHandle {
+        ThermalWatchdogHandle::spawn(
+            ThermalWatchdog::new(&self.config),
+            sensor,
+            heater,
+            Duration::from_millis(self.config.thermal_check_interval_ms),
+        )
+    }
+
+    /// React to a fault reported by the thermal watchdog
+    pub fn handle_thermal_fault(&mut self, fault: &ThermalFault) {
+        self.trigger_emergency_stop(&format!("Thermal fault: {}", fault));
+    }
+
+    /// Begin a hardware operation (Safe -> Operating)
+    ///
+    /// Every dispense or pump run must call this first; it fails unless the
+    /// system has passed pre-flight and is idle in `Safe`.
+    pub fn begin_operation(&mut self) -> Result<()> {
+        if self.emergency_stop_triggered {
+            return Err(HotChocolaBotError::EmergencyStop("active - cannot start operation".to_string()).into());
+        }
+
+        self.transition(SafetyEvents::StartOperation)?;
+        Ok(())
+    }
+
+    /// Finish a hardware operation successfully (Operating -> Safe)
+    pub fn complete_operation(&mut self) -> Result<()> {
+        self.transition(SafetyEvents::CompleteOperation)?;
+        self.record_success();
+        Ok(())
+    }
+
+    /// Report an anomaly during an operation (Operating -> Anomaly)
+    pub fn report_anomaly(&mut self, reason: &str) -> Result<()> {
+        warn!("Anomaly detected: {}", reason);
+        self.consecutive_failures += 1;
+        self.transition(SafetyEvents::DetectAnomaly)?;
+        Ok(())
+    }
+
+    /// Attempt to recover from an anomaly (Anomaly -> Safe, or Unsafe if not possible)
+    pub fn recover(&mut self) -> Result<()> {
+        if self.emergency_stop_triggered || self.consecutive_failures > 3 {
+            self.transition(SafetyEvents::FailRecovery)?;
+            return Err(HotChocolaBotError::ManualResetRequired("recovery not possible".to_string()).into());
+        }
+
+        self.transition(SafetyEvents::Recover)?;
+        Ok(())
+    }
+
+    /// Reset emergency stop after manual intervention (Unsafe -> Initialized)
+    ///
+    /// Pre-flight checks must pass again before the next operation.
     pub fn reset_emergency_stop(&mut self) -> Result<()> {
         if self.consecutive_failures > 3 {
-            anyhow::bail!("Too many consecutive failures. Manual inspection required.");
+            return Err(HotChocolaBotError::ManualResetRequired(
+                "too many consecutive failures, inspect the machine".to_string()
+            ).into());
         }
 
         info!("Resetting emergency stop");
+        self.transition(SafetyEvents::Reset)?;
         self.emergency_stop_triggered = false;
         self.consecutive_failures += 1;
         Ok(())
@@ -209,38 +497,53 @@ pub fn record_success(&mut self) {
     }
 }
 
+/// Context for the safety state machine actions
+pub struct SafetyContext;
+
 // State machine action implementations
-fn initialize_checks(_context: &mut ()) -> () {
-    info!("Initializing safety checks");
-}
+impl SafetyStateMachineContext for SafetyContext {
+    fn initialize_checks(&mut self) -> Result<(), ()> {
+        info!("Initializing safety checks");
+        Ok(())
+    }
 
-fn run_preflight(_context: &mut ()) -> () {
-    info!("Running preflight");
-}
+    fn run_preflight(&mut self) -> Result<(), ()> {
+        info!("Running preflight");
+        Ok(())
+    }
 
-fn begin_operation(_context: &mut ()) -> () {
-    info!("Beginning operation");
-}
+    fn begin_operation(&mut self) -> Result<(), ()> {
+        info!("Beginning operation");
+        Ok(())
+    }
 
-fn finalize_operation(_context: &mut ()) -> () {
-    info!("Finalizing operation");
-}
+    fn finalize_operation(&mut self) -> Result<(), ()> {
+        info!("Finalizing operation");
+        Ok(())
+    }
 
-fn handle_anomaly(_context: &mut ()) -> () {
-    warn!("Handling anomaly");
-}
+    fn handle_anomaly(&mut self) -> Result<(), ()> {
+        warn!("Handling anomaly");
+        Ok(())
+    }
 
-fn attempt_recovery(_context: &mut ()) -> () {
-    info!("Attempting recovery");
-}
+    fn attempt_recovery(&mut self) -> Result<(), ()> {
+        info!("Attempting recovery");
+        Ok(())
+    }
 
-fn perform_reset(_context: &mut ()) -> () {
-    info!("Performing system reset");
+    fn perform_reset(&mut self) -> Result<(), ()> {
+        info!("Performing system reset");
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::BotConfig;
+    use crate::hardware::factory::ControllerHardware;
+    use crate::hardware::mock::{MockAdc, MockEmergencyStop, MockTemperatureSensor};
 
     #[test]
     fn test_temperature_validation() {
@@ -251,6 +554,14 @@ fn test_temperature_validation() {
             operation_timeout: 120,
             verbose_logging: true,
             emergency_stop_enabled: true,
+            thermal_check_interval_ms: 1000,
+            max_heating_rate: 5.0,
+            min_heating_rise: 1.0,
+            heating_check_window: 60,
+            stuck_sensor_timeout: 30,
+            min_supply_voltage: 11.0,
+            max_supply_voltage: 13.5,
+            preflight_pump_pulse_ms: 100,
         };
 
         let monitor = SafetyMonitor::new(&config).unwrap();
@@ -270,6 +581,14 @@ fn test_emergency_stop() {
             operation_timeout: 120,
             verbose_logging: true,
             emergency_stop_enabled: true,
+            thermal_check_interval_ms: 1000,
+            max_heating_rate: 5.0,
+            min_heating_rise: 1.0,
+            heating_check_window: 60,
+            stuck_sensor_timeout: 30,
+            min_supply_voltage: 11.0,
+            max_supply_voltage: 13.5,
+            preflight_pump_pulse_ms: 100,
         };
 
         let mut monitor = SafetyMonitor::new(&config).unwrap();
@@ -277,5 +596,117 @@ fn test_emergency_stop() {
         assert!(!monitor.is_emergency_stop());
         monitor.trigger_emergency_stop("Test");
         assert!(monitor.is_emergency_stop());
+        assert!(matches!(monitor.state_machine.state(), SafetyStates::Unsafe));
+    }
+
+    #[test]
+    fn test_thermal_fault_trips_emergency_stop() {
+        let config = crate::config::BotConfig::default().safety;
+        let mut monitor = SafetyMonitor::new(&config).unwrap();
+
+        monitor.handle_thermal_fault(&ThermalFault::OverTemperature { temp: 95.0, max: 90.0 });
+        assert!(monitor.is_emergency_stop());
+        assert!(matches!(monitor.state_machine.state(), SafetyStates::Unsafe));
+    }
+
+    #[test]
+    fn test_operation_lifecycle() {
+        let config = crate::config::BotConfig::default().safety;
+        let mut monitor = SafetyMonitor::new(&config).unwrap();
+        assert!(matches!(monitor.state(), SafetyStates::Initialized));
+
+        // Not yet through pre-flight
+        assert!(monitor.begin_operation().is_err());
+
+        monitor.transition(SafetyEvents::PassPreflight).unwrap();
+        monitor.begin_operation().unwrap();
+        assert!(matches!(monitor.state(), SafetyStates::Operating));
+
+        // Only one operation at a time
+        assert!(monitor.begin_operation().is_err());
+
+        monitor.report_anomaly("test").unwrap();
+        assert!(matches!(monitor.state(), SafetyStates::Anomaly));
+        monitor.recover().unwrap();
+        assert!(matches!(monitor.state(), SafetyStates::Safe));
+    }
+
+    #[test]
+    fn test_reset_requires_unsafe_and_returns_to_initialized() {
+        let config = crate::config::BotConfig::default().safety;
+        let mut monitor = SafetyMonitor::new(&config).unwrap();
+
+        assert!(monitor.reset_emergency_stop().is_err());
+
+        monitor.trigger_emergency_stop("Test");
+        monitor.reset_emergency_stop().unwrap();
+        assert!(!monitor.is_emergency_stop());
+        assert!(matches!(monitor.state(), SafetyStates::Initialized));
+    }
+
+    async fn preflight_with(hardware: ControllerHardware) -> (SafetyMonitor, bool) {
+        let config = BotConfig::default();
+        let controller = DispenseController::with_hardware(config.clone(), hardware);
+        let mut monitor = SafetyMonitor::new(&config.safety).unwrap();
+        let passed = monitor.run_preflight_checks(&controller).await.unwrap();
+        (monitor, passed)
+    }
+
+    fn failed_checks(monitor: &SafetyMonitor) -> Vec<&'static str> {
+        monitor.last_preflight().iter().filter(|r| !r.passed).map(|r| r.check).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_preflight_passes_on_healthy_rig() {
+        let (monitor, passed) = preflight_with(ControllerHardware::mock()).await;
+
+        assert!(passed);
+        assert!(matches!(monitor.state(), SafetyStates::Safe));
+        assert_eq!(monitor.last_preflight().len(), 6);
+        assert!(failed_checks(&monitor).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_preflight_fails_with_emergency_stop_pressed() {
+        let mut estop = MockEmergencyStop::new();
+        estop.press();
+        let hardware = ControllerHardware {
+            emergency_stop: Some(Box::new(estop)),
+            ..ControllerHardware::mock()
+        };
+
+        let (monitor, passed) = preflight_with(hardware).await;
+
+        assert!(!passed);
+        assert!(matches!(monitor.state(), SafetyStates::Unsafe));
+        assert_eq!(failed_checks(&monitor), vec!["emergency_stop"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_preflight_fails_on_low_supply_voltage() {
+        // 9.6V rail through the 4:1 divider
+        let hardware = ControllerHardware {
+            adc: Box::new(MockAdc::new(2.4)),
+            ..ControllerHardware::mock()
+        };
+
+        let (monitor, passed) = preflight_with(hardware).await;
+
+        assert!(!passed);
+        assert_eq!(failed_checks(&monitor), vec!["power_supply"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_preflight_fails_on_implausible_temperature() {
+        // Disconnected sensor reading the bus as all ones
+        let hardware = ControllerHardware {
+            temp_sensor: Box::new(MockTemperatureSensor::new(127.9)),
+            ..ControllerHardware::mock()
+        };
+
+        let (monitor, passed) = preflight_with(hardware).await;
+
+        assert!(!passed);
+        assert_eq!(failed_checks(&monitor), vec!["temperature_sensor"]);
     }
 }
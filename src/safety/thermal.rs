@@ -0,0 +1,308 @@
+//! Thermal runaway and heater fault detection
+//!
+//! The static min/max window in `SafetyMonitor::validate_temperature` cannot
+//! spot a heater that has come loose from the jug, a sensor that has fallen
+//! out of the milk, or a relay that has welded shut. The watchdog looks at
+//! how the temperature *changes* relative to what the heater is doing.
+
+use crate::config::SafetyConfig;
+use crate::control::thermostat::{SharedHeater, SharedSensor};
+use std::collections::VecDeque;
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, error};
+
+/// Heater power above which the heater counts as "driven hard"
+const HEATING_POWER_THRESHOLD: f32 = 0.5;
+
+/// Fault conditions detected by the thermal watchdog
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ThermalFault {
+    #[error("Heater on for {window_secs}s but temperature rose only {rise:.1}°C")]
+    NotHeating { rise: f32, window_secs: u64 },
+
+    #[error("Temperature rising at {rate:.1}°C/s (max plausible {max_rate:.1}°C/s)")]
+    RunawayRate { rate: f32, max_rate: f32 },
+
+    #[error("Temperature sensor stuck at {value:.2}°C for {stuck_secs}s while heating")]
+    StuckSensor { value: f32, stuck_secs: u64 },
+
+    #[error("Temperature {temp:.1}°C above maximum {max:.1}°C")]
+    OverTemperature { temp: f32, max: f32 },
+
+    #[error("Temperature sensor read failed: {0}")]
+    SensorFailure(String),
+}
+
+/// A single watchdog observation
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    temp: f32,
+    power: f32,
+}
+
+/// Stateful detector fed with (time, temperature, heater power) samples
+pub struct ThermalWatchdog {
+    max_temperature: f32,
+    max_heating_rate: f32,
+    min_heating_rise: f32,
+    heating_check_window: Duration,
+    stuck_sensor_timeout: Duration,
+    history: VecDeque<Sample>,
+    unchanged_since: Option<Instant>,
+}
+
+impl ThermalWatchdog {
+    /// Create a watchdog using the thresholds from `SafetyConfig`
+    pub fn new(config: &SafetyConfig) -> Self {
+        Self {
+            max_temperature: config.max_temperature,
+            max_heating_rate: config.max_heating_rate,
+            min_heating_rise: config.min_heating_rise,
+            heating_check_window: Duration::from_secs(config.heating_check_window),
+            stuck_sensor_timeout: Duration::from_secs(config.stuck_sensor_timeout),
+            history: VecDeque::new(),
+            unchanged_since: None,
+        }
+    }
+
+    /// Feed one observation; returns a fault if one is detected
+    pub fn observe(&mut self, at: Instant, temp: f32, power: f32) -> Option<ThermalFault> {
+        let sample = Sample { at, temp, power };
+        let previous = self.history.back().copied();
+        self.history.push_back(sample);
+
+        if temp > self.max_temperature {
+            return Some(ThermalFault::OverTemperature { temp, max: self.max_temperature });
+        }
+
+        if let Some(fault) = previous.and_then(|prev| self.check_rate(prev, sample)) {
+            return Some(fault);
+        }
+
+        if let Some(fault) = self.check_stuck(previous, sample) {
+            return Some(fault);
+        }
+
+        self.check_heating()
+    }
+
+    /// Rising faster than the heater could physically manage
+    fn check_rate(&self, prev: Sample, sample: Sample) -> Option<ThermalFault> {
+        let dt = sample.at.duration_since(prev.at).as_secs_f32();
+        if dt <= 0.0 {
+            return None;
+        }
+
+        let rate = (sample.temp - prev.temp) / dt;
+        (rate > self.max_heating_rate).then_some(ThermalFault::RunawayRate {
+            rate,
+            max_rate: self.max_heating_rate,
+        })
+    }
+
+    /// Bit-identical readings for a long time while the heater is on
+    fn check_stuck(&mut self, prev: Option<Sample>, sample: Sample) -> Option<ThermalFault> {
+        let unchanged = prev.is_some_and(|p| p.temp == sample.temp && p.power > 0.0);
+
+        if !unchanged || sample.power <= 0.0 {
+            self.unchanged_since = None;
+            return None;
+        }
+
+        let since = *self.unchanged_since.get_or_insert(prev.map_or(sample.at, |p| p.at));
+        let stuck_for = sample.at.duration_since(since);
+
+        (stuck_for >= self.stuck_sensor_timeout).then_some(ThermalFault::StuckSensor {
+            value: sample.temp,
+            stuck_secs: stuck_for.as_secs(),
+        })
+    }
+
+    /// Heater driven hard for a whole window without a meaningful rise
+    fn check_heating(&mut self) -> Option<ThermalFault> {
+        let latest = *self.history.back()?;
+
+        // Keep just enough history to cover one window
+        while self.history.len() > 2
+            && latest.at.duration_since(self.history[1].at) >= self.heating_check_window
+        {
+            self.history.pop_front();
+        }
+
+        let oldest = *self.history.front()?;
+        let span = latest.at.duration_since(oldest.at);
+        if span < self.heating_check_window {
+            return None;
+        }
+
+        let driven_hard = self.history.iter().all(|s| s.power >= HEATING_POWER_THRESHOLD);
+        let rise = latest.temp - oldest.temp;
+
+        (driven_hard && rise < self.min_heating_rise).then_some(ThermalFault::NotHeating {
+            rise,
+            window_secs: span.as_secs(),
+        })
+    }
+}
+
+/// Handle to a running watchdog task
+pub struct ThermalWatchdogHandle {
+    fault: watch::Receiver<Option<ThermalFault>>,
+    task: JoinHandle<()>,
+}
+
+impl ThermalWatchdogHandle {
+    /// Start sampling sensor and heater every `interval`
+    ///
+    /// On a fault the heater is switched off immediately, before anyone is
+    /// notified, and the watchdog stops.
+    pub fn spawn(
+        mut watchdog: ThermalWatchdog,
+        sensor: SharedSensor,
+        heater: SharedHeater,
+        interval: Duration,
+    ) -> Self {
+        let (fault_tx, fault_rx) = watch::channel(None);
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+
+            loop {
+                ticker.tick().await;
+
+                let reading = sensor.lock().await.read_temperature().await;
+                let power = heater.lock().await.power();
+
+                let fault = match reading {
+                    Ok(temp) => {
+                        debug!("Thermal watchdog: {:.2}°C at {:.0}% power", temp, power * 100.0);
+                        watchdog.observe(Instant::now(), temp, power)
+                    }
+                    Err(e) => Some(ThermalFault::SensorFailure(e.to_string())),
+                };
+
+                if let Some(fault) = fault {
+                    error!("THERMAL FAULT: {}", fault);
+                    if let Err(e) = heater.lock().await.off().await {
+                        error!("Failed to switch heater off after thermal fault: {:?}", e);
+                    }
+                    let _ = fault_tx.send(Some(fault));
+                    return;
+                }
+            }
+        });
+
+        Self { fault: fault_rx, task }
+    }
+
+    /// Fault latched by the watchdog, if any
+    #[cfg(test)]
+    pub fn fault(&self) -> Option<ThermalFault> {
+        self.fault.borrow().clone()
+    }
+
+    /// Wait until the watchdog trips
+    pub async fn tripped(&mut self) -> ThermalFault {
+        loop {
+            if let Some(fault) = self.fault.borrow_and_update().clone() {
+                return fault;
+            }
+            if self.fault.changed().await.is_err() {
+                // Watchdog task ended without a fault; never resolve
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl Drop for ThermalWatchdogHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BotConfig;
+    use crate::hardware::{Heater, TemperatureSensor};
+    use crate::hardware::mock::{MockHeater, MockTemperatureSensor};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    fn watchdog() -> ThermalWatchdog {
+        ThermalWatchdog::new(&BotConfig::default().safety)
+    }
+
+    #[test]
+    fn test_normal_heating_passes() {
+        let mut wd = watchdog();
+        let start = Instant::now();
+
+        for i in 0..120 {
+            let fault = wd.observe(start + Duration::from_secs(i), 20.0 + i as f32 * 0.4, 1.0);
+            assert_eq!(fault, None, "false positive at {}s", i);
+        }
+    }
+
+    #[test]
+    fn test_heater_on_without_rise_detected() {
+        let mut wd = watchdog();
+        let start = Instant::now();
+
+        let fault = (0..120)
+            .find_map(|i| wd.observe(start + Duration::from_secs(i), 20.0 + (i % 2) as f32 * 0.1, 1.0));
+        assert!(matches!(fault, Some(ThermalFault::NotHeating { .. })));
+    }
+
+    #[test]
+    fn test_implausible_rate_detected() {
+        let mut wd = watchdog();
+        let start = Instant::now();
+
+        assert_eq!(wd.observe(start, 40.0, 0.5), None);
+        let fault = wd.observe(start + Duration::from_secs(1), 60.0, 0.5);
+        assert!(matches!(fault, Some(ThermalFault::RunawayRate { .. })));
+    }
+
+    #[test]
+    fn test_stuck_sensor_detected_only_while_heating() {
+        let mut wd = watchdog();
+        let start = Instant::now();
+
+        // Constant reading with heater off is fine (sitting at ambient)
+        for i in 0..120 {
+            assert_eq!(wd.observe(start + Duration::from_secs(i), 21.0, 0.0), None);
+        }
+
+        let mut wd = watchdog();
+        let fault = (0..120)
+            .find_map(|i| wd.observe(start + Duration::from_secs(i), 21.0, 0.3));
+        assert!(matches!(fault, Some(ThermalFault::StuckSensor { .. })));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watchdog_trips_on_mock_sensor_and_cuts_heater() {
+        // A sensor that never changes while the heater is at full power
+        let sensor: Box<dyn TemperatureSensor> = Box::new(MockTemperatureSensor::new(20.0));
+        let mut heater: Box<dyn Heater> = Box::new(MockHeater::new());
+        heater.set_power(1.0).await.unwrap();
+        let heater = Arc::new(Mutex::new(heater));
+
+        let mut handle = ThermalWatchdogHandle::spawn(
+            watchdog(),
+            Arc::new(Mutex::new(sensor)),
+            Arc::clone(&heater),
+            Duration::from_secs(1),
+        );
+
+        let fault = handle.tripped().await;
+        assert!(matches!(fault, ThermalFault::StuckSensor { .. }));
+        assert_eq!(handle.fault(), Some(fault));
+        assert_eq!(heater.lock().await.power(), 0.0);
+    }
+}